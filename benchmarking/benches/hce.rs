@@ -8,6 +8,7 @@ use engine::{
     math::{self, DefaultLmrParams, Probability, Ratio},
 };
 use std::{hint::black_box, time::Duration};
+use uom::si::{information::mebibyte, u64::Information};
 
 use criterion::{Criterion, criterion_group, criterion_main};
 use engine::{
@@ -16,7 +17,7 @@ use engine::{
         position::Position,
         search::mcts::{
             HceParts, MctsParts,
-            eval::hce::EvalInfo,
+            eval::hce::{EvalInfo, StaticEvaluator},
             node::{Height, Tree, VisitCount, node_state::Evaluated},
             search::TreeSearcher,
             select::puct::PuctSelector,
@@ -34,7 +35,8 @@ pub fn policy(c: &mut Criterion) {
 
     let moves = pos.collect_legals(MoveList::new());
     let params = C_MctsHceParams;
-    let eval = EvalInfo::new(moves, &mut pos, params.shared());
+    let mut static_eval = StaticEvaluator::new(Information::new::<mebibyte>(1), params.clone().shared());
+    let eval = EvalInfo::new(moves, &mut pos, params.shared(), &mut static_eval);
 
     let mut buf = List::<{ MAX_LEGAL_MOVES }, f32>::new();
 
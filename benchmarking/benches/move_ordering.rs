@@ -76,6 +76,7 @@ fn bench_positions(c: &mut Criterion, group_name: &str, target: RtStage) {
             tt_move: Move::null(),
             killers: Killers::default(),
             hh: &mut hh,
+            prev_moves: Default::default(),
             color: pos.get_turn(),
             phase: TaperValue::from_position(pos.piece_info()),
             params: C_IdNnueParams,
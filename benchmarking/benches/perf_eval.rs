@@ -2,11 +2,13 @@ use std::fmt::Display;
 
 use engine::{
     core::{
+        eval::nnue::{AccUpdates, AccumulatorPair, EagerAccUpdates, LazyAccUpdates},
         r#move::Move,
         move_iter::sliding_piece::magics,
-        params::C_MctsHceParams,
-        position::{EpdLineImport, Position},
+        params::{C_IdNnueParams, C_MctsHceParams, IParams},
+        position::{EpdLineImport, PieceInfoObserver, Position},
         search::{
+            id::{self, NnueEvaluator},
             limit::UciLimit,
             mcts::{
                 self, HceParts, MctsConfig, mcts,
@@ -17,12 +19,13 @@ use engine::{
         },
         zobrist,
     },
-    math::entropy,
+    math::{self, entropy},
     misc::{CancellationToken, DebugMode},
     uci::tokens::Tokenizer,
 };
 use itertools::Itertools;
 use tabled::{Table, Tabled};
+use uom::si::{information::mebibyte, u64::Information};
 
 struct Test {
     id: String,
@@ -231,6 +234,8 @@ fn main() {
         print_dataset(&tests);
     }
 
+    compare_nnue_updates();
+
     let runner = MctsHceRunner;
 
     let solutions = tests.into_iter().map(|test| run_perf_eval(test, runner)).collect::<Vec<_>>();
@@ -238,6 +243,48 @@ fn main() {
     print_solution(&solutions);
 }
 
+/// Runs the bench of the id search with the lazy and the eager accumulator
+/// updates of the nnue. Both evaluate the same, so they search the same nodes
+/// and only differ in speed.
+fn compare_nnue_updates() {
+    #[derive(Tabled)]
+    struct UpdatesRow {
+        #[tabled(rename = "Updates")]
+        updates: &'static str,
+        #[tabled(rename = "Nodes")]
+        nodes: u64,
+        #[tabled(rename = "Time")]
+        time: String,
+        #[tabled(rename = "NPS")]
+        nps: u64,
+    }
+
+    fn bench<Updates: AccUpdates>(updates: &'static str) -> UpdatesRow
+    where
+        AccumulatorPair<Updates>: PieceInfoObserver,
+    {
+        let mut tt = id::TT::new_of_size(Information::new::<mebibyte>(16));
+        let mut hh = id::HH::new();
+        let mut eval = NnueEvaluator::<Updates>::default();
+        let (nodes, time) = id::bench::<C_IdNnueParams>(id::BENCH_DEPTH, &mut tt, &mut hh, &mut eval, C_IdNnueParams.shared());
+
+        UpdatesRow {
+            updates,
+            nodes,
+            time: format!("{:.2}s", time.as_secs_f64()),
+            nps: nodes * 1000 / (time.as_millis() as u64).max(1),
+        }
+    }
+
+    math::init(C_IdNnueParams);
+
+    let rows = vec![bench::<LazyAccUpdates>("lazy"), bench::<EagerAccUpdates>("eager")];
+    assert_eq!(rows[0].nodes, rows[1].nodes, "The updates should not change the search.");
+
+    println!("\nNNUE Accumulator Updates:\n");
+    println!("{}\n", Table::new(rows));
+}
+
 fn print_solution<D: Display>(results: &[TestResult<D>]) {
     #[derive(Tabled)]
     struct SolutionRow {
@@ -332,6 +332,10 @@ impl Bitboard {
 
     pub const fn edges() -> Self { Self { v: !0x007E7E7E7E7E7E00_u64 } }
 
+    pub const fn light_squares() -> Self { Self { v: 0x55AA55AA55AA55AA_u64 } }
+
+    pub const fn dark_squares() -> Self { Self { v: !Self::light_squares().v } }
+
     pub fn into_floats(self, upside_down: bool) -> Floats {
         let mut data: Floats = Default::default();
         for sq in squares::A1_C..squares::H8_C {
@@ -1,5 +1,5 @@
 use crate::{
-    core::params::TunableConfiguration,
+    core::{params::TunableConfiguration, position::Validation},
     misc::{InvalidValueError, ValueOutOfRangeError},
 };
 use std::{
@@ -351,6 +351,9 @@ pub struct Configuration {
     /// Path to quantized nnue weights file. If empty, uses shipped nnue.
     nnue_path: ConfigOption<StringOption>,
 
+    /// Size of the pawn-structure hash table of the hand-crafted evaluation.
+    pawn_hash: ConfigOption<Spin<UciMebibyte>>,
+
     /// Whether to keep the game tree in between `go`-commands.
     game_tree_caching: ConfigOption<Check>,
 
@@ -363,6 +366,9 @@ pub struct Configuration {
     /// time until the opponent actually moves).
     ponder: ConfigOption<Check>,
 
+    /// How strictly positions given by the `position` command are validated.
+    position_validation: ConfigOption<Combo>,
+
     pub tunable: TunableConfiguration,
 }
 
@@ -404,9 +410,11 @@ impl Configuration {
                 dirichlet_epsilon: ConfigOption::new("dirichlet-epsilon", Spin::<UciPercent>::new(_ratio(0.25), _ratio(0.), _ratio(1.))),
                 weights_path: ConfigOption::new("weights-path", StringOption::new("./weights")),
                 nnue_path: ConfigOption::new("nnue-path", StringOption::new("")),
+                pawn_hash: ConfigOption::new("pawn-hash", Spin::<UciMebibyte>::new(_mebibyte(1), _mebibyte(1), _mebibyte(1024))),
                 game_tree_caching: ConfigOption::new("game-tree-caching", Check::new(true)),
                 gui_lag: ConfigOption::new("gui-lag", Spin::<UciMillis>::new(_millis(100), _millis(1), _millis(10_000))),
                 ponder: ConfigOption::new("ponder", Check::new(true)),
+                position_validation: ConfigOption::new("position-validation", Combo::new("strict", vec!["strict", "lenient"])),
                 tunable: TunableConfiguration::default()
             },
         }
@@ -444,9 +452,16 @@ impl Configuration {
     pub fn dirichlet_epsilon(&self) -> f32 { self.dirichlet_epsilon.value.get::<ratio>() }
     pub fn weights_path(&self) -> &str { &self.weights_path.value }
     pub fn nnue_path(&self) -> &str { &self.nnue_path.value }
+    pub fn pawn_hash(&self) -> Information { self.pawn_hash.value }
     pub fn game_tree_caching(&self) -> bool { self.game_tree_caching.value }
     pub fn gui_lag(&self) -> u16 { self.gui_lag.value.get::<millisecond>() as u16 }
     pub fn ponder(&self) -> bool { self.ponder.value }
+    pub fn position_validation(&self) -> Validation {
+        match self.position_validation.value.as_str() {
+            "lenient" => Validation::Lenient,
+            _ => Validation::Strict,
+        }
+    }
 
     #[allow(clippy::unit_arg)]
     pub fn set(&mut self, name: &str, value: &str) -> Result<(), Box<dyn Error>> {
@@ -461,6 +476,7 @@ impl Configuration {
             "nalimovpath" => return Ok(self.uci_nalimov_path.set(value)),
             "nalimovcache" => return self.uci_nalimov_cache.set(value),
             "ownbook" => return self.uci_ownbook.set(value),
+            "pawn-hash" => return self.pawn_hash.set(value),
             "multipv" => return self.uci_multipv.set(value),
             "uci_showcurrline" => return self.uci_show_currline.set(value),
             "uci_showrefutations" => return self.uci_show_refutations.set(value),
@@ -472,6 +488,7 @@ impl Configuration {
             "uci_shredderbasespath" => return Ok(self.uci_shredder_bases_path.set(value)),
             "uci_setpositionvalue" => return Ok(self.uci_set_position_value.set(value)),
             "ponder" => return self.ponder.set(value),
+            "position-validation" => return self.position_validation.set(value),
             "threads" => return self.threads.set(value),
             "weights-path" => return Ok(self.weights_path.set(value)),
             "nnue-path" => return Ok(self.nnue_path.set(value)),
@@ -511,7 +528,9 @@ impl Configuration {
         println!("{}", self.game_tree_caching);
         println!("{}", self.gui_lag);
         println!("{}", self.nnue_path);
+        println!("{}", self.pawn_hash);
         println!("{}", self.ponder);
+        println!("{}", self.position_validation);
         println!("{}", self.threads);
         println!("{}", self.weights_path);
 
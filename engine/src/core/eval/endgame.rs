@@ -0,0 +1,279 @@
+//! Endgame knowledge of the hand-crafted evaluation.
+//!
+//! Endgames are recognized by their material signature. A few of them are
+//! evaluated exactly, the others scale the regular evaluation towards a draw
+//! if their configuration is known to be hard to win.
+
+use std::{collections::HashMap, sync::LazyLock};
+
+use rustc_hash::FxBuildHasher;
+
+use crate::core::{
+    bitboard::{Bitboard, BitboardIteratorExt},
+    color::{Color, Perspective, colors},
+    coordinates::{File, Rank, Square, files, ranks, squares},
+    eval::{hce::piece_score, pawns},
+    piece::{Piece, PieceType, piece_type},
+    position::PieceInfo,
+    search::score::{AnyScore, Score},
+    turn::Turn,
+    zobrist,
+};
+
+pub mod kpk;
+#[cfg(test)] pub mod test;
+
+/// Base score of an endgame that is known to be won. It is well below the
+/// mate scores, so search still prefers an actual mate.
+pub const KNOWN_WIN: i32 = 10_000;
+
+/// Exact evaluation of an endgame, relative to the strong side.
+pub type EvalFn = fn(&PieceInfo, strong: Color, turn: Turn) -> AnyScore;
+
+/// Scale factor of an endgame, for the side that is ahead.
+pub type ScaleFn = fn(&PieceInfo, strong: Color) -> ScaleFactor;
+
+/// The fraction of the evaluation that is kept, in 64ths.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ScaleFactor(u8);
+
+impl ScaleFactor {
+    pub const DRAW: Self = Self(0);
+    pub const NORMAL: Self = Self(64);
+
+    pub const fn new(v: u8) -> Self {
+        debug_assert!(v <= Self::NORMAL.0);
+        Self(v)
+    }
+
+    pub const fn v(&self) -> u8 { self.0 }
+
+    pub const fn apply(self, score: AnyScore) -> AnyScore { AnyScore::new(score.v() * self.0 as i32 / Self::NORMAL.0 as i32) }
+}
+
+static ENDGAMES: LazyLock<Endgames> = LazyLock::new(Endgames::new);
+
+/// The registry of all known endgames.
+pub struct Endgames {
+    /// Keyed by the material key of the position.
+    evals: HashMap<zobrist::Hash, (Color, EvalFn), FxBuildHasher>,
+    /// Keyed by the material key of the position without its pawns, so they
+    /// apply to any number of pawns. The strong side is `None` if both sides
+    /// have the same pieces.
+    scales: HashMap<zobrist::Hash, (Option<Color>, ScaleFn), FxBuildHasher>,
+}
+
+impl Endgames {
+    fn new() -> Self {
+        let mut endgames = Self {
+            evals: HashMap::default(),
+            scales: HashMap::default(),
+        };
+
+        endgames.add_eval(&[piece_type::QUEEN], &[], kxk);
+        endgames.add_eval(&[piece_type::ROOK], &[], kxk);
+        endgames.add_eval(&[piece_type::BISHOP, piece_type::KNIGHT], &[], kbnk);
+        endgames.add_eval(&[piece_type::PAWN], &[], kpk);
+
+        endgames.add_scale(&[piece_type::BISHOP], &[], kbpsk);
+        endgames.add_scale(&[piece_type::BISHOP], &[piece_type::BISHOP], kbkb);
+        endgames.add_scale(&[piece_type::ROOK], &[piece_type::ROOK], krkr);
+
+        endgames
+    }
+
+    /// The registry, which is built on first use. The zobrist keys need to be
+    /// initialized by then.
+    pub fn get() -> &'static Self { &ENDGAMES }
+
+    fn add_eval(&mut self, strong_pieces: &[PieceType], weak_pieces: &[PieceType], eval: EvalFn) {
+        for strong in [colors::WHITE, colors::BLACK] {
+            self.evals.insert(signature(strong, strong_pieces, weak_pieces), (strong, eval));
+        }
+    }
+
+    fn add_scale(&mut self, strong_pieces: &[PieceType], weak_pieces: &[PieceType], scale: ScaleFn) {
+        let symmetric = strong_pieces == weak_pieces;
+        for strong in [colors::WHITE, colors::BLACK] {
+            let key = signature(strong, strong_pieces, weak_pieces);
+            self.scales.insert(key, ((!symmetric).then_some(strong), scale));
+        }
+    }
+
+    #[inline]
+    pub fn probe_eval(&self, material_key: zobrist::Hash) -> Option<(Color, EvalFn)> { self.evals.get(&material_key).copied() }
+
+    #[inline]
+    pub fn probe_scale(&self, pos: &PieceInfo, material_key: zobrist::Hash) -> Option<(Option<Color>, ScaleFn)> {
+        self.scales.get(&without_pawns(pos, material_key)).copied()
+    }
+}
+
+/// Applies the endgame knowledge about `pos` to its static evaluation `score`.
+///
+/// Known endgames replace the evaluation, drawish ones scale it if it favours
+/// their strong side.
+#[inline]
+pub fn adjust<P: Perspective>(pos: &PieceInfo, turn: Turn, material_key: zobrist::Hash, score: Score<P>) -> Score<P> {
+    let endgames = Endgames::get();
+    if let Some((strong, eval)) = endgames.probe_eval(material_key) {
+        return eval(pos, strong, turn).contextualize(strong);
+    }
+
+    if let Some((strong, scale)) = endgames.probe_scale(pos, material_key) {
+        let ahead = if score.0.v() >= 0 { P::COLOR } else { !P::COLOR };
+        if strong.is_none_or(|strong| strong == ahead) {
+            let scaled = scale(pos, ahead).apply(score.0);
+            return unsafe { scaled.interpret_as() };
+        }
+    }
+
+    score
+}
+
+/// The material key of a position where `strong` has a king and
+/// `strong_pieces`, and the other side a king and `weak_pieces`.
+fn signature(strong: Color, strong_pieces: &[PieceType], weak_pieces: &[PieceType]) -> zobrist::Hash {
+    let mut key = zobrist::Hash::default();
+    for (color, pieces) in [(strong, strong_pieces), (!strong, weak_pieces)] {
+        for pt in piece_type::PAWN..=piece_type::KING {
+            let piece = Piece::from((color, pt));
+            let count = pieces.iter().filter(|&&p| p == pt).count() + usize::from(pt == piece_type::KING);
+            for n in 0..count as i8 {
+                key.toggle_piece_count(piece, n);
+            }
+        }
+    }
+    key
+}
+
+/// Removes the pawns of `pos` from its material key.
+fn without_pawns(pos: &PieceInfo, mut material_key: zobrist::Hash) -> zobrist::Hash {
+    for color in [colors::WHITE, colors::BLACK] {
+        let pawn = Piece::from((color, piece_type::PAWN));
+        for n in 0..pos.get_piece_count(pawn) {
+            material_key.toggle_piece_count(pawn, n);
+        }
+    }
+    material_key
+}
+
+fn king(pos: &PieceInfo, c: Color) -> Square { pos.get_bitboard(piece_type::KING, c).lsb().expect("Every position has a king.") }
+
+/// Bonus for driving the king to the edge of the board.
+fn push_to_edge(sq: Square) -> i32 {
+    let file = File::from(sq).v() as i32;
+    let rank = Rank::from(sq).v() as i32;
+    let centre_distance = (3 - file).max(file - 4) + (3 - rank).max(rank - 4);
+    20 * centre_distance
+}
+
+/// Bonus for bringing the kings close to each other.
+fn push_close(sq1: Square, sq2: Square) -> i32 { 140 - 20 * sq1.distance(sq2) as i32 }
+
+/// The non-king material of `c`.
+fn material(pos: &PieceInfo, c: Color) -> i32 {
+    (piece_type::PAWN..piece_type::KING)
+        .map(|pt| pos.get_bitboard(pt, c).pop_cnt() as i32 * piece_score(pt).v())
+        .sum()
+}
+
+/// A queen or a rook against a bare king mates by driving the king to the
+/// edge with the help of our king.
+fn kxk(pos: &PieceInfo, strong: Color, _turn: Turn) -> AnyScore {
+    let strong_king = king(pos, strong);
+    let weak_king = king(pos, !strong);
+    AnyScore::new(KNOWN_WIN + material(pos, strong) + push_to_edge(weak_king) + push_close(strong_king, weak_king))
+}
+
+/// Bishop and knight can only mate in the corners of the bishop's color.
+fn kbnk(pos: &PieceInfo, strong: Color, _turn: Turn) -> AnyScore {
+    let strong_king = king(pos, strong);
+    let weak_king = king(pos, !strong);
+
+    let corners = [squares::A1, squares::H1, squares::A8, squares::H8]
+        .map(Bitboard::from)
+        .into_iter()
+        .aggregate();
+    let bishop_squares = if (pos.get_bitboard(piece_type::BISHOP, strong) & Bitboard::light_squares()).is_empty() {
+        Bitboard::dark_squares()
+    }
+    else {
+        Bitboard::light_squares()
+    };
+    let corner_distance = (corners & bishop_squares).map(|corner| weak_king.distance(corner)).min().unwrap_or(7) as i32;
+
+    AnyScore::new(KNOWN_WIN + material(pos, strong) + 40 * (7 - corner_distance) + push_close(strong_king, weak_king))
+}
+
+/// King and pawn against king is looked up in the [kpk] bitbase.
+fn kpk(pos: &PieceInfo, strong: Color, turn: Turn) -> AnyScore {
+    let pawn = pos.get_bitboard(piece_type::PAWN, strong).lsb().expect("The signature has a pawn.");
+    if !kpk::probe(strong, king(pos, strong), pawn, king(pos, !strong), turn) {
+        return AnyScore::new(0);
+    }
+
+    let rank = Rank::from(pawn).v() as i32;
+    let relative_rank = if strong == colors::WHITE { rank } else { 7 - rank };
+    AnyScore::new(KNOWN_WIN + piece_score(piece_type::PAWN).v() + 20 * relative_rank)
+}
+
+/// Bishop and rook pawns can not win if the bishop does not control the
+/// promotion square, and the defending king reaches the corner.
+fn kbpsk(pos: &PieceInfo, strong: Color) -> ScaleFactor {
+    let pawns = pos.get_bitboard(piece_type::PAWN, strong);
+    if pawns.is_empty() {
+        return ScaleFactor::NORMAL;
+    }
+
+    let bishop_on_light = !(pos.get_bitboard(piece_type::BISHOP, strong) & Bitboard::light_squares()).is_empty();
+    let weak_king = king(pos, !strong);
+    let queening_rank = if strong == colors::WHITE { ranks::_8 } else { ranks::_1 };
+    for file in [files::A, files::H] {
+        if !Bitboard::from(file).contains(pawns) {
+            continue;
+        }
+
+        let promo_sq = Square::from((file, queening_rank));
+        if Bitboard::light_squares().is_bit_set(promo_sq) != bishop_on_light && weak_king.distance(promo_sq) <= 1 {
+            return ScaleFactor::DRAW;
+        }
+    }
+
+    ScaleFactor::NORMAL
+}
+
+/// Bishops of opposite colors are drawish, even a few pawns up.
+fn kbkb(pos: &PieceInfo, strong: Color) -> ScaleFactor {
+    let bishops = pos.get_piece_bb(piece_type::BISHOP);
+    if !(bishops & Bitboard::light_squares()).pop_cnt_eq_1() {
+        return ScaleFactor::NORMAL;
+    }
+
+    let extra_pawns = pos.get_bitboard(piece_type::PAWN, strong).pop_cnt() as i32 - pos.get_bitboard(piece_type::PAWN, !strong).pop_cnt() as i32;
+    if extra_pawns <= 1 {
+        ScaleFactor::new(16)
+    }
+    else {
+        ScaleFactor::new(32)
+    }
+}
+
+/// Rook endings are drawish if the defending king blocks a lone pawn, or if
+/// a single extra pawn has to be converted with all pawns on one wing.
+fn krkr(pos: &PieceInfo, strong: Color) -> ScaleFactor {
+    let strong_pawns = pos.get_bitboard(piece_type::PAWN, strong);
+    let weak_pawns = pos.get_bitboard(piece_type::PAWN, !strong);
+
+    if strong_pawns.pop_cnt_eq_1() && weak_pawns.is_empty() && pawns::front_span(strong_pawns, strong).is_bit_set(king(pos, !strong)) {
+        return ScaleFactor::new(8);
+    }
+
+    let queen_side = (files::A..=files::D).map(Bitboard::from).aggregate();
+    let all_pawns = strong_pawns | weak_pawns;
+    if strong_pawns.pop_cnt() == weak_pawns.pop_cnt() + 1 && ((all_pawns & queen_side).is_empty() || all_pawns.and_not_c(queen_side).is_empty()) {
+        return ScaleFactor::new(40);
+    }
+
+    ScaleFactor::NORMAL
+}
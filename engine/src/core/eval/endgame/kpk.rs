@@ -0,0 +1,150 @@
+//! A bitbase of all king and pawn versus king positions.
+//!
+//! The bitbase is generated by retrograde analysis on first use. Positions
+//! are normalized to the pawn being white and on the queen side, which leaves
+//! 24 pawn squares.
+
+use std::sync::LazyLock;
+
+use crate::core::{
+    color::{Color, colors},
+    coordinates::{File, Rank, Square, files},
+    move_iter::{king, pawn},
+    turn::Turn,
+};
+
+const N_INDICES: usize = 2 /* turn */ * 24 /* pawn */ * 64 /* black king */ * 64 /* white king */;
+
+/// Results of the positions during the generation, so that the results of
+/// all successors can be or-ed together.
+type Outcome = u8;
+const INVALID: Outcome = 0;
+const UNKNOWN: Outcome = 1;
+const DRAW: Outcome = 2;
+const WIN: Outcome = 4;
+
+static BITBASE: LazyLock<Box<[u64]>> = LazyLock::new(generate);
+
+/// Whether the side with the pawn wins.
+pub fn probe(strong: Color, strong_king: Square, pawn: Square, weak_king: Square, turn: Turn) -> bool {
+    let (mut wk, mut p, mut bk) = (strong_king, pawn, weak_king);
+    if strong == colors::BLACK {
+        (wk, p, bk) = (wk.flip_v(), p.flip_v(), bk.flip_v());
+    }
+    if File::from(p) > files::D {
+        (wk, p, bk) = (wk.flip_h(), p.flip_h(), bk.flip_h());
+    }
+
+    let idx = index(turn == strong, wk, bk, p);
+    BITBASE[idx / 64] & (1 << (idx % 64)) != 0
+}
+
+/// `white_to_move`, the kings and the pawn on files A to D and ranks 2 to 7.
+#[inline]
+fn index(white_to_move: bool, wk: Square, bk: Square, p: Square) -> usize {
+    let file = File::from(p).v() as usize;
+    let rank = Rank::from(p).v() as usize;
+    wk.index() | bk.index() << 6 | usize::from(!white_to_move) << 12 | file << 13 | (6 - rank) << 15
+}
+
+fn decode(idx: usize) -> (bool, Square, Square, Square) {
+    let sq = |v: usize| {
+        // Safety: `v` is masked or computed to be in 0..64.
+        unsafe { Square::from_v(v as u8) }
+    };
+    let file = (idx >> 13) & 3;
+    let rank = 6 - (idx >> 15);
+    (idx >> 12 & 1 == 0, sq(idx & 63), sq(idx >> 6 & 63), sq(rank * 8 + file))
+}
+
+fn generate() -> Box<[u64]> {
+    let mut db: Vec<Outcome> = (0..N_INDICES).map(initial).collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for idx in 0..N_INDICES {
+            if db[idx] == UNKNOWN {
+                db[idx] = classify(&db, idx);
+                changed |= db[idx] != UNKNOWN;
+            }
+        }
+    }
+
+    let mut bits = vec![0u64; N_INDICES / 64].into_boxed_slice();
+    for (idx, &result) in db.iter().enumerate() {
+        if result == WIN {
+            bits[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+    bits
+}
+
+/// The result of a position that can be told without looking at its
+/// successors.
+fn initial(idx: usize) -> Outcome {
+    let (white_to_move, wk, bk, p) = decode(idx);
+    let pawn_attacks = pawn::compute_attacks(p.into(), colors::WHITE);
+
+    if wk.distance(bk) <= 1 || wk == p || bk == p || (white_to_move && pawn_attacks.is_bit_set(bk)) {
+        return INVALID;
+    }
+
+    let rank = Rank::from(p).v();
+    if white_to_move && rank == 6 {
+        // Safety: the pawn is on the 7th rank.
+        let promo_sq = unsafe { Square::from_v(p.v() + 8) };
+        if wk != promo_sq && bk != promo_sq && (bk.distance(promo_sq) > 1 || wk.distance(promo_sq) == 1) {
+            return WIN;
+        }
+    }
+
+    let bk_moves = king::lookup_attacks(bk).and_not_c(king::lookup_attacks(wk));
+    if !white_to_move && (bk_moves.and_not_c(pawn_attacks).is_empty() || bk_moves.is_bit_set(p)) {
+        // Stalemate, or the pawn gets captured.
+        return DRAW;
+    }
+
+    UNKNOWN
+}
+
+/// The result of a position by the results of its successors.
+fn classify(db: &[Outcome], idx: usize) -> Outcome {
+    let (white_to_move, wk, bk, p) = decode(idx);
+    let (good, bad) = if white_to_move { (WIN, DRAW) } else { (DRAW, WIN) };
+
+    let mut r = INVALID;
+    if white_to_move {
+        for sq in king::lookup_attacks(wk) {
+            r |= db[index(false, sq, bk, p)];
+        }
+
+        let rank = Rank::from(p).v();
+        if rank < 6 {
+            // Safety: the pawn is below the 7th rank.
+            let push = unsafe { Square::from_v(p.v() + 8) };
+            r |= db[index(false, wk, bk, push)];
+
+            if rank == 1 && push != wk && push != bk {
+                // Safety: the pawn is on the 2nd rank.
+                let double_push = unsafe { Square::from_v(p.v() + 16) };
+                r |= db[index(false, wk, bk, double_push)];
+            }
+        }
+    }
+    else {
+        for sq in king::lookup_attacks(bk) {
+            r |= db[index(true, wk, sq, p)];
+        }
+    }
+
+    if r & good != 0 {
+        good
+    }
+    else if r & UNKNOWN != 0 {
+        UNKNOWN
+    }
+    else {
+        bad
+    }
+}
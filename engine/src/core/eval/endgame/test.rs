@@ -0,0 +1,51 @@
+use super::*;
+use crate::core::{color::perspectives::White, move_iter::sliding_piece::magics, position::Position};
+
+/// White's adjusted score of `fen`, with a static eval of `score` for white.
+fn adjusted(fen: &str, score: i32) -> i32 {
+    zobrist::init();
+    magics::init();
+
+    let pos = Position::from_fen(fen).unwrap();
+    let score: Score<White> = unsafe { AnyScore::new(score).interpret_as() };
+    adjust::<White>(pos.piece_info(), pos.get_turn(), pos.get_material_key(), score).0.v()
+}
+
+#[test]
+fn kpk_bitbase() {
+    // the black king is outside the square of the pawn
+    assert!(adjusted("k7/8/8/8/7P/8/8/K7 w - - 0 1", 0) > KNOWN_WIN);
+    assert!(adjusted("k7/8/8/7p/8/8/8/K7 b - - 0 1", 0) < -KNOWN_WIN);
+    // the black king catches the pawn only if it is to move
+    assert!(adjusted("8/8/8/2k5/7P/8/8/K7 w - - 0 1", 0) > KNOWN_WIN);
+    assert_eq!(adjusted("8/8/8/2k5/7P/8/8/K7 b - - 0 1", 0), 0);
+    // rook pawns are drawn with the defending king in the corner
+    assert_eq!(adjusted("k7/8/1K6/P7/8/8/8/8 w - - 0 1", 100), 0);
+    // the king in front of its pawn on the 6th rank always wins
+    assert!(adjusted("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1", 0) > KNOWN_WIN);
+}
+
+#[test]
+fn mating_nets() {
+    // the lone king is better off in the centre
+    assert!(adjusted("8/8/8/3k4/8/8/8/Q3K3 w - - 0 1", 0) < adjusted("k7/8/8/8/8/8/8/Q3K3 w - - 0 1", 0));
+    assert!(adjusted("3K4/8/8/8/8/8/8/r3k3 w - - 0 1", 0) < -KNOWN_WIN);
+    // the dark squared bishop can only mate on a1 and h8
+    let right_corner = adjusted("8/8/8/8/8/8/8/k1K1B1N1 w - - 0 1", 0);
+    let wrong_corner = adjusted("k1KB2N1/8/8/8/8/8/8/8 w - - 0 1", 0);
+    assert!(right_corner > wrong_corner && wrong_corner > KNOWN_WIN);
+}
+
+#[test]
+fn drawish_scale_factors() {
+    // the bishop does not control a8
+    assert_eq!(adjusted("1k6/8/8/8/8/8/PB6/K7 w - - 0 1", 200), 0);
+    assert_eq!(adjusted("1k6/8/8/8/8/8/P7/KB6 w - - 0 1", 200), 200);
+    // bishops of opposite colors
+    assert_eq!(adjusted("4k3/8/3b4/8/3P4/8/4B3/4K3 w - - 0 1", 200), 50);
+    assert_eq!(adjusted("4k3/8/3b4/8/3P4/8/5B2/4K3 w - - 0 1", 200), 200);
+    // the defending king blocks the pawn
+    assert_eq!(adjusted("4k3/8/8/4P3/8/8/r7/4K2R w - - 0 1", 256), 32);
+    // only the side that is ahead is scaled
+    assert_eq!(adjusted("4k3/8/8/4P3/8/8/r7/4K2R w - - 0 1", -256), -256);
+}
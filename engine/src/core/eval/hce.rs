@@ -1,8 +1,9 @@
 use crate::{
     core::{
-        bitboard::Bitboard,
+        bitboard::{Bitboard, BitboardIteratorExt},
         color::{Color, Perspective, colors},
-        coordinates::{EpTargetSquare, File, Rank, Square, files, pawn_utils::single_step, squares},
+        coordinates::{EpTargetSquare, File, Rank, Square, files, pawn_utils::single_step, ranks, squares},
+        eval::pawns::{self, PawnEntry},
         move_iter::{bishop::Bishop, king, knight, pawn, queen::Queen, rook::Rook, sliding_piece::SlidingAttacks},
         piece::{Piece, PieceType, piece_type},
         position::{PieceInfo, PieceInfoObserver},
@@ -14,6 +15,39 @@ use crate::{
 };
 use const_for::const_for;
 
+#[cfg(test)] pub mod test;
+
+/// Weights of the hand-crafted evaluation terms.
+///
+/// Tapered terms have a middle game (`_mg`) and an end game (`_eg`) weight.
+pub const trait HceParams {
+    fn rook_open_file_mg(&self) -> i32;
+    fn rook_open_file_eg(&self) -> i32;
+    fn rook_semi_open_file_mg(&self) -> i32;
+    fn rook_semi_open_file_eg(&self) -> i32;
+    fn rook_seventh_mg(&self) -> i32;
+    fn rook_seventh_eg(&self) -> i32;
+    fn knight_outpost_mg(&self) -> i32;
+    fn knight_outpost_eg(&self) -> i32;
+    fn bishop_outpost_mg(&self) -> i32;
+    fn bishop_outpost_eg(&self) -> i32;
+    fn king_attack_knight(&self) -> i32;
+    fn king_attack_bishop(&self) -> i32;
+    fn king_attack_rook(&self) -> i32;
+    fn king_attack_queen(&self) -> i32;
+    fn threat_mg(&self) -> i32;
+    fn threat_eg(&self) -> i32;
+    fn hanging_mg(&self) -> i32;
+    fn hanging_eg(&self) -> i32;
+    fn space_mg(&self) -> i32;
+    fn isolated_mg(&self) -> i32;
+    fn isolated_eg(&self) -> i32;
+    fn doubled_mg(&self) -> i32;
+    fn doubled_eg(&self) -> i32;
+    fn backward_mg(&self) -> i32;
+    fn backward_eg(&self) -> i32;
+}
+
 pub struct Psqt([i32; squares::N_VARIANTS]);
 
 impl Psqt {
@@ -251,7 +285,7 @@ const PIECE_PHASES: [PiecePhase; piece_type::N_VARIANTS] = {
     [NONE, PAWN, KNIGHT, BISHOP, ROOK, QUEEN, NONE]
 };
 
-const fn piece_phase(piece: PieceType) -> PiecePhase { PIECE_PHASES[piece.v() as usize] }
+pub const fn piece_phase(piece: PieceType) -> PiecePhase { PIECE_PHASES[piece.v() as usize] }
 
 /// Tapered Evaluation Phase value.
 /// Where:
@@ -536,68 +570,270 @@ pub fn king_safety<P: Perspective>(pos: &PieceInfo, _ep_sq: EpTargetSquare, _tur
     }
 }
 
-// todo: ep capture possibilities are subject of qsearch anyway, so maybe it's
-// better to just ignore that possibility.
-#[allow(clippy::erasing_op)]
-pub fn passed_pawns<P: Perspective>(pos: &PieceInfo, ep_sq: EpTargetSquare, turn: Turn) -> Score<P> {
+/// Tarrasch rule: rooks belong behind passed pawns, ours and theirs.
+pub fn rooks_behind_passers<P: Perspective>(pos: &PieceInfo, passed: Bitboard) -> Score<P> {
     let us = P::COLOR;
     let them = !us;
 
-    let our_pawns = pos.get_bitboard(piece_type::PAWN, us);
-    let our_attacks = pawn::compute_attacks(our_pawns, us);
-    let their_pawns = pos.get_bitboard(piece_type::PAWN, them);
-    let their_attacks = pawn::compute_attacks(their_pawns, them);
+    let our_passer_rearspan = pawns::rear_span(passed, us);
 
-    // we only consider the ep a valid capture if it's the opponents turn.
-    let ep_target = if turn == them { Some(ep_sq) } else { None };
-    let ep_target_bb = Bitboard::from(ep_target.and_then(|x| x.v()));
+    let our_rooks = pos.get_bitboard(piece_type::ROOK, us);
+    let their_rooks = pos.get_bitboard(piece_type::ROOK, them);
+    let protective_rooks = our_rooks & our_passer_rearspan;
+    let aggressor_rooks = their_rooks & our_passer_rearspan;
 
-    // inlined compassrose because the constant 'NORT_C' wichi is literally just '8'
-    // is 'unconstrained' -_-, thanks rust
-    //
-    // if this gets ever fixed, we should just be able to pass in
-    // single_step::<P::COLOR>() as the direction without trouble...
-    //
-    let their_frontfill = match P::COLOR {
-        colors::WHITE => (their_pawns | their_attacks).fill::<-8 /*south*/>(),
-        colors::BLACK => (their_pawns | their_attacks).fill::<8 /* north*/>(),
+    let score = protective_rooks.pop_cnt() as i32 * 20 - aggressor_rooks.pop_cnt() as i32 * 15;
+
+    unsafe { AnyScore::from(score).interpret_as() }
+}
+
+/// Rooks on files without our pawns, with or without their pawns.
+pub fn rook_files<P: Perspective>(pos: &PieceInfo, phase: TaperValue, params: &impl HceParams) -> Score<P> {
+    let our_pawns = pos.get_bitboard(piece_type::PAWN, P::COLOR);
+    let their_pawns = pos.get_bitboard(piece_type::PAWN, !P::COLOR);
+
+    let (mut open, mut semi_open) = (0, 0);
+    for rook in pos.get_bitboard(piece_type::ROOK, P::COLOR) {
+        let file = Bitboard::from(File::from(rook));
+        if (our_pawns & file).is_empty() {
+            match (their_pawns & file).is_empty() {
+                true => open += 1,
+                false => semi_open += 1,
+            }
+        }
+    }
+
+    tapered(
+        phase,
+        open * params.rook_open_file_mg() + semi_open * params.rook_semi_open_file_mg(),
+        open * params.rook_open_file_eg() + semi_open * params.rook_semi_open_file_eg(),
+    )
+}
+
+/// Rooks on the 7th rank, as long as their king is stuck on the 8th or there
+/// are pawns to eat.
+pub fn rook_on_seventh<P: Perspective>(pos: &PieceInfo, phase: TaperValue, params: &impl HceParams) -> Score<P> {
+    let us = P::COLOR;
+    let them = !us;
+
+    let (seventh, eighth) = match us {
+        colors::WHITE => (Bitboard::from(ranks::_7), Bitboard::from(ranks::_8)),
+        colors::BLACK => (Bitboard::from(ranks::_2), Bitboard::from(ranks::_1)),
         _ => unreachable!(),
     };
 
-    // if the passed pawn can be captured en passant, don't count him
-    let their_ep_capture = their_attacks & ep_target_bb;
-    let their_ep_capt_sq = their_ep_capture.shift(single_step(us));
+    let rooks = pos.get_bitboard(piece_type::ROOK, us) & seventh;
+    let their_pawns = pos.get_bitboard(piece_type::PAWN, them);
+    let their_king = pos.get_bitboard(piece_type::KING, them);
+    if rooks.is_empty() || ((their_pawns & seventh).is_empty() && (their_king & eighth).is_empty()) {
+        return Score::ZERO;
+    }
 
-    let passed_pawns = our_pawns & !(their_frontfill | their_ep_capt_sq);
+    let cnt = rooks.pop_cnt() as i32;
+    tapered(phase, cnt * params.rook_seventh_mg(), cnt * params.rook_seventh_eg())
+}
 
-    let our_passer_rearspan = match P::COLOR {
-        colors::WHITE => passed_pawns.span::<-8 /*south*/>(),
-        colors::BLACK => passed_pawns.span::<8 /* north*/>(),
+/// Knights and bishops in their half, protected by our pawns, that their
+/// pawns can never chase away.
+pub fn outposts<P: Perspective>(pos: &PieceInfo, phase: TaperValue, params: &impl HceParams) -> Score<P> {
+    let us = P::COLOR;
+    let them = !us;
+
+    let outpost_ranks = match us {
+        colors::WHITE => Bitboard::from(ranks::_4) | Bitboard::from(ranks::_5) | Bitboard::from(ranks::_6),
+        colors::BLACK => Bitboard::from(ranks::_5) | Bitboard::from(ranks::_4) | Bitboard::from(ranks::_3),
         _ => unreachable!(),
     };
 
-    // normal or doubled passed pawns
-    let secondary_passed_pawns = passed_pawns & our_passer_rearspan;
-    let primary_passed_pawns = passed_pawns & !our_passer_rearspan;
+    let our_attacks = pawn::compute_attacks(pos.get_bitboard(piece_type::PAWN, us), us);
+    let their_attacks = pawn::compute_attacks(pos.get_bitboard(piece_type::PAWN, them), them);
+    let outposts = outpost_ranks & our_attacks & !pawns::front_fill(their_attacks, them);
 
-    // protected passed pawn
-    let protected_passed_pawns = passed_pawns & our_attacks;
+    let knights = (pos.get_bitboard(piece_type::KNIGHT, us) & outposts).pop_cnt() as i32;
+    let bishops = (pos.get_bitboard(piece_type::BISHOP, us) & outposts).pop_cnt() as i32;
 
-    // tarrasch rule
-    let our_rooks = pos.get_bitboard(piece_type::ROOK, us);
+    tapered(
+        phase,
+        knights * params.knight_outpost_mg() + bishops * params.bishop_outpost_mg(),
+        knights * params.knight_outpost_eg() + bishops * params.bishop_outpost_eg(),
+    )
+}
+
+/// Attack units of our pieces on the squares around their king, looked up in a
+/// safety table. Only counts if at least two of our pieces take part.
+pub fn king_attack<P: Perspective>(pos: &PieceInfo, phase: TaperValue, params: &impl HceParams) -> Score<P> {
+    #[rustfmt::skip]
+    const SAFETY_TABLE: [i32; 100] = [
+          0,   0,   1,   2,   3,   5,   7,   9,  12,  15,
+         18,  22,  26,  30,  35,  39,  44,  50,  56,  62,
+         68,  75,  82,  85,  89,  97, 105, 113, 122, 131,
+        140, 150, 169, 180, 191, 202, 213, 225, 237, 248,
+        260, 272, 283, 295, 307, 319, 330, 342, 354, 366,
+        377, 389, 401, 412, 424, 436, 448, 459, 471, 483,
+        494, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+        500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+        500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+        500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    ];
+
+    let Some(their_king) = pos.get_bitboard(piece_type::KING, !P::COLOR).lsb()
+    else {
+        return Score::ZERO;
+    };
+
+    let zone = king::lookup_attacks(their_king) | Bitboard::from(their_king);
+    let occ = pos.get_occupancy();
+
+    let (mut units, mut attackers) = (0, 0);
+    for (pt, weight) in [
+        (piece_type::KNIGHT, params.king_attack_knight()),
+        (piece_type::BISHOP, params.king_attack_bishop()),
+        (piece_type::ROOK, params.king_attack_rook()),
+        (piece_type::QUEEN, params.king_attack_queen()),
+    ] {
+        for sq in pos.get_bitboard(pt, P::COLOR) {
+            let hits = piece_attacks(pt, sq, occ) & zone;
+            if !hits.is_empty() {
+                attackers += 1;
+                units += weight * hits.pop_cnt() as i32;
+            }
+        }
+    }
+
+    if attackers < 2 {
+        return Score::ZERO;
+    }
+
+    let score = SAFETY_TABLE[units.clamp(0, SAFETY_TABLE.len() as i32 - 1) as usize];
+    tapered(phase, score, 0)
+}
+
+/// Their pieces attacked by our pieces of lower value.
+pub fn threats<P: Perspective>(pos: &PieceInfo, phase: TaperValue, params: &impl HceParams) -> Score<P> {
+    let us = P::COLOR;
+    let them = !us;
+
+    let their_minors = pos.get_bitboard(piece_type::KNIGHT, them) | pos.get_bitboard(piece_type::BISHOP, them);
     let their_rooks = pos.get_bitboard(piece_type::ROOK, them);
-    let protective_rooks = our_rooks & our_passer_rearspan;
-    let aggressor_rooks = their_rooks & our_passer_rearspan;
+    let their_queens = pos.get_bitboard(piece_type::QUEEN, them);
 
-    // score primary passed pawns higher than secondary/doubled passed pawns
-    // give a bonus for protected passed pawns.
-    let score = protected_passed_pawns.pop_cnt() as i32 * 50
-        + primary_passed_pawns.pop_cnt() as i32 * 30
-        + secondary_passed_pawns.pop_cnt() as i32 * 0
-        + protective_rooks.pop_cnt() as i32 * 20
-        - aggressor_rooks.pop_cnt() as i32 * 15;
+    let by_pawns = attacks_by(pos, piece_type::PAWN, us) & (their_minors | their_rooks | their_queens);
+    let by_minors = (attacks_by(pos, piece_type::KNIGHT, us) | attacks_by(pos, piece_type::BISHOP, us)) & (their_rooks | their_queens);
+    let by_rooks = attacks_by(pos, piece_type::ROOK, us) & their_queens;
 
-    unsafe { AnyScore::from(score).interpret_as() }
+    let cnt = (by_pawns | by_minors | by_rooks).pop_cnt() as i32;
+    tapered(phase, cnt * params.threat_mg(), cnt * params.threat_eg())
+}
+
+/// Their pieces we attack, which they do not defend.
+pub fn hanging<P: Perspective>(pos: &PieceInfo, phase: TaperValue, params: &impl HceParams) -> Score<P> {
+    let us = P::COLOR;
+    let them = !us;
+
+    let all_attacks = |c| (piece_type::PAWN..=piece_type::KING).map(|pt| attacks_by(pos, pt, c)).aggregate();
+
+    let their_pieces = pos.get_color_bb(them) & !pos.get_piece_bb(piece_type::KING);
+    let hanging = their_pieces & all_attacks(us) & !all_attacks(them);
+
+    let cnt = hanging.pop_cnt() as i32;
+    tapered(phase, cnt * params.hanging_mg(), cnt * params.hanging_eg())
+}
+
+/// Safe squares in the center of our half, squares behind our pawns count
+/// twice.
+pub fn space<P: Perspective>(pos: &PieceInfo, phase: TaperValue, params: &impl HceParams) -> Score<P> {
+    let us = P::COLOR;
+    let them = !us;
+
+    let center_files = Bitboard::from(files::C) | Bitboard::from(files::D) | Bitboard::from(files::E) | Bitboard::from(files::F);
+    let our_ranks = match us {
+        colors::WHITE => Bitboard::from(ranks::_2) | Bitboard::from(ranks::_3) | Bitboard::from(ranks::_4),
+        colors::BLACK => Bitboard::from(ranks::_7) | Bitboard::from(ranks::_6) | Bitboard::from(ranks::_5),
+        _ => unreachable!(),
+    };
+
+    let our_pawns = pos.get_bitboard(piece_type::PAWN, us);
+    let their_attacks = attacks_by(pos, piece_type::PAWN, them);
+
+    let safe = center_files & our_ranks & !our_pawns & !their_attacks;
+    let behind = safe & pawns::rear_span(our_pawns, us);
+
+    let cnt = (safe.pop_cnt() + behind.pop_cnt()) as i32;
+    tapered(phase, cnt * params.space_mg(), 0)
+}
+
+/// Hand-crafted static evaluation relative to `P`.
+pub fn eval<P: Perspective>(
+    pos: &PieceInfo,
+    turn: Turn,
+    ep_sq: EpTargetSquare,
+    phase: TaperValue,
+    pawns: &PawnEntry,
+    params: &impl HceParams,
+) -> Score<P> {
+    fn static_value<P: Perspective>(
+        pos: &PieceInfo,
+        ep_sq: EpTargetSquare,
+        phase: TaperValue,
+        turn: Turn,
+        pawns: &PawnEntry,
+        params: &impl HceParams,
+    ) -> Score<P> {
+        material::<P>(pos)
+            + mobility::<P>(pos, phase)
+            + psqt::<P>(pos, phase)
+            + bishop_pair::<P>(pos)
+            + king_safety::<P>(pos, ep_sq, turn, phase)
+            + pawns.score::<P>(phase)
+            + rooks_behind_passers::<P>(pos, pawns.passed(P::COLOR))
+            + hygge_king::<P>(pos, phase)
+            + rook_files::<P>(pos, phase, params)
+            + rook_on_seventh::<P>(pos, phase, params)
+            + outposts::<P>(pos, phase, params)
+            + king_attack::<P>(pos, phase, params)
+            + threats::<P>(pos, phase, params)
+            + hanging::<P>(pos, phase, params)
+            + space::<P>(pos, phase, params)
+    }
+
+    let (ep_w, ep_b) = if P::COLOR == colors::WHITE {
+        (ep_sq, EpTargetSquare::none())
+    }
+    else {
+        (EpTargetSquare::none(), ep_sq)
+    };
+    let w_q = static_value::<P>(pos, ep_w, phase, turn, pawns, params);
+    let b_q = static_value::<P::Opponent>(pos, ep_b, phase, turn, pawns, params);
+    w_q + !b_q
+}
+
+/// Tapers the `mg` and `eg` weights of a term by `phase`.
+pub fn tapered<P: Perspective>(phase: TaperValue, mg: i32, eg: i32) -> Score<P> {
+    let score = phase.weighted_eval(mg.into(), eg.into());
+    unsafe { score.interpret_as() }
+}
+
+fn piece_attacks(pt: PieceType, sq: Square, occ: Bitboard) -> Bitboard {
+    match pt {
+        piece_type::KNIGHT => knight::lookup_attacks(sq),
+        piece_type::BISHOP => <Bishop as SlidingAttacks>::lookup_attacks(sq, occ),
+        piece_type::ROOK => <Rook as SlidingAttacks>::lookup_attacks(sq, occ),
+        piece_type::QUEEN => <Queen as SlidingAttacks>::lookup_attacks(sq, occ),
+        piece_type::KING => king::lookup_attacks(sq),
+        _ => unreachable!(),
+    }
+}
+
+/// All squares attacked by the pieces of type `pt` and color `c`.
+fn attacks_by(pos: &PieceInfo, pt: PieceType, c: Color) -> Bitboard {
+    let pieces = pos.get_bitboard(pt, c);
+    match pt {
+        piece_type::PAWN => pawn::compute_attacks(pieces, c),
+        _ => {
+            let occ = pos.get_occupancy();
+            pieces.map(|sq| piece_attacks(pt, sq, occ)).aggregate()
+        }
+    }
 }
 
 pub fn bishop_pair<P: Perspective>(pos: &PieceInfo) -> Score<P> {
@@ -0,0 +1,42 @@
+use super::*;
+use crate::core::{
+    color::perspectives::{Black, White},
+    move_iter::sliding_piece::magics,
+    params::C_IdHceParams,
+    position::Position,
+    zobrist,
+};
+
+const MG: TaperValue = TaperValue::new(0);
+
+fn pieces(fen: &str) -> PieceInfo {
+    zobrist::init();
+    magics::init();
+    Position::from_fen(fen).unwrap().piece_info().clone()
+}
+
+#[test]
+fn rooks_on_open_and_semi_open_files() {
+    // a1 is on a semi-open file, h1 on an open one
+    let pos = pieces("4k3/p7/8/8/8/8/1P6/R3K2R w KQ - 0 1");
+    assert_eq!(rook_files::<White>(&pos, MG, &C_IdHceParams).0.v(), 25 + 12);
+}
+
+#[test]
+fn outposts_need_to_be_safe_from_pawns() {
+    let pos = pieces("4k3/8/8/3N4/4P3/8/8/4K3 w - - 0 1");
+    assert_eq!(outposts::<White>(&pos, MG, &C_IdHceParams).0.v(), 20);
+
+    // c7-c6 would chase the knight away
+    let pos = pieces("4k3/2p5/8/3N4/4P3/8/8/4K3 w - - 0 1");
+    assert_eq!(outposts::<White>(&pos, MG, &C_IdHceParams).0.v(), 0);
+}
+
+#[test]
+fn threats_and_hanging_pieces() {
+    let pos = pieces("4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1");
+    assert_eq!(threats::<White>(&pos, MG, &C_IdHceParams).0.v(), 30);
+    assert_eq!(hanging::<White>(&pos, MG, &C_IdHceParams).0.v(), 20);
+    assert_eq!(threats::<Black>(&pos, MG, &C_IdHceParams).0.v(), 0);
+    assert_eq!(hanging::<Black>(&pos, MG, &C_IdHceParams).0.v(), 0);
+}
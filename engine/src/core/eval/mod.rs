@@ -6,16 +6,19 @@ use crate::core::{
     config::Configuration,
     coordinates::EpTargetSquare,
     eval::hce::TaperValue,
-    position::{PieceInfo, PieceInfoObserver},
+    position::{PieceInfo, PieceInfoObserver, Position},
     search::score::Score,
     turn::Turn,
+    zobrist,
 };
 
+pub mod endgame;
 pub mod hce;
 pub mod nnue;
+pub mod pawns;
 
 pub trait StaticEvaluator: Sized {
-    fn eval<P: Perspective>(&mut self, pos: &PieceInfo, turn: Turn, ep_sq: EpTargetSquare, phase: TaperValue) -> Score<P>;
+    fn eval<P: Perspective>(&mut self, pos: &PieceInfo, turn: Turn, ep_sq: EpTargetSquare, phase: TaperValue, keys: EvalKeys) -> Score<P>;
 
     fn init(&mut self, _pos: &PieceInfo) {}
 
@@ -37,6 +40,23 @@ pub trait StaticEvaluator: Sized {
     }
 }
 
+/// The keys of a position that the static evaluators look up cached or
+/// special-cased terms by.
+#[derive(Clone, Copy, Debug)]
+pub struct EvalKeys {
+    pub pawn: zobrist::Hash,
+    pub material: zobrist::Hash,
+}
+
+impl EvalKeys {
+    pub fn of(pos: &Position) -> Self {
+        Self {
+            pawn: pos.get_pawn_key(),
+            material: pos.get_material_key(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum GameResult {
     Win { relative_to: Color },
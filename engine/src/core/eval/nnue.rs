@@ -1,28 +1,37 @@
 use crate::core::{depth::Depth, search::data::SearchStack};
 use std::{
-    fs::File,
+    fs,
     hint::unreachable_unchecked,
-    io::{self, Read},
-    mem,
+    io,
     path::Path,
+    sync::{Arc, LazyLock},
 };
 
+use bytemuck::{Pod, Zeroable};
 use thiserror::Error;
 
 use crate::{
     core::{
-        color::{
-            Color, Perspective, colors,
-            perspectives::{Black, White},
-        },
+        bitboard::Bitboard,
+        color::{Color, Perspective, colors},
         coordinates::{Square, squares},
         piece::{Piece, PieceType, piece_type},
         position::{PieceInfo, PieceInfoObserver},
         search::score::AnyScore,
     },
-    misc::{CheckHealth, CheckHealthResult},
+    misc::{CheckHealth, CheckHealthResult, List},
 };
 
+pub mod buckets;
+pub mod deep;
+pub mod format;
+pub mod simd;
+#[cfg(test)] pub mod test;
+pub mod trace;
+
+use buckets::{KingBucket, N_KING_BUCKETS, N_OUTPUT_BUCKETS, Pieces, RefreshCache};
+use deep::DeepLayers;
+
 pub type TValue = i16;
 pub type TEval = i32;
 
@@ -30,82 +39,200 @@ const PIECES: usize = piece_type::N_VARIANTS - 1;
 const COLORS: usize = colors::N_VARIANTS;
 const SQUARES: usize = squares::N_VARIANTS;
 
-pub const INPUT_SIZE: usize = PIECES * COLORS * SQUARES;
+/// The input features of a single king bucket.
+pub const FEATURES_PER_BUCKET: usize = PIECES * COLORS * SQUARES;
+pub const INPUT_SIZE: usize = N_KING_BUCKETS * FEATURES_PER_BUCKET;
 pub const HIDDEN_SIZE: usize = 2 << 7;
-pub const OUTPUT_SIZE: usize = 1;
+pub const OUTPUT_SIZE: usize = N_OUTPUT_BUCKETS;
 
 // todo: use Cp::SCALE?
 pub const SCALE: TValue = 400;
 pub const QA: TValue = 255;
 pub const QB: TValue = 64;
 
-pub type NetworkBytes = [u8; size_of::<Network>()];
+/// The raw layers of the embedded network, which was trained with the plain
+/// inputs of a single king bucket, without [buckets::MIRRORED].
+pub static DEFAULT_NNUE: &[u8] = include_bytes!("../../../../checkpoints/nnue-1783259751942-768-256_400_255-64-40/quantised.bin");
 
-pub static DEFAULT_NNUE: NetworkBytes = *include_bytes!("../../../../checkpoints/nnue-1783259751942-768-256_400_255-64-40/quantised.bin");
+static DEFAULT_NETWORK: LazyLock<Arc<Network>> =
+    LazyLock::new(|| Arc::new(Network::from_raw_bytes(DEFAULT_NNUE).expect("The default network should have the layout of the engine.")));
 
-static mut NNUE: Network = unsafe { mem::transmute::<NetworkBytes, Network>(DEFAULT_NNUE) };
+/// The network that is embedded into the engine.
+pub fn default_nnue() -> Arc<Network> { DEFAULT_NETWORK.clone() }
 
-/// Gets a nnue, defaulting to some default net if the user net paniced during
-/// initialization.
-#[allow(static_mut_refs)]
-pub fn get_nnue() -> &'static Network { unsafe { &NNUE } }
+pub fn read_net_bytes(path: &Path) -> Result<Vec<u8>, LoadNnueError> { Ok(fs::read(path)?) }
 
-pub fn read_net_bytes(path: &Path) -> Result<NetworkBytes, io::Error> {
-    let mut f = File::open(path)?;
-    let mut bytes = [0u8; size_of::<Network>()];
-    f.read_exact(&mut bytes)?;
-    Ok(bytes)
+/// Loads a network from the contents of a network file and checks its health.
+pub fn load_nnue(bytes: &[u8]) -> Result<Arc<Network>, LoadNnueError> {
+    let net = Network::from_bytes(bytes)?;
+    net.check_health()?;
+    Ok(Arc::new(net))
 }
 
-#[allow(static_mut_refs)]
-pub fn set_nnue(bytes: NetworkBytes) -> Result<(), CheckNnueHealthError> {
-    let net: Network = unsafe { mem::transmute::<NetworkBytes, Network>(bytes) };
-    net.check_health()?;
+/// Loads the network of the `nnue-path` option, where an empty path stands
+/// for the embedded network.
+pub fn nnue_from_path(path: &str) -> Result<Arc<Network>, LoadNnueError> {
+    if path.is_empty() {
+        let net = default_nnue();
+        net.check_health()?;
+        Ok(net)
+    }
+    else {
+        load_nnue(&read_net_bytes(Path::new(path))?)
+    }
+}
 
-    unsafe {
-        NNUE = net;
+/// The layers after the accumulators.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Architecture {
+    /// `(768 -> N)x2 -> 1`, with a SCReLU on the accumulators.
+    Shallow = 0,
+    /// `(768 -> N)x2 -> L1 -> L2 -> 1`, see [deep].
+    Deep = 1,
+}
+
+impl Architecture {
+    /// The size of the networks of this architecture in bytes, without a
+    /// header.
+    pub const fn size(self) -> usize {
+        let head = match self {
+            Self::Shallow => size_of::<OutputLayer>(),
+            Self::Deep => size_of::<DeepLayers>(),
+        };
+        size_of::<[HiddenLayer; INPUT_SIZE]>() + size_of::<HiddenLayer>() + head
     }
+}
 
-    Ok(())
+impl TryFrom<u32> for Architecture {
+    type Error = LoadNnueError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Shallow),
+            1 => Ok(Self::Deep),
+            _ => Err(LoadNnueError::UnknownArchitecture(value)),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LoadNnueError {
+    #[error("Failed to read the network: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Expected at least {expected} bytes, but the file has {actual}")]
+    Truncated { expected: usize, actual: usize },
+
+    #[error("The network has no header, raw networks of the trainer have to be packed first")]
+    MissingHeader,
+
+    #[error("Unsupported version {0} of the network format, expected version {VERSION}", VERSION = format::VERSION)]
+    UnsupportedVersion(u32),
+
+    #[error("Unknown network architecture {0}")]
+    UnknownArchitecture(u32),
+
+    #[error("Unknown compression {0} of the network")]
+    UnknownCompression(u32),
+
+    #[error("The network is {actual}, but the engine expects {expected}")]
+    Descriptor {
+        expected: format::Descriptor,
+        actual: format::Descriptor,
+    },
+
+    #[error("Expected {expected} bytes for a {architecture:?} network, but got {actual}")]
+    Size {
+        architecture: Architecture,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("The checksum of the network is {actual:#010x}, but the header expects {expected:#010x}")]
+    Checksum { expected: u32, actual: u32 },
+
+    #[error(transparent)]
+    Unhealthy(#[from] CheckNnueHealthError),
 }
 
 #[repr(C, align(64))]
+#[derive(Clone, Copy, Pod, Zeroable)]
 pub struct HiddenLayer {
     vals: [TValue; HIDDEN_SIZE],
 }
 
-#[repr(C)]
 pub struct Network {
-    acc_weights: [HiddenLayer; INPUT_SIZE],
+    acc_weights: Box<[HiddenLayer; INPUT_SIZE]>,
     acc_biases: HiddenLayer,
-    out_weights: [TValue; colors::N_VARIANTS * HIDDEN_SIZE],
-    out_bias: [TValue; OUTPUT_SIZE],
+    head: Head,
+}
+
+enum Head {
+    Shallow(Box<OutputLayer>),
+    Deep(Box<DeepLayers>),
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct OutputLayer {
+    /// The weights of the side to move, then of the other side, for each
+    /// output bucket.
+    weights: [[[TValue; HIDDEN_SIZE]; COLORS]; OUTPUT_SIZE],
+    bias: [TValue; OUTPUT_SIZE],
+}
+
+impl OutputLayer {
+    /// The output of the network, quantised by `QA * QB`.
+    fn output(&self, acc_stm: &simd::Layer, acc_nstm: &simd::Layer, bucket: usize) -> TEval {
+        let [out_stm, out_nstm] = &self.weights[bucket];
+        let mut eval = simd::screlu_dot(acc_stm, out_stm) + simd::screlu_dot(acc_nstm, out_nstm);
+
+        eval /= TEval::from(QA);
+        eval += TEval::from(self.bias[bucket]);
+        eval
+    }
+
+    fn forward(&self, acc_stm: &simd::Layer, acc_nstm: &simd::Layer, bucket: usize) -> TEval {
+        let mut eval = self.output(acc_stm, acc_nstm, bucket);
+        eval *= TEval::from(SCALE);
+        eval /= TEval::from(QA) * TEval::from(QB);
+        eval
+    }
 }
 
 impl Network {
-    pub fn forward(&self, acc_stm: &mut Accumulator, acc_nstm: &mut Accumulator) -> AnyScore {
+    pub fn architecture(&self) -> Architecture {
+        match self.head {
+            Head::Shallow(_) => Architecture::Shallow,
+            Head::Deep(_) => Architecture::Deep,
+        }
+    }
+
+    /// Evaluates the accumulators with the head of the output `bucket`, see
+    /// [buckets::output_bucket].
+    pub fn forward(&self, acc_stm: &mut Accumulator, acc_nstm: &mut Accumulator, bucket: usize) -> AnyScore {
         #[cfg(debug_assertions)]
         {
             acc_stm.check_health().expect("Unhealthy accumulator");
             acc_nstm.check_health().expect("Unhealthy accumulator");
         }
 
-        let mut eval: i32 = 0;
+        let eval = match &self.head {
+            Head::Shallow(out) => out.forward(&acc_stm.values, &acc_nstm.values, bucket),
+            Head::Deep(layers) => layers.forward(&acc_stm.values, &acc_nstm.values, bucket),
+        };
 
-        for (&value, &weight) in acc_stm.values().iter().zip(&self.out_weights[..HIDDEN_SIZE]) {
-            eval += activation(value) * TEval::from(weight);
-        }
+        AnyScore::from(eval)
+    }
 
-        for (&value, &weight) in acc_nstm.values().iter().zip(&self.out_weights[HIDDEN_SIZE..]) {
-            eval += activation(value) * TEval::from(weight);
+    /// The output of the network before it is scaled into centipawns, which
+    /// is what the trainer fits the sigmoid of.
+    pub fn raw_output(&self, acc_stm: &Accumulator, acc_nstm: &Accumulator, bucket: usize) -> f32 {
+        match &self.head {
+            Head::Shallow(out) => out.output(&acc_stm.values, &acc_nstm.values, bucket) as f32 / (f32::from(QA) * f32::from(QB)),
+            Head::Deep(layers) => layers.output(&acc_stm.values, &acc_nstm.values, bucket),
         }
-
-        eval /= TEval::from(QA);
-        eval += TEval::from(self.out_bias[0]);
-        eval *= TEval::from(SCALE);
-        eval /= TEval::from(QA) * TEval::from(QB);
-
-        AnyScore::from(eval)
     }
 }
 
@@ -116,6 +243,9 @@ pub enum CheckNnueHealthError {
 
     #[error("Value {value} at index {idx} in {field} is out of expected range")]
     OutOfRange { field: &'static str, idx: usize, value: i16 },
+
+    #[error("Value at index {idx} in {field} is not finite")]
+    NotFinite { field: &'static str, idx: usize },
 }
 
 impl CheckHealth for Network {
@@ -142,11 +272,16 @@ impl CheckHealth for Network {
         // acc_biases
         check_slice(&self.acc_biases.vals, "acc_biases")?;
 
-        // out_weights
-        check_slice(&self.out_weights, "out_weights")?;
+        match &self.head {
+            Head::Shallow(out) => {
+                // out_weights
+                check_slice(out.weights.as_flattened().as_flattened(), "out_weights")?;
 
-        // out_bias
-        check_slice(&self.out_bias, "out_bias")?;
+                // out_bias
+                check_slice(&out.bias, "out_bias")?;
+            }
+            Head::Deep(layers) => layers.check_health()?,
+        }
 
         // verify not all zeros (sum of absolute values > 0)
         let total_abs: i64 = self
@@ -154,8 +289,6 @@ impl CheckHealth for Network {
             .iter()
             .flat_map(|layer| layer.vals.iter())
             .chain(self.acc_biases.vals.iter())
-            .chain(self.out_weights.iter())
-            .chain(self.out_bias.iter())
             .map(|&x| x.abs() as i64)
             .sum();
 
@@ -194,9 +327,7 @@ impl Accumulator {
             self.inputs[idx] += 1;
         }
 
-        for (val, weight) in self.values.iter_mut().zip(&net.acc_weights[idx].vals) {
-            *val += *weight
-        }
+        simd::add(&mut self.values, &net.acc_weights[idx].vals);
     }
 
     /// Remove a feature from an accumulator.
@@ -206,9 +337,7 @@ impl Accumulator {
             self.inputs[idx] -= 1;
         }
 
-        for (val, weight) in self.values.iter_mut().zip(&net.acc_weights[idx].vals) {
-            *val -= *weight
-        }
+        simd::sub(&mut self.values, &net.acc_weights[idx].vals);
     }
 
     pub fn update_feature(&mut self, idx: usize, net: &Network, update: i16) {
@@ -217,9 +346,7 @@ impl Accumulator {
             self.inputs[idx] += update;
         }
 
-        for (val, weight) in self.values.iter_mut().zip(&net.acc_weights[idx].vals) {
-            *val += *weight * update;
-        }
+        simd::add_scaled(&mut self.values, &net.acc_weights[idx].vals, update);
     }
 
     pub fn values(&mut self) -> [i16; HIDDEN_SIZE] { self.values }
@@ -248,162 +375,178 @@ impl CheckHealth for Accumulator {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct FeatureUpdates {
-    counts: [[[i8; SQUARES]; PIECES]; COLORS],
+/// How the accumulators of the search stack follow the moves of the search.
+pub trait AccUpdates: Clone + Default {
+    /// The updates of the accumulators of the network `net`.
+    fn for_network(net: &Arc<Network>) -> Self;
+
+    /// Starts the accumulators of `child` from those of `parent`, one ply
+    /// deeper.
+    fn propagate(parent: &AccumulatorPair<Self>, child: &mut AccumulatorPair<Self>);
+
+    /// Brings the accumulators at `idx` up to date with its board.
+    fn update(stack: &mut AccumulatorStack<Self>, idx: Depth, net: &Network);
 }
 
-impl Default for FeatureUpdates {
-    fn default() -> Self {
-        Self {
-            counts: [[[0; SQUARES]; PIECES]; COLORS],
-        }
-    }
+/// Applies the updates to the accumulators right away, with the network `net`.
+#[derive(Clone)]
+pub struct EagerAccUpdates {
+    net: Arc<Network>,
 }
 
-impl FeatureUpdates {
-    pub fn get_mut(&mut self, sq: Square, pt: PieceType, c: Color) -> &mut i8 {
-        let c = c.v() as usize;
-        let pt = pt.v() as usize - 1;
-        let sq = sq.v() as usize;
-        unsafe { self.counts.get_unchecked_mut(c).get_unchecked_mut(pt).get_unchecked_mut(sq) }
-    }
+impl EagerAccUpdates {
+    pub fn new(net: Arc<Network>) -> Self { Self { net } }
 }
 
-#[derive(Clone, Default)]
-pub struct EagerAccUpdates;
+impl Default for EagerAccUpdates {
+    fn default() -> Self { Self::new(default_nnue()) }
+}
 
-#[derive(Clone)]
-struct LazyAccUpdates {
-    /// Before you apply anything, reset everything.
-    reset: bool,
+impl AccUpdates for EagerAccUpdates {
+    fn for_network(net: &Arc<Network>) -> Self { Self::new(net.clone()) }
 
-    /// The pieces that were put(+) or removed(-) on the board.
-    updates: FeatureUpdates,
+    fn propagate(parent: &AccumulatorPair<Self>, child: &mut AccumulatorPair<Self>) {
+        child.white = parent.white;
+        child.black = parent.black;
+        child.pieces = parent.pieces;
+        child.buckets = parent.buckets;
+    }
 
-    /// Which features have been updated?
-    updated: Vec<IndexInfo>,
+    fn update(stack: &mut AccumulatorStack<Self>, idx: Depth, net: &Network) { stack.accs.get_mut(idx).refresh(&mut stack.cache, net); }
 }
 
-impl Default for LazyAccUpdates {
-    fn default() -> Self { Self::new() }
+/// Records the pieces that each ply put on or removed from the board, and only
+/// applies them once the accumulators of a ply are needed. Plies that are cut
+/// off before they are evaluated never touch their accumulators.
+#[derive(Clone)]
+pub struct LazyAccUpdates {
+    /// Whether the accumulators are up to date with the board.
+    computed: bool,
+
+    /// Whether the accumulators are rebuilt from the board, instead of
+    /// updated from the parent.
+    rebuild: bool,
+
+    // a move puts at most two pieces and removes at most two pieces, e.g.
+    // castling or capturing with a promotion
+    put: List<2, (Square, Piece)>,
+    removed: List<2, (Square, Piece)>,
 }
 
-impl LazyAccUpdates {
-    fn new() -> Self {
+impl Default for LazyAccUpdates {
+    fn default() -> Self {
         Self {
-            reset: false,
-            updates: Default::default(),
-            updated: Vec::with_capacity(16),
+            computed: false,
+            // there is no parent to update from
+            rebuild: true,
+            put: List::new(),
+            removed: List::new(),
         }
     }
+}
 
-    fn put(&mut self, sq: Square, p: Piece) { self.update(sq, p, 1); }
-    fn remove(&mut self, sq: Square, p: Piece) { self.update(sq, p, -1); }
-
-    fn update(&mut self, sq: Square, p: Piece, delta: i8) {
-        let (c, pt) = p.unpack();
-
-        let cnt = self.updates.get_mut(sq, pt, c);
-
-        if *cnt == 0 {
-            self.updated.push(IndexInfo { sq, pt, c });
+impl LazyAccUpdates {
+    /// Applies the pieces of the ply to `acc` of `perspective`, which is built
+    /// for the king `bucket`.
+    fn apply(&self, acc: &mut Accumulator, perspective: Color, bucket: KingBucket, net: &Network) {
+        for &(sq, p) in self.removed.as_slice() {
+            let (c, pt) = p.unpack();
+            acc.remove_feature(bucket.input_index(perspective, sq, pt, c), net);
+        }
+        for &(sq, p) in self.put.as_slice() {
+            let (c, pt) = p.unpack();
+            acc.add_feature(bucket.input_index(perspective, sq, pt, c), net);
         }
-
-        *cnt += delta;
     }
+}
+
+impl AccUpdates for LazyAccUpdates {
+    fn for_network(_: &Arc<Network>) -> Self { Self::default() }
 
-    fn reset(&mut self) {
-        self.updates = Default::default();
-        self.reset = true;
+    fn propagate(parent: &AccumulatorPair<Self>, child: &mut AccumulatorPair<Self>) {
+        // only the board is needed right away, for the refreshes
+        child.pieces = parent.pieces;
+        child.updates.computed = false;
+        child.updates.rebuild = false;
+        child.updates.put.clear();
+        child.updates.removed.clear();
     }
 
-    fn apply(&mut self, acc_white: &mut Accumulator, acc_black: &mut Accumulator, net: &Network) {
-        if self.reset {
-            *acc_white = Accumulator::init(net);
-            *acc_black = Accumulator::init(net);
-            self.reset = false;
+    fn update(stack: &mut AccumulatorStack<Self>, idx: Depth, net: &Network) {
+        // the last ancestor whose accumulators are known, or that is built from scratch
+        let mut start = idx;
+        while !stack.accs.get(start).updates.computed && !stack.accs.get(start).updates.rebuild {
+            debug_assert!(start > Depth::ROOT, "The root accumulators are always known.");
+            start -= 1;
         }
 
-        for IndexInfo { sq, pt, c } in self.updated.drain(..) {
-            let cnt = {
-                let ptr = self.updates.get_mut(sq, pt, c);
-                let val = *ptr;
-                *ptr = 0;
-                val as i16
-            };
-
-            if cnt == 0 {
-                continue;
-            }
-
-            let idx_w = input_index_for::<White>(sq, pt, c);
-            let idx_b = input_index_for::<Black>(sq, pt, c);
+        let accs = stack.accs.get_mut(start);
+        if !accs.updates.computed {
+            accs.rebuild(&mut stack.cache, net);
+        }
 
-            match cnt {
-                // +1 or -1 generates much better assembly so switch here
-                1 => {
-                    acc_white.add_feature(idx_w, net);
-                    acc_black.add_feature(idx_b, net);
-                }
-                -1 => {
-                    acc_white.remove_feature(idx_w, net);
-                    acc_black.remove_feature(idx_b, net);
-                }
-                _ => {
-                    acc_white.update_feature(idx_w, net, cnt);
-                    acc_black.update_feature(idx_b, net, cnt);
-                }
-            }
+        let cache = &mut stack.cache;
+        for ply in start..idx {
+            stack.accs.propagate(ply, ply + 1, |parent, child| child.update_from(parent, cache, net));
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct IndexInfo {
-    sq: Square,
-    pt: PieceType,
-    c: Color,
-}
-
 #[derive(Clone)]
-pub struct AccumulatorPair<AccUpdates> {
-    updates: AccUpdates,
+pub struct AccumulatorPair<Updates> {
+    updates: Updates,
     white: Accumulator,
     black: Accumulator,
+
+    /// The board, which the accumulators are refreshed from.
+    pieces: Pieces,
+
+    /// The king buckets the accumulators are built for. An accumulator is
+    /// refreshed before it is used, if its king has changed buckets since.
+    buckets: [KingBucket; COLORS],
 }
 
 impl<Updates: Default> Default for AccumulatorPair<Updates> {
-    fn default() -> Self { Self::new(get_nnue()) }
+    fn default() -> Self { Self::new(Updates::default(), &default_nnue()) }
 }
 
-impl<Updates: Default> AccumulatorPair<Updates> {
-    pub fn new(net: &Network) -> Self {
+impl<Updates> AccumulatorPair<Updates> {
+    pub fn new(updates: Updates, net: &Network) -> Self {
         Self {
-            updates: Updates::default(),
+            updates,
             white: Accumulator::init(net),
             black: Accumulator::init(net),
+            pieces: Pieces::default(),
+            buckets: Default::default(),
         }
     }
 }
 
-impl AccumulatorPair<LazyAccUpdates> {
-    pub fn inherit_from(&mut self, parent: &Self) {
-        self.white = parent.white;
-        self.black = parent.black;
-
-        self.updates.reset = parent.updates.reset;
-        self.updates.updates = parent.updates.updates;
+impl<Updates> AccumulatorPair<Updates> {
+    fn put(&mut self, sq: Square, p: Piece) {
+        let (c, pt) = p.unpack();
+        self.pieces[c.v() as usize][pt.v() as usize - 1] |= Bitboard::from(sq);
+    }
 
-        self.updates.updated.clear();
-        self.updates.updated.extend_from_slice(&parent.updates.updated);
+    fn remove(&mut self, sq: Square, p: Piece) {
+        let (c, pt) = p.unpack();
+        self.pieces[c.v() as usize][pt.v() as usize - 1] ^= Bitboard::from(sq);
     }
 
-    pub fn sync(&mut self, net: &Network) { self.updates.apply(&mut self.white, &mut self.black, net); }
+    fn set_pieces(&mut self, pos: &PieceInfo) { self.pieces = pieces_of(pos); }
 
-    pub fn get_mut_for<P: Perspective>(&mut self, net: &Network) -> (&mut Accumulator, &mut Accumulator) {
-        self.sync(net);
+    /// Refreshes the accumulators whose king bucket is outdated.
+    fn refresh(&mut self, cache: &mut RefreshCache, net: &Network) {
+        for (perspective, acc) in [(colors::WHITE, &mut self.white), (colors::BLACK, &mut self.black)] {
+            let bucket = KingBucket::of(perspective, &self.pieces);
+            if bucket != self.buckets[perspective.v() as usize] {
+                *acc = cache.refresh(perspective, bucket, &self.pieces, net);
+                self.buckets[perspective.v() as usize] = bucket;
+            }
+        }
+    }
 
+    fn get_mut_for<P: Perspective>(&mut self) -> (&mut Accumulator, &mut Accumulator) {
         match P::COLOR {
             colors::WHITE => (&mut self.white, &mut self.black),
             colors::BLACK => (&mut self.black, &mut self.white),
@@ -412,38 +555,75 @@ impl AccumulatorPair<LazyAccUpdates> {
     }
 }
 
-impl AccumulatorPair<EagerAccUpdates> {
-    pub fn inherit_from(&mut self, parent: &Self) {
-        self.white = parent.white;
-        self.black = parent.black;
+impl AccumulatorPair<LazyAccUpdates> {
+    /// Builds both accumulators from the board.
+    fn rebuild(&mut self, cache: &mut RefreshCache, net: &Network) {
+        for (perspective, acc) in [(colors::WHITE, &mut self.white), (colors::BLACK, &mut self.black)] {
+            let bucket = KingBucket::of(perspective, &self.pieces);
+            *acc = cache.refresh(perspective, bucket, &self.pieces, net);
+            self.buckets[perspective.v() as usize] = bucket;
+        }
+        self.updates.computed = true;
+        self.updates.rebuild = false;
     }
 
-    pub fn get_mut_for<P: Perspective>(&mut self) -> (&mut Accumulator, &mut Accumulator) {
-        match P::COLOR {
-            colors::WHITE => (&mut self.white, &mut self.black),
-            colors::BLACK => (&mut self.black, &mut self.white),
-            _ => unsafe { unreachable_unchecked() },
+    /// Computes the accumulators from the computed ones of the `parent`. An
+    /// accumulator whose king has changed buckets is refreshed instead.
+    fn update_from(&mut self, parent: &Self, cache: &mut RefreshCache, net: &Network) {
+        for (perspective, acc, parent_acc) in [
+            (colors::WHITE, &mut self.white, &parent.white),
+            (colors::BLACK, &mut self.black, &parent.black),
+        ] {
+            let bucket = KingBucket::of(perspective, &self.pieces);
+            if bucket == parent.buckets[perspective.v() as usize] {
+                *acc = *parent_acc;
+                self.updates.apply(acc, perspective, bucket, net);
+            }
+            else {
+                *acc = cache.refresh(perspective, bucket, &self.pieces, net);
+            }
+            self.buckets[perspective.v() as usize] = bucket;
         }
+        self.updates.computed = true;
+    }
+}
+
+impl AccumulatorPair<EagerAccUpdates> {
+    fn add_features(&mut self, sq: Square, p: Piece) {
+        let (c, pt) = p.unpack();
+        let net = &self.updates.net;
+        let [bucket_w, bucket_b] = self.buckets;
+        self.white.add_feature(bucket_w.input_index(colors::WHITE, sq, pt, c), net);
+        self.black.add_feature(bucket_b.input_index(colors::BLACK, sq, pt, c), net);
+    }
+
+    fn remove_features(&mut self, sq: Square, p: Piece) {
+        let (c, pt) = p.unpack();
+        let net = &self.updates.net;
+        let [bucket_w, bucket_b] = self.buckets;
+        self.white.remove_feature(bucket_w.input_index(colors::WHITE, sq, pt, c), net);
+        self.black.remove_feature(bucket_b.input_index(colors::BLACK, sq, pt, c), net);
     }
 }
 
 impl PieceInfoObserver for AccumulatorPair<LazyAccUpdates> {
     fn on_init(&mut self, pos: &PieceInfo) {
-        // reset
-        self.updates.reset();
-
-        // put pieces
-        for sq in squares::A1..=squares::H8 {
-            let p = pos.get_piece(sq);
-            if p.piece_type() != piece_type::NONE {
-                self.updates.put(sq, p);
-            }
-        }
+        self.set_pieces(pos);
+        self.updates.computed = false;
+        self.updates.rebuild = true;
+        self.updates.put.clear();
+        self.updates.removed.clear();
     }
 
-    fn on_piece_put(&mut self, sq: Square, p: Piece) { self.updates.put(sq, p); }
+    fn on_piece_put(&mut self, sq: Square, p: Piece) {
+        self.put(sq, p);
+        self.updates.put.push((sq, p));
+    }
 
-    fn on_piece_removed(&mut self, sq: Square, p: Piece) { self.updates.remove(sq, p); }
+    fn on_piece_removed(&mut self, sq: Square, p: Piece) {
+        self.remove(sq, p);
+        self.updates.removed.push((sq, p));
+    }
 
     fn on_piece_moved(&mut self, from: Square, to: Square, p: Piece) {
         self.on_piece_removed(from, p);
@@ -454,28 +634,28 @@ impl PieceInfoObserver for AccumulatorPair<LazyAccUpdates> {
 impl PieceInfoObserver for AccumulatorPair<EagerAccUpdates> {
     fn on_init(&mut self, pos: &PieceInfo) {
         // reset
-        self.white = Accumulator::init(get_nnue());
-        self.black = Accumulator::init(get_nnue());
+        self.set_pieces(pos);
+        self.buckets = [colors::WHITE, colors::BLACK].map(|perspective| KingBucket::of(perspective, &self.pieces));
+        self.white = Accumulator::init(&self.updates.net);
+        self.black = Accumulator::init(&self.updates.net);
 
         // put pieces
         for sq in squares::A1..=squares::H8 {
             let p = pos.get_piece(sq);
             if p.piece_type() != piece_type::NONE {
-                self.on_piece_put(sq, p);
+                self.add_features(sq, p);
             }
         }
     }
 
     fn on_piece_put(&mut self, sq: Square, p: Piece) {
-        let (c, pt) = p.unpack();
-        self.white.add_feature(input_index_for::<White>(sq, pt, c), get_nnue());
-        self.black.add_feature(input_index_for::<Black>(sq, pt, c), get_nnue());
+        self.put(sq, p);
+        self.add_features(sq, p);
     }
 
     fn on_piece_removed(&mut self, sq: Square, p: Piece) {
-        let (c, pt) = p.unpack();
-        self.white.remove_feature(input_index_for::<White>(sq, pt, c), get_nnue());
-        self.black.remove_feature(input_index_for::<Black>(sq, pt, c), get_nnue());
+        self.remove(sq, p);
+        self.remove_features(sq, p);
     }
 
     fn on_piece_moved(&mut self, from: Square, to: Square, p: Piece) {
@@ -484,41 +664,53 @@ impl PieceInfoObserver for AccumulatorPair<EagerAccUpdates> {
     }
 }
 
-#[derive(Default)]
 pub struct AccumulatorStack<Updates: Clone> {
     accs: SearchStack<AccumulatorPair<Updates>>,
+    cache: RefreshCache,
 }
 
-impl AccumulatorStack<LazyAccUpdates> {
-    pub fn get_accs_mut(&mut self, idx: Depth) -> &mut AccumulatorPair<LazyAccUpdates> { self.accs.get_mut(idx) }
-
-    pub fn propagate(&mut self, old: Depth, new: Depth) {
-        self.accs.propagate(old, new, |parent, child| {
-            child.inherit_from(parent);
-        })
+impl<Updates: AccUpdates> AccumulatorStack<Updates> {
+    /// The accumulators of the network `net`, which each start from `updates`.
+    pub fn new(updates: Updates, net: &Network) -> Self {
+        Self {
+            accs: SearchStack::from(vec![
+                AccumulatorPair::new(updates, net);
+                SearchStack::<AccumulatorPair<Updates>>::CAPACITY
+            ]),
+            cache: RefreshCache::new(net),
+        }
     }
-}
 
-impl AccumulatorStack<EagerAccUpdates> {
-    pub fn get_accs_mut(&mut self, idx: Depth) -> &mut AccumulatorPair<EagerAccUpdates> { self.accs.get_mut(idx) }
+    pub fn get_accs_mut(&mut self, idx: Depth) -> &mut AccumulatorPair<Updates> { self.accs.get_mut(idx) }
 
-    pub fn propagate(&mut self, old: Depth, new: Depth) {
-        self.accs.propagate(old, new, |parent, child| {
-            child.inherit_from(parent);
-        })
+    /// The up to date accumulators of the side to move `P` and of its opponent.
+    pub fn get_mut_for<P: Perspective>(&mut self, idx: Depth, net: &Network) -> (&mut Accumulator, &mut Accumulator) {
+        Updates::update(self, idx, net);
+        self.accs.get_mut(idx).get_mut_for::<P>()
     }
-}
 
-#[inline(always)]
-fn input_index_for<P: Perspective>(sq: Square, pt: PieceType, c: Color) -> usize {
-    let (mut sq, mut c) = (sq, c);
+    pub fn propagate(&mut self, old: Depth, new: Depth) { self.accs.propagate(old, new, Updates::propagate) }
+}
 
-    if P::COLOR == colors::BLACK {
-        c = !c;
-        sq = sq.flip_v();
+fn pieces_of(pos: &PieceInfo) -> Pieces {
+    let mut pieces = Pieces::default();
+    for c in [colors::WHITE, colors::BLACK] {
+        for pt in piece_type::PAWN..=piece_type::KING {
+            pieces[c.v() as usize][pt.v() as usize - 1] = pos.get_bitboard(pt, c);
+        }
     }
+    pieces
+}
 
-    input_index(sq, pt, c)
+/// The input features of the pieces of `pos`, seen from `perspective`. This is
+/// what the accumulator of `perspective` sums up, for the trainers that build
+/// it from scratch.
+pub fn active_features(pos: &PieceInfo, perspective: Color) -> impl Iterator<Item = usize> {
+    let bucket = KingBucket::of(perspective, &pieces_of(pos));
+    pos.get_occupancy().into_iter().map(move |sq| {
+        let (c, pt) = pos.get_piece(sq).unpack();
+        bucket.input_index(perspective, sq, pt, c)
+    })
 }
 
 #[inline(always)]
@@ -529,7 +721,3 @@ fn input_index(sq: Square, pt: PieceType, c: Color) -> usize {
 
     c * PIECES * SQUARES + pt * SQUARES + sq
 }
-
-const fn activation(x: TValue) -> TEval { screlu(x) }
-const fn crelu(x: TValue) -> TEval { TEval::from(x).clamp(0, TEval::from(QA)) }
-const fn screlu(x: TValue) -> TEval { crelu(x).pow(2) }
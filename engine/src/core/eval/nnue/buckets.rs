@@ -0,0 +1,217 @@
+//! King buckets of the input features, the refresh cache of the accumulators
+//! and the output buckets.
+//!
+//! Each perspective sees the board through the bucket of its own king. With
+//! [MIRRORED], the board is mirrored horizontally if the king is on the king
+//! side, so only the queen side needs buckets.
+//!
+//! The output layer has a head for each range of material on the board, since
+//! endgames are evaluated quite differently from middlegames.
+
+use crate::core::{
+    bitboard::Bitboard,
+    color::{Color, colors},
+    coordinates::{File, Rank, Square, files},
+    piece::{PieceType, piece_type},
+};
+
+use super::{Accumulator, COLORS, FEATURES_PER_BUCKET, Network, PIECES, input_index};
+
+/// Whether the board is mirrored for a king on the king side. It has to match
+/// the inputs the network was trained with, and the embedded network was
+/// trained without.
+pub const MIRRORED: bool = false;
+
+/// The bucket of each king square on the queen side, from rank 1 to 8 of its
+/// own side, which the king side shares. It has to match the layout the
+/// network was trained with.
+#[rustfmt::skip]
+pub const KING_BUCKET_LAYOUT: [usize; 32] = [
+    0, 0, 0, 0,
+    0, 0, 0, 0,
+    0, 0, 0, 0,
+    0, 0, 0, 0,
+    0, 0, 0, 0,
+    0, 0, 0, 0,
+    0, 0, 0, 0,
+    0, 0, 0, 0,
+];
+
+pub const N_KING_BUCKETS: usize = {
+    let mut max = 0;
+    let mut i = 0;
+    while i < KING_BUCKET_LAYOUT.len() {
+        if KING_BUCKET_LAYOUT[i] > max {
+            max = KING_BUCKET_LAYOUT[i];
+        }
+        i += 1;
+    }
+    max + 1
+};
+
+/// The king buckets and the mirroring of the inputs of a network, which the
+/// trainer has to use as well.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InputLayout {
+    /// See [KING_BUCKET_LAYOUT].
+    pub king_buckets: [usize; 32],
+    /// See [MIRRORED].
+    pub mirrored: bool,
+}
+
+impl InputLayout {
+    /// The inputs the engine evaluates.
+    pub const ENGINE: Self = Self {
+        king_buckets: KING_BUCKET_LAYOUT,
+        mirrored: MIRRORED,
+    };
+
+    /// The bucket of each king square of the own side, where the king side
+    /// shares the buckets of the queen side. Trainers without mirroring take
+    /// the buckets of all squares.
+    pub const fn all_squares(&self) -> [usize; 64] {
+        let mut buckets = [0; 64];
+        let mut sq = 0;
+        while sq < 64 {
+            let file = if sq % 8 > 3 { 7 - sq % 8 } else { sq % 8 };
+            buckets[sq] = self.king_buckets[sq / 8 * 4 + file];
+            sq += 1;
+        }
+        buckets
+    }
+
+    /// A hash of the layout, which the header of a network file records, since
+    /// a network with other inputs has the same size.
+    pub const fn hash(&self) -> u32 {
+        // FNV-1a
+        let mut hash: u32 = 0x811c_9dc5;
+        let mut i = 0;
+        while i < self.king_buckets.len() {
+            hash = (hash ^ self.king_buckets[i] as u32).wrapping_mul(0x0100_0193);
+            i += 1;
+        }
+        (hash ^ self.mirrored as u32).wrapping_mul(0x0100_0193)
+    }
+}
+
+/// The hash of [InputLayout::ENGINE].
+pub const INPUT_LAYOUT_HASH: u32 = InputLayout::ENGINE.hash();
+
+/// The number of output buckets. It has to match the number the network was
+/// trained with.
+pub const N_OUTPUT_BUCKETS: usize = 1;
+
+/// The output bucket of a board with the pieces `occupancy`, of equally many
+/// piece counts each.
+#[inline]
+pub const fn output_bucket(occupancy: Bitboard) -> usize {
+    const DIVISOR: usize = 32usize.div_ceil(N_OUTPUT_BUCKETS);
+    (occupancy.pop_cnt() as usize - 2) / DIVISOR
+}
+
+/// The pieces of each color and type.
+pub type Pieces = [[Bitboard; PIECES]; COLORS];
+
+/// The king bucket a perspective sees the board through.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct KingBucket {
+    bucket: usize,
+    mirrored: bool,
+}
+
+impl KingBucket {
+    /// The bucket of `perspective` with its king on `king`.
+    #[inline]
+    pub fn new(perspective: Color, king: Square) -> Self {
+        let king = if perspective == colors::BLACK {
+            king.flip_v()
+        }
+        else {
+            king
+        };
+        // the layout is symmetric, whether the inputs are mirrored or not
+        let king_side = File::from(king) > files::D;
+        let king = if king_side { king.flip_h() } else { king };
+        Self {
+            bucket: KING_BUCKET_LAYOUT[Rank::from(king).v() as usize * 4 + File::from(king).v() as usize],
+            mirrored: MIRRORED && king_side,
+        }
+    }
+
+    /// The bucket of `perspective` on the board `pieces`.
+    #[inline]
+    pub fn of(perspective: Color, pieces: &Pieces) -> Self {
+        let king = pieces[perspective.v() as usize][piece_type::KING.v() as usize - 1]
+            .lsb()
+            .expect("Every position has a king.");
+        Self::new(perspective, king)
+    }
+
+    /// The index of the input feature of a piece, seen from `perspective`.
+    #[inline(always)]
+    pub fn input_index(self, perspective: Color, sq: Square, pt: PieceType, c: Color) -> usize {
+        let (mut sq, mut c) = (sq, c);
+        if perspective == colors::BLACK {
+            c = !c;
+            sq = sq.flip_v();
+        }
+        if self.mirrored {
+            sq = sq.flip_h();
+        }
+
+        self.bucket * FEATURES_PER_BUCKET + input_index(sq, pt, c)
+    }
+
+    #[inline]
+    fn cache_index(self) -> usize { self.bucket * 2 + usize::from(self.mirrored) }
+}
+
+/// The board and accumulator of the last refresh of each perspective and king
+/// bucket, also known as a Finny table.
+///
+/// Refreshing an accumulator from the cache only applies the difference of
+/// the boards, instead of adding every piece to the biases.
+pub struct RefreshCache {
+    entries: Box<[[RefreshEntry; 2 * N_KING_BUCKETS]; COLORS]>,
+}
+
+#[derive(Clone)]
+struct RefreshEntry {
+    acc: Accumulator,
+    pieces: Pieces,
+}
+
+impl RefreshCache {
+    pub fn new(net: &Network) -> Self {
+        let entry = RefreshEntry {
+            acc: Accumulator::init(net),
+            pieces: Pieces::default(),
+        };
+        Self {
+            entries: Box::new(std::array::from_fn(|_| std::array::from_fn(|_| entry.clone()))),
+        }
+    }
+
+    /// Builds the accumulator of `perspective` in `bucket` on the board
+    /// `pieces`.
+    pub fn refresh(&mut self, perspective: Color, bucket: KingBucket, pieces: &Pieces, net: &Network) -> Accumulator {
+        let entry = &mut self.entries[perspective.v() as usize][bucket.cache_index()];
+
+        for c in [colors::WHITE, colors::BLACK] {
+            for pt in piece_type::PAWN..=piece_type::KING {
+                let cached = &mut entry.pieces[c.v() as usize][pt.v() as usize - 1];
+                let current = pieces[c.v() as usize][pt.v() as usize - 1];
+
+                for sq in current.and_not_c(*cached) {
+                    entry.acc.add_feature(bucket.input_index(perspective, sq, pt, c), net);
+                }
+                for sq in cached.and_not_c(current) {
+                    entry.acc.remove_feature(bucket.input_index(perspective, sq, pt, c), net);
+                }
+                *cached = current;
+            }
+        }
+
+        entry.acc
+    }
+}
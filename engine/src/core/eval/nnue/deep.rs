@@ -0,0 +1,112 @@
+//! The layers of the deep architecture, `(768 -> N)x2 -> L1 -> L2 -> 1`.
+//!
+//! The accumulators are clipped into bytes, which are multiplied with the int8
+//! weights of L1. Most of the clipped values are zero, so only the chunks of
+//! the input with non-zero values are multiplied. The small layers after L1
+//! are computed in floats.
+
+use bytemuck::{Pod, Zeroable};
+use static_assertions::const_assert;
+
+use super::{CheckNnueHealthError, HIDDEN_SIZE, OUTPUT_SIZE, QA, SCALE, TEval, TValue, simd};
+use crate::misc::{CheckHealth, CheckHealthResult};
+
+pub const L1_SIZE: usize = 16;
+pub const L2_SIZE: usize = 32;
+
+/// The quantisation of the weights of L1.
+pub const QL1: TValue = 64;
+
+// the outputs of L1 are processed in chunks of 8 values
+const_assert!(L1_SIZE.is_multiple_of(8));
+
+/// The clipped accumulators of the side to move, then of the other side.
+pub type Input = [u8; 2 * HIDDEN_SIZE];
+
+/// The number of inputs that are skipped at once if they are all zero.
+const CHUNK_SIZE: usize = 4;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct DeepLayers {
+    /// The weights of each input to L1, for each output bucket.
+    l1_weights: [[[i8; L1_SIZE]; OUTPUT_SIZE]; 2 * HIDDEN_SIZE],
+    l1_biases: [[f32; L1_SIZE]; OUTPUT_SIZE],
+    l2_weights: [[[f32; L2_SIZE]; OUTPUT_SIZE]; L1_SIZE],
+    l2_biases: [[f32; L2_SIZE]; OUTPUT_SIZE],
+    l3_weights: [[f32; OUTPUT_SIZE]; L2_SIZE],
+    l3_biases: [f32; OUTPUT_SIZE],
+}
+
+impl DeepLayers {
+    pub fn forward(&self, acc_stm: &simd::Layer, acc_nstm: &simd::Layer, bucket: usize) -> TEval {
+        (self.output(acc_stm, acc_nstm, bucket) * f32::from(SCALE)) as TEval
+    }
+
+    /// The output of the layers, before it is scaled into centipawns.
+    pub fn output(&self, acc_stm: &simd::Layer, acc_nstm: &simd::Layer, bucket: usize) -> f32 {
+        let mut input: Input = [0; 2 * HIDDEN_SIZE];
+        let [stm, nstm] = input.as_chunks_mut::<HIDDEN_SIZE>().0
+        else {
+            unreachable!("The input consists of two accumulators.")
+        };
+        simd::clip(acc_stm, stm);
+        simd::clip(acc_nstm, nstm);
+
+        let l1 = self.l1(&input, bucket);
+
+        let dequantise = 1. / (f32::from(QA) * f32::from(QL1));
+        let l1: [f32; L1_SIZE] = std::array::from_fn(|i| screlu(l1[i] as f32 * dequantise + self.l1_biases[bucket][i]));
+
+        let mut l2 = self.l2_biases[bucket];
+        for (&x, weights) in l1.iter().zip(&self.l2_weights) {
+            for (out, &weight) in l2.iter_mut().zip(&weights[bucket]) {
+                *out += x * weight;
+            }
+        }
+
+        let mut out = self.l3_biases[bucket];
+        for (&x, weights) in l2.iter().zip(&self.l3_weights) {
+            out += screlu(x) * weights[bucket];
+        }
+        out
+    }
+
+    /// The quantised L1, which only multiplies the chunks of the input that
+    /// aren't all zero.
+    fn l1(&self, input: &Input, bucket: usize) -> [i32; L1_SIZE] {
+        let mut out = [0; L1_SIZE];
+        let weights = self.l1_weights.as_chunks::<CHUNK_SIZE>().0;
+        for (chunk, weights) in input.as_chunks::<CHUNK_SIZE>().0.iter().zip(weights) {
+            if u32::from_ne_bytes(*chunk) == 0 {
+                continue;
+            }
+
+            for (&x, weights) in chunk.iter().zip(weights) {
+                simd::add_product(&mut out, &weights[bucket], x);
+            }
+        }
+        out
+    }
+}
+
+impl CheckHealth for DeepLayers {
+    type Error = CheckNnueHealthError;
+
+    fn check_health(&self) -> CheckHealthResult<Self::Error> {
+        fn check_slice(slice: &[f32], field: &'static str) -> Result<(), CheckNnueHealthError> {
+            match slice.iter().position(|val| !val.is_finite()) {
+                Some(idx) => Err(CheckNnueHealthError::NotFinite { field, idx }),
+                None => Ok(()),
+            }
+        }
+
+        check_slice(self.l1_biases.as_flattened(), "l1_biases")?;
+        check_slice(self.l2_weights.as_flattened().as_flattened(), "l2_weights")?;
+        check_slice(self.l2_biases.as_flattened(), "l2_biases")?;
+        check_slice(self.l3_weights.as_flattened(), "l3_weights")?;
+        check_slice(&self.l3_biases, "l3_biases")
+    }
+}
+
+fn screlu(x: f32) -> f32 { x.clamp(0., 1.).powi(2) }
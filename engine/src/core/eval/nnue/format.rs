@@ -0,0 +1,263 @@
+//! The file format of the networks.
+//!
+//! A network file starts with a [Header], which describes the network and
+//! guards it with a checksum, followed by its layers as saved by the trainer.
+//! The layers may be compressed. The raw networks of the trainer have no
+//! header, so they have to be packed into a network file before the engine
+//! loads them.
+
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{Read, Write},
+};
+
+use bytemuck::{Pod, Zeroable};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder};
+
+use super::{
+    Architecture, DeepLayers, HIDDEN_SIZE, Head, HiddenLayer, INPUT_SIZE, LoadNnueError, Network, OUTPUT_SIZE, OutputLayer, QA, QB, SCALE, buckets,
+    deep::{L1_SIZE, L2_SIZE, QL1},
+};
+
+pub const MAGIC: [u8; 4] = *b"NPHN";
+
+/// The version of the format, which is increased on every incompatible change.
+pub const VERSION: u32 = 1;
+
+/// The trainer pads the networks it saves to a multiple of this many bytes.
+const PADDING: usize = 64;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct Header {
+    pub magic: [u8; 4],
+    pub version: u32,
+    pub descriptor: Descriptor,
+    /// See [Compression].
+    pub compression: u32,
+    /// The size of the stored, maybe compressed, layers in bytes.
+    pub size: u32,
+    /// The CRC32 of the uncompressed layers.
+    pub checksum: u32,
+}
+
+/// The shape and the quantisation of a network.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Pod, Zeroable)]
+pub struct Descriptor {
+    /// See [Architecture].
+    pub architecture: u32,
+    pub input_size: u32,
+    pub hidden_size: u32,
+    /// The number of output buckets.
+    pub output_size: u32,
+    /// The sizes of L1 and L2 of the deep architecture, zero otherwise.
+    pub l1_size: u32,
+    pub l2_size: u32,
+    /// The [buckets::InputLayout::hash] of the inputs.
+    pub input_layout: u32,
+    pub qa: i32,
+    /// The quantisation of the weights after the accumulators.
+    pub qb: i32,
+    pub scale: i32,
+}
+
+impl Descriptor {
+    /// The networks of `architecture` that the engine can evaluate.
+    pub const fn of(architecture: Architecture) -> Self {
+        let (l1_size, l2_size, qb) = match architecture {
+            Architecture::Shallow => (0, 0, QB),
+            Architecture::Deep => (L1_SIZE as u32, L2_SIZE as u32, QL1),
+        };
+        Self {
+            architecture: architecture as u32,
+            input_size: INPUT_SIZE as u32,
+            hidden_size: HIDDEN_SIZE as u32,
+            output_size: OUTPUT_SIZE as u32,
+            l1_size,
+            l2_size,
+            input_layout: buckets::INPUT_LAYOUT_HASH,
+            qa: QA as i32,
+            qb: qb as i32,
+            scale: SCALE as i32,
+        }
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({} -> {})x2", self.input_size, self.hidden_size)?;
+        if self.l1_size != 0 {
+            write!(f, " -> {} -> {}", self.l1_size, self.l2_size)?;
+        }
+        write!(
+            f,
+            " -> 1 in {} buckets, inputs {:#010x}, QA {}, QB {}, scale {}",
+            self.output_size, self.input_layout, self.qa, self.qb, self.scale
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Compression {
+    None = 0,
+    Zlib = 1,
+}
+
+impl TryFrom<u32> for Compression {
+    type Error = LoadNnueError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Zlib),
+            _ => Err(LoadNnueError::UnknownCompression(value)),
+        }
+    }
+}
+
+/// Encodes the `layers` of a network of `architecture`, as saved by the
+/// trainer, into a network file. `inputs` are the inputs the network was
+/// trained with, which the engine only loads if they are its own.
+pub fn encode(architecture: Architecture, inputs: buckets::InputLayout, layers: &[u8], compression: Compression) -> Vec<u8> {
+    let stored = match compression {
+        Compression::None => layers.to_vec(),
+        Compression::Zlib => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+            encoder.write_all(layers).expect("Writing to a vector is infallible.");
+            encoder.finish().expect("Writing to a vector is infallible.")
+        }
+    };
+
+    let header = Header {
+        magic: MAGIC,
+        version: VERSION,
+        descriptor: Descriptor {
+            input_layout: inputs.hash(),
+            ..Descriptor::of(architecture)
+        },
+        compression: compression as u32,
+        size: stored.len() as u32,
+        checksum: crc32fast::hash(layers),
+    };
+
+    let mut bytes = bytemuck::bytes_of(&header).to_vec();
+    bytes.extend(stored);
+    bytes
+}
+
+/// Checks the header of `bytes` and returns the architecture and the
+/// uncompressed layers of the network.
+fn decode(bytes: &[u8]) -> Result<(Architecture, Cow<'_, [u8]>), LoadNnueError> {
+    if !bytes.starts_with(&MAGIC) {
+        return Err(LoadNnueError::MissingHeader);
+    }
+
+    let (header, stored) = bytes.split_at_checked(size_of::<Header>()).ok_or(LoadNnueError::Truncated {
+        expected: size_of::<Header>(),
+        actual: bytes.len(),
+    })?;
+    let header: Header = bytemuck::pod_read_unaligned(header);
+
+    if header.version != VERSION {
+        return Err(LoadNnueError::UnsupportedVersion(header.version));
+    }
+
+    let architecture = Architecture::try_from(header.descriptor.architecture)?;
+    let expected = Descriptor::of(architecture);
+    if header.descriptor != expected {
+        return Err(LoadNnueError::Descriptor {
+            expected,
+            actual: header.descriptor,
+        });
+    }
+
+    if stored.len() != header.size as usize {
+        return Err(LoadNnueError::Truncated {
+            expected: size_of::<Header>() + header.size as usize,
+            actual: bytes.len(),
+        });
+    }
+
+    let layers = match Compression::try_from(header.compression)? {
+        Compression::None => Cow::Borrowed(stored),
+        Compression::Zlib => {
+            // anything beyond the size of the network is too much anyways
+            let mut layers = Vec::with_capacity(architecture.size());
+            ZlibDecoder::new(stored)
+                .take((architecture.size() + PADDING) as u64)
+                .read_to_end(&mut layers)?;
+            Cow::Owned(layers)
+        }
+    };
+
+    let checksum = crc32fast::hash(&layers);
+    if checksum != header.checksum {
+        return Err(LoadNnueError::Checksum {
+            expected: header.checksum,
+            actual: checksum,
+        });
+    }
+
+    Ok((architecture, layers))
+}
+
+/// Reads the layers of a network in the order of the file.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn read<T: Pod>(&mut self) -> T {
+        let (value, rest) = self.bytes.split_at(size_of::<T>());
+        self.bytes = rest;
+        bytemuck::pod_read_unaligned(value)
+    }
+
+    /// Reads a value that is too large for the stack.
+    fn read_boxed<T: Pod>(&mut self) -> Box<T> {
+        let (value, rest) = self.bytes.split_at(size_of::<T>());
+        self.bytes = rest;
+        let mut boxed = bytemuck::zeroed_box::<T>();
+        bytemuck::bytes_of_mut(boxed.as_mut()).copy_from_slice(value);
+        boxed
+    }
+}
+
+impl Network {
+    /// Loads a network from the contents of a network file. The network is
+    /// not checked for health.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoadNnueError> {
+        let (architecture, layers) = decode(bytes)?;
+        Self::from_layers(architecture, &layers)
+    }
+
+    /// Loads the raw layers of a shallow network as saved by the trainer,
+    /// without a header. Nothing checks that the network was trained with the
+    /// inputs of the engine, so this is only for the embedded network and the
+    /// trainers.
+    pub fn from_raw_bytes(layers: &[u8]) -> Result<Self, LoadNnueError> { Self::from_layers(Architecture::Shallow, layers) }
+
+    fn from_layers(architecture: Architecture, layers: &[u8]) -> Result<Self, LoadNnueError> {
+        let expected = architecture.size();
+        if !(expected..expected + PADDING).contains(&layers.len()) {
+            return Err(LoadNnueError::Size {
+                architecture,
+                expected,
+                actual: layers.len(),
+            });
+        }
+
+        let mut reader = Reader { bytes: layers };
+        Ok(Self {
+            acc_weights: reader.read_boxed::<[HiddenLayer; INPUT_SIZE]>(),
+            acc_biases: reader.read(),
+            head: match architecture {
+                Architecture::Shallow => Head::Shallow(reader.read_boxed::<OutputLayer>()),
+                Architecture::Deep => Head::Deep(reader.read_boxed::<DeepLayers>()),
+            },
+        })
+    }
+}
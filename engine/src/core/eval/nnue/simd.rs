@@ -0,0 +1,141 @@
+//! The kernels of the accumulator updates and of the layers after them.
+//!
+//! With AVX2 they are vectorized with `wide`, otherwise the scalar versions
+//! are used. Both give bit-exact results.
+
+use static_assertions::const_assert;
+
+use super::{HIDDEN_SIZE, TValue};
+
+#[cfg(not(target_feature = "avx2"))] pub use scalar::*;
+#[cfg(target_feature = "avx2")] pub use vectorized::*;
+
+// the hidden layer is processed in chunks of 16 values
+const_assert!(HIDDEN_SIZE.is_multiple_of(16));
+
+pub type Layer = [TValue; HIDDEN_SIZE];
+
+#[cfg(any(test, not(target_feature = "avx2")))]
+pub mod scalar {
+    use super::{
+        super::{HIDDEN_SIZE, QA, TEval, TValue},
+        Layer,
+    };
+
+    #[inline]
+    pub fn add(acc: &mut Layer, weights: &Layer) {
+        for (val, weight) in acc.iter_mut().zip(weights) {
+            *val += *weight
+        }
+    }
+
+    #[inline]
+    pub fn sub(acc: &mut Layer, weights: &Layer) {
+        for (val, weight) in acc.iter_mut().zip(weights) {
+            *val -= *weight
+        }
+    }
+
+    #[inline]
+    pub fn add_scaled(acc: &mut Layer, weights: &Layer, scale: TValue) {
+        for (val, weight) in acc.iter_mut().zip(weights) {
+            *val += *weight * scale;
+        }
+    }
+
+    /// The dot product of the activated accumulator `acc` and `weights`.
+    #[inline]
+    pub fn screlu_dot(acc: &Layer, weights: &Layer) -> TEval {
+        let mut eval: TEval = 0;
+        for (&value, &weight) in acc.iter().zip(weights) {
+            eval += activation(value) * TEval::from(weight);
+        }
+        eval
+    }
+
+    /// Clips the accumulator `acc` into the range of a byte.
+    #[inline]
+    pub fn clip(acc: &Layer, out: &mut [u8; HIDDEN_SIZE]) {
+        for (out, &val) in out.iter_mut().zip(acc) {
+            *out = val.clamp(0, QA) as u8;
+        }
+    }
+
+    /// Adds `weights` times `x` to `acc`.
+    #[inline]
+    pub fn add_product<const N: usize>(acc: &mut [i32; N], weights: &[i8; N], x: u8) {
+        for (val, &weight) in acc.iter_mut().zip(weights) {
+            *val += i32::from(weight) * i32::from(x);
+        }
+    }
+
+    const fn activation(x: TValue) -> TEval { screlu(x) }
+    const fn crelu(x: TValue) -> TEval { TEval::from(x).clamp(0, TEval::from(QA)) }
+    const fn screlu(x: TValue) -> TEval { crelu(x).pow(2) }
+}
+
+#[cfg(target_feature = "avx2")]
+pub mod vectorized {
+    use wide::{i16x8, i16x16, i32x8};
+
+    use super::{
+        super::{HIDDEN_SIZE, QA, TEval, TValue},
+        Layer,
+    };
+
+    #[inline]
+    pub fn add(acc: &mut Layer, weights: &Layer) {
+        for (val, weight) in acc.as_chunks_mut::<16>().0.iter_mut().zip(weights.as_chunks::<16>().0) {
+            *val = (i16x16::from(*val) + i16x16::from(*weight)).to_array();
+        }
+    }
+
+    #[inline]
+    pub fn sub(acc: &mut Layer, weights: &Layer) {
+        for (val, weight) in acc.as_chunks_mut::<16>().0.iter_mut().zip(weights.as_chunks::<16>().0) {
+            *val = (i16x16::from(*val) - i16x16::from(*weight)).to_array();
+        }
+    }
+
+    #[inline]
+    pub fn add_scaled(acc: &mut Layer, weights: &Layer, scale: TValue) {
+        let scale = i16x16::splat(scale);
+        for (val, weight) in acc.as_chunks_mut::<16>().0.iter_mut().zip(weights.as_chunks::<16>().0) {
+            *val = (i16x16::from(*val) + i16x16::from(*weight) * scale).to_array();
+        }
+    }
+
+    /// The dot product of the activated accumulator `acc` and `weights`.
+    ///
+    /// The squared values don't fit into 16 bits, so they are widened to 32
+    /// bits before the multiplication.
+    #[inline]
+    pub fn screlu_dot(acc: &Layer, weights: &Layer) -> TEval {
+        let (zero, qa) = (i16x8::splat(0), i16x8::splat(QA));
+        let mut sum = i32x8::splat(0);
+        for (val, weight) in acc.as_chunks::<8>().0.iter().zip(weights.as_chunks::<8>().0) {
+            let val = i32x8::from(i16x8::from(*val).max(zero).min(qa));
+            sum += val * val * i32x8::from(i16x8::from(*weight));
+        }
+        sum.reduce_add()
+    }
+
+    /// Clips the accumulator `acc` into the range of a byte.
+    #[inline]
+    pub fn clip(acc: &Layer, out: &mut [u8; HIDDEN_SIZE]) {
+        let (zero, qa) = (i16x16::splat(0), i16x16::splat(QA));
+        for (out, val) in out.as_chunks_mut::<16>().0.iter_mut().zip(acc.as_chunks::<16>().0) {
+            let clipped = i16x16::from(*val).max(zero).min(qa).to_array();
+            *out = clipped.map(|val| val as u8);
+        }
+    }
+
+    /// Adds `weights` times `x` to `acc`. `N` has to be a multiple of 8.
+    #[inline]
+    pub fn add_product<const N: usize>(acc: &mut [i32; N], weights: &[i8; N], x: u8) {
+        let x = i32x8::splat(i32::from(x));
+        for (val, weight) in acc.as_chunks_mut::<8>().0.iter_mut().zip(weights.as_chunks::<8>().0) {
+            *val = (i32x8::from(*val) + i32x8::from(i16x8::from(weight.map(i16::from))) * x).to_array();
+        }
+    }
+}
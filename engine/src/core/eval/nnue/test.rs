@@ -0,0 +1,441 @@
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+use super::{
+    buckets::InputLayout,
+    deep::{L1_SIZE, L2_SIZE, QL1},
+    format::{Compression, Header},
+    simd::{Layer, scalar},
+    *,
+};
+use crate::core::{
+    color::perspectives::White,
+    coordinates::squares::*,
+    r#move::{Move, MoveFlag, move_flags},
+    move_iter::sliding_piece::magics,
+    position::Position,
+    zobrist,
+};
+
+fn random_layer(rng: &mut SmallRng, range: std::ops::RangeInclusive<TValue>) -> Layer { std::array::from_fn(|_| rng.random_range(range.clone())) }
+
+#[test]
+#[cfg(target_feature = "avx2")]
+fn vectorized_matches_scalar() {
+    use super::simd::vectorized;
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    for _ in 0..100 {
+        // accumulators reach well beyond the clipping range of the activation
+        let acc = random_layer(&mut rng, -2 * QA..=2 * QA);
+        let weights = random_layer(&mut rng, -500..=500);
+        let scale = rng.random_range(-3..=3);
+
+        assert_eq!(vectorized::screlu_dot(&acc, &weights), scalar::screlu_dot(&acc, &weights));
+
+        let (mut expected, mut actual) = (acc, acc);
+        scalar::add(&mut expected, &weights);
+        vectorized::add(&mut actual, &weights);
+        assert_eq!(actual, expected);
+
+        scalar::sub(&mut expected, &weights);
+        vectorized::sub(&mut actual, &weights);
+        assert_eq!(actual, expected);
+
+        scalar::add_scaled(&mut expected, &weights, scale);
+        vectorized::add_scaled(&mut actual, &weights, scale);
+        assert_eq!(actual, expected);
+
+        let (mut expected, mut actual) = ([0; HIDDEN_SIZE], [0; HIDDEN_SIZE]);
+        scalar::clip(&acc, &mut expected);
+        vectorized::clip(&acc, &mut actual);
+        assert_eq!(actual, expected);
+
+        let weights: [i8; L1_SIZE] = std::array::from_fn(|_| rng.random());
+        let x = rng.random();
+        let (mut expected, mut actual) = ([1000; L1_SIZE], [1000; L1_SIZE]);
+        scalar::add_product(&mut expected, &weights, x);
+        vectorized::add_product(&mut actual, &weights, x);
+        assert_eq!(actual, expected);
+    }
+}
+
+#[test]
+fn scalar_forward() {
+    let mut rng = SmallRng::seed_from_u64(1);
+    let acc = random_layer(&mut rng, -2 * QA..=2 * QA);
+    let weights = random_layer(&mut rng, -500..=500);
+
+    let expected: TEval = acc
+        .iter()
+        .zip(&weights)
+        .map(|(&v, &w)| TEval::from(v).clamp(0, TEval::from(QA)).pow(2) * TEval::from(w))
+        .sum();
+    assert_eq!(scalar::screlu_dot(&acc, &weights), expected);
+    assert_eq!(simd::screlu_dot(&acc, &weights), expected);
+}
+
+/// A network with random weights, such that every feature has an effect.
+pub fn random_network(rng: &mut SmallRng) -> Network {
+    let mut net = Network::from_raw_bytes(&vec![0; Architecture::Shallow.size()]).unwrap();
+    for layer in net.acc_weights.iter_mut().chain([&mut net.acc_biases]) {
+        layer.vals = random_layer(rng, -100..=100);
+    }
+    net
+}
+
+/// The accumulator of `perspective`, built from scratch.
+fn accumulate(net: &Network, pos: &PieceInfo, perspective: Color) -> Layer {
+    let mut pieces = Pieces::default();
+    for c in [colors::WHITE, colors::BLACK] {
+        for pt in piece_type::PAWN..=piece_type::KING {
+            pieces[c.v() as usize][pt.v() as usize - 1] = pos.get_bitboard(pt, c);
+        }
+    }
+
+    let bucket = KingBucket::of(perspective, &pieces);
+    let mut acc = net.acc_biases.vals;
+    for sq in squares::A1..=squares::H8 {
+        let (c, pt) = pos.get_piece(sq).unpack();
+        if pt != piece_type::NONE {
+            scalar::add(&mut acc, &net.acc_weights[bucket.input_index(perspective, sq, pt, c)].vals);
+        }
+    }
+    acc
+}
+
+#[test]
+fn active_features_sum_up_to_the_accumulators() {
+    magics::init();
+    zobrist::init();
+
+    let net = random_network(&mut SmallRng::seed_from_u64(4));
+    let pos = Position::from_fen("r3k2r/pp1n1ppp/2p1pn2/q7/1bPP4/2N1PN2/PP1B1PPP/R2QKB1R b KQkq - 3 9").unwrap();
+    for perspective in [colors::WHITE, colors::BLACK] {
+        let mut acc = net.acc_biases.vals;
+        for feature in active_features(pos.piece_info(), perspective) {
+            scalar::add(&mut acc, &net.acc_weights[feature].vals);
+        }
+        assert_eq!(acc, accumulate(&net, pos.piece_info(), perspective));
+    }
+}
+
+/// Moves of both kings across the middle of the board, which mirrors their
+/// perspective with [buckets::MIRRORED], and captures.
+const MOVES: [(Square, Square, MoveFlag); 5] = [
+    (E1, G1, move_flags::KING_CASTLE),
+    (E8, D8, move_flags::QUIET),
+    (F1, F7, move_flags::QUIET),
+    (D7, D2, move_flags::CAPTURE),
+    (G1, G2, move_flags::QUIET),
+];
+
+#[test]
+fn king_buckets_mirror() {
+    let queen_side = KingBucket::new(colors::WHITE, D1);
+    let king_side = KingBucket::new(colors::WHITE, E1);
+    assert_eq!(queen_side != king_side, buckets::MIRRORED);
+    let mirrored_pawn = if buckets::MIRRORED { H2 } else { A2 };
+    assert_eq!(
+        queen_side.input_index(colors::WHITE, A2, piece_type::PAWN, colors::WHITE),
+        king_side.input_index(colors::WHITE, mirrored_pawn, piece_type::PAWN, colors::WHITE)
+    );
+
+    // both sides see their own pieces the same way
+    let black = KingBucket::new(colors::BLACK, E8);
+    assert_eq!(
+        king_side.input_index(colors::WHITE, H2, piece_type::PAWN, colors::WHITE),
+        black.input_index(colors::BLACK, H7, piece_type::PAWN, colors::BLACK)
+    );
+}
+
+#[test]
+fn input_layout_covers_all_king_squares() {
+    // the buckets a trainer without mirroring takes for each king square of the
+    // side to move, and for the flipped one of the other side
+    let buckets = buckets::InputLayout::ENGINE.all_squares();
+    for king in A1..=H8 {
+        let bucket = buckets[king.index()];
+        let expected = KingBucket::new(colors::WHITE, king);
+        assert_eq!(KingBucket::new(colors::BLACK, king.flip_v()), expected, "{king}");
+        assert_eq!(
+            expected.input_index(colors::WHITE, A2, piece_type::PAWN, colors::WHITE) / FEATURES_PER_BUCKET,
+            bucket,
+            "{king}"
+        );
+    }
+}
+
+/// The accumulator of `perspective` with the plain inputs the embedded network
+/// was trained on, without king buckets or mirroring.
+fn accumulate_plain(net: &Network, pos: &PieceInfo, perspective: Color) -> Accumulator {
+    let mut acc = Accumulator::init(net);
+    for sq in pos.get_occupancy() {
+        let (c, pt) = pos.get_piece(sq).unpack();
+        let (sq, c) = if perspective == colors::BLACK {
+            (sq.flip_v(), !c)
+        }
+        else {
+            (sq, c)
+        };
+        acc.add_feature(input_index(sq, pt, c), net);
+    }
+    acc
+}
+
+#[test]
+fn default_network_evaluates_mirrors_with_plain_inputs() {
+    magics::init();
+    zobrist::init();
+
+    let net = default_nnue();
+    // the kings are on the king side, then on the queen side of the mirror
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbkqbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKQBNR w - - 0 1",
+        "r4rk1/pp3ppp/2n1bn2/2pp4/3P4/2N1PN2/PPQ2PPP/R4RK1 w - - 0 12",
+        "1kr4r/ppp3pp/2nb1n2/4pp2/4P3/2NP1N2/PPP2QPP/1KR4R w - - 0 12",
+    ] {
+        let pos = Position::from_fen(fen).unwrap();
+        let bucket = buckets::output_bucket(pos.get_occupancy());
+        let mut accs = AccumulatorStack::new(LazyAccUpdates::default(), &net);
+        accs.get_accs_mut(Depth::ROOT).on_init(pos.piece_info());
+        let (white, black) = accs.get_mut_for::<White>(Depth::ROOT, &net);
+
+        let mut plain_white = accumulate_plain(&net, pos.piece_info(), colors::WHITE);
+        let mut plain_black = accumulate_plain(&net, pos.piece_info(), colors::BLACK);
+        assert_eq!(white.values, plain_white.values, "{fen}");
+        assert_eq!(black.values, plain_black.values, "{fen}");
+        assert_eq!(
+            net.forward(white, black, bucket),
+            net.forward(&mut plain_white, &mut plain_black, bucket),
+            "{fen}"
+        );
+    }
+}
+
+#[test]
+fn lazy_updates_match_rebuild() {
+    magics::init();
+    zobrist::init();
+
+    let net = random_network(&mut SmallRng::seed_from_u64(2));
+    let mut pos = Position::from_fen("4k3/3r4/8/8/8/8/3P4/4K2R w K - 0 1").unwrap();
+    let mut accs = AccumulatorStack::new(LazyAccUpdates::default(), &net);
+    accs.get_accs_mut(Depth::ROOT).on_init(pos.piece_info());
+
+    let mut ply = Depth::ROOT;
+    for (i, (from, to, flag)) in MOVES.into_iter().enumerate() {
+        accs.propagate(ply, ply + 1);
+        ply += 1;
+        pos.make_move(Move::new(from, to, flag), accs.get_accs_mut(ply));
+
+        // the plies in between are computed with the next evaluation
+        if i % 2 == 0 {
+            continue;
+        }
+
+        let (white, black) = accs.get_mut_for::<White>(ply, &net);
+        assert_eq!(white.values, accumulate(&net, pos.piece_info(), colors::WHITE));
+        assert_eq!(black.values, accumulate(&net, pos.piece_info(), colors::BLACK));
+    }
+}
+
+#[test]
+fn eager_updates_match_rebuild() {
+    magics::init();
+    zobrist::init();
+
+    let net = Arc::new(random_network(&mut SmallRng::seed_from_u64(3)));
+    let mut cache = RefreshCache::new(&net);
+    let mut pos = Position::from_fen("4k3/3r4/8/8/8/8/3P4/4K2R w K - 0 1").unwrap();
+    let mut accs = AccumulatorPair::new(EagerAccUpdates::new(net.clone()), &net);
+    accs.on_init(pos.piece_info());
+
+    for (from, to, flag) in MOVES {
+        pos.make_move(Move::new(from, to, flag), &mut accs);
+
+        accs.refresh(&mut cache, &net);
+        assert_eq!(accs.white.values, accumulate(&net, pos.piece_info(), colors::WHITE));
+        assert_eq!(accs.black.values, accumulate(&net, pos.piece_info(), colors::BLACK));
+    }
+}
+
+#[test]
+fn output_buckets_cover_all_piece_counts() {
+    magics::init();
+    zobrist::init();
+
+    let kings = Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let full = Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    assert_eq!(buckets::output_bucket(kings.get_occupancy()), 0);
+    assert_eq!(buckets::output_bucket(full.get_occupancy()), N_OUTPUT_BUCKETS - 1);
+
+    // the buckets never decrease with more material
+    let mut occupancy = kings.get_occupancy();
+    let mut last = 0;
+    for sq in squares::A2..=squares::H7 {
+        occupancy |= Bitboard::from(sq);
+        if occupancy.pop_cnt() > 32 {
+            break;
+        }
+        let bucket = buckets::output_bucket(occupancy);
+        assert!(bucket >= last && bucket < N_OUTPUT_BUCKETS);
+        last = bucket;
+    }
+}
+
+/// The bytes of a network with zeroed accumulator weights and the `head`.
+fn network_bytes(head: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = vec![0; size_of::<[HiddenLayer; INPUT_SIZE]>() + size_of::<HiddenLayer>()];
+    bytes.extend(head.concat());
+    bytes
+}
+
+#[test]
+fn network_from_bytes() {
+    // raw networks of the trainer are shallow, with the padding of the trainer,
+    // and only load as such
+    let mut bytes = vec![0; Architecture::Shallow.size()];
+    assert_eq!(Network::from_raw_bytes(&bytes).unwrap().architecture(), Architecture::Shallow);
+    bytes.resize(bytes.len().next_multiple_of(64), 0);
+    assert_eq!(Network::from_raw_bytes(&bytes).unwrap().architecture(), Architecture::Shallow);
+    assert!(matches!(Network::from_bytes(&bytes), Err(LoadNnueError::MissingHeader)));
+
+    let layers = (0..Architecture::Deep.size()).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+    for compression in [Compression::None, Compression::Zlib] {
+        let deep = format::encode(Architecture::Deep, InputLayout::ENGINE, &layers, compression);
+        assert_eq!(Network::from_bytes(&deep).unwrap().architecture(), Architecture::Deep);
+    }
+
+    // the header decides the expected size
+    let wrong = format::encode(Architecture::Deep, InputLayout::ENGINE, &bytes, Compression::None);
+    assert!(matches!(Network::from_bytes(&wrong), Err(LoadNnueError::Size { .. })));
+}
+
+/// Changes the header of the network file `bytes`.
+fn edit_header(bytes: &[u8], edit: impl FnOnce(&mut Header)) -> Vec<u8> {
+    let mut header: Header = bytemuck::pod_read_unaligned(&bytes[..size_of::<Header>()]);
+    edit(&mut header);
+    [bytemuck::bytes_of(&header), &bytes[size_of::<Header>()..]].concat()
+}
+
+#[test]
+fn corrupt_network_files() {
+    let layers = (0..Architecture::Shallow.size()).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+    let bytes = format::encode(Architecture::Shallow, InputLayout::ENGINE, &layers, Compression::Zlib);
+
+    let load = |bytes: &[u8]| Network::from_bytes(bytes).err().expect("The network should be rejected.");
+    assert!(matches!(load(&bytes[..bytes.len() - 1]), LoadNnueError::Truncated { .. }));
+    assert!(matches!(load(&bytes[..10]), LoadNnueError::Truncated { .. }));
+    assert!(matches!(
+        load(&edit_header(&bytes, |header| header.version += 1)),
+        LoadNnueError::UnsupportedVersion(_)
+    ));
+    assert!(matches!(
+        load(&edit_header(&bytes, |header| header.descriptor.architecture = 42)),
+        LoadNnueError::UnknownArchitecture(42)
+    ));
+    assert!(matches!(
+        load(&edit_header(&bytes, |header| header.descriptor.hidden_size *= 2)),
+        LoadNnueError::Descriptor { .. }
+    ));
+    assert!(matches!(
+        load(&edit_header(&bytes, |header| header.descriptor.input_layout ^= 1)),
+        LoadNnueError::Descriptor { .. }
+    ));
+    assert!(matches!(
+        load(&edit_header(&bytes, |header| header.compression = 42)),
+        LoadNnueError::UnknownCompression(42)
+    ));
+    assert!(matches!(
+        load(&edit_header(&bytes, |header| header.checksum ^= 1)),
+        LoadNnueError::Checksum { .. }
+    ));
+
+    let mut corrupt = format::encode(Architecture::Shallow, InputLayout::ENGINE, &layers, Compression::None);
+    *corrupt.last_mut().unwrap() ^= 1;
+    assert!(matches!(load(&corrupt), LoadNnueError::Checksum { .. }));
+}
+
+#[test]
+fn networks_of_other_inputs_are_rejected() {
+    let layers = (0..Architecture::Shallow.size()).map(|i| (i % 5) as u8).collect::<Vec<_>>();
+    let load = |inputs: InputLayout| Network::from_bytes(&format::encode(Architecture::Shallow, inputs, &layers, Compression::None));
+    assert!(load(InputLayout::ENGINE).is_ok());
+
+    // the same size, but trained with the other mirroring or other king buckets
+    let mirrored = InputLayout {
+        mirrored: !buckets::MIRRORED,
+        ..InputLayout::ENGINE
+    };
+    let mut bucketed = InputLayout::ENGINE;
+    bucketed.king_buckets[31] += 1;
+    for inputs in [mirrored, bucketed] {
+        assert!(
+            matches!(load(inputs), Err(LoadNnueError::Descriptor { actual, .. }) if actual.input_layout == inputs.hash()),
+            "{inputs:?}"
+        );
+    }
+}
+
+#[test]
+fn deep_forward_matches_dense() {
+    let mut rng = SmallRng::seed_from_u64(3);
+    let mut floats = |n| (0..n).map(|_| rng.random_range(-1.0..1.0)).collect::<Vec<f32>>();
+    let (l1_biases, l2_weights, l2_biases) = (
+        floats(OUTPUT_SIZE * L1_SIZE),
+        floats(L1_SIZE * OUTPUT_SIZE * L2_SIZE),
+        floats(OUTPUT_SIZE * L2_SIZE),
+    );
+    let (l3_weights, l3_biases) = (floats(L2_SIZE * OUTPUT_SIZE), floats(OUTPUT_SIZE));
+    let l1_weights = (0..2 * HIDDEN_SIZE * OUTPUT_SIZE * L1_SIZE)
+        .map(|_| rng.random_range(-127..=127))
+        .collect::<Vec<i8>>();
+
+    let bytes = network_bytes(&[
+        bytemuck::cast_slice(&l1_weights),
+        bytemuck::cast_slice(&l1_biases),
+        bytemuck::cast_slice(&l2_weights),
+        bytemuck::cast_slice(&l2_biases),
+        bytemuck::cast_slice(&l3_weights),
+        bytemuck::cast_slice(&l3_biases),
+    ]);
+    let net = Network::from_bytes(&format::encode(Architecture::Deep, InputLayout::ENGINE, &bytes, Compression::None)).unwrap();
+
+    let screlu = |x: f32| x.clamp(0., 1.).powi(2);
+    for _ in 0..20 {
+        // most of the clipped accumulators are zero
+        let (mut stm, mut nstm) = (Accumulator::init(&net), Accumulator::init(&net));
+        stm.values = random_layer(&mut rng, -2 * QA..=QA);
+        nstm.values = random_layer(&mut rng, -2 * QA..=QA);
+        let bucket = rng.random_range(0..OUTPUT_SIZE);
+
+        let input = stm
+            .values
+            .iter()
+            .chain(&nstm.values)
+            .map(|&v| i32::from(v.clamp(0, QA)))
+            .collect::<Vec<_>>();
+        let l1 = (0..L1_SIZE)
+            .map(|j| {
+                let sum: i32 = (0..input.len())
+                    .map(|i| input[i] * i32::from(l1_weights[(i * OUTPUT_SIZE + bucket) * L1_SIZE + j]))
+                    .sum();
+                screlu(sum as f32 * (1. / (f32::from(QA) * f32::from(QL1))) + l1_biases[bucket * L1_SIZE + j])
+            })
+            .collect::<Vec<_>>();
+        let mut l2 = l2_biases[bucket * L2_SIZE..][..L2_SIZE].to_vec();
+        for (i, x) in l1.into_iter().enumerate() {
+            for (j, out) in l2.iter_mut().enumerate() {
+                *out += x * l2_weights[(i * OUTPUT_SIZE + bucket) * L2_SIZE + j];
+            }
+        }
+        let mut out = l3_biases[bucket];
+        for (i, x) in l2.into_iter().enumerate() {
+            out += screlu(x) * l3_weights[i * OUTPUT_SIZE + bucket];
+        }
+
+        let expected = AnyScore::from((out * f32::from(SCALE)) as TEval);
+        assert_eq!(net.forward(&mut stm, &mut nstm, bucket), expected);
+    }
+}
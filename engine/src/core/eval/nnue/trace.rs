@@ -0,0 +1,56 @@
+//! How the network evaluates a position, broken down into its pieces.
+
+use std::fmt;
+
+use crate::core::{
+    coordinates::{File, Rank, Square, squares},
+    piece::Piece,
+    search::score::AnyScore,
+};
+
+/// The evaluation of a position by the network, from White's perspective.
+pub struct Trace {
+    /// The piece on each square, and how much the eval drops if the piece is
+    /// removed. The kings can't be removed.
+    pub squares: [(Piece, Option<AnyScore>); squares::N_VARIANTS],
+
+    /// The output bucket of the position, see [super::buckets::output_bucket].
+    pub bucket: usize,
+
+    /// The output of the network before it is scaled into centipawns.
+    pub raw: f32,
+
+    pub eval: AnyScore,
+}
+
+fn pawns(score: AnyScore) -> String { format!("{:+.2}", score.v() as f32 / 100.) }
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = "+-------".repeat(8) + "+";
+
+        writeln!(f, "{separator}")?;
+        for rank in (0..=7).rev() {
+            let row = (0..=7).map(|file| {
+                let sq = Square::from((File::try_from(file).unwrap(), Rank::try_from(rank).unwrap()));
+                self.squares[sq.v() as usize]
+            });
+
+            for (piece, _) in row.clone() {
+                let c: char = piece.into();
+                write!(f, "|{c:^7}")?;
+            }
+            writeln!(f, "|")?;
+
+            for (_, contribution) in row {
+                write!(f, "|{:^7}", contribution.map(pawns).unwrap_or_default())?;
+            }
+            writeln!(f, "|")?;
+            writeln!(f, "{separator}")?;
+        }
+
+        writeln!(f, "Output bucket:  {}", self.bucket)?;
+        writeln!(f, "Raw output:     {:.4}", self.raw)?;
+        write!(f, "Eval (White):   {}", pawns(self.eval))
+    }
+}
@@ -0,0 +1,222 @@
+//! Pawn-structure terms of the hand-crafted evaluation.
+//!
+//! The terms in here only depend on where the pawns are, so they are computed
+//! once per pawn structure and cached in a [`PawnTable`] keyed by the pawn key
+//! of the position.
+
+use crate::core::{
+    bitboard::{Bitboard, BitboardIteratorExt},
+    color::{Color, Perspective, colors, perspectives},
+    coordinates::{files, pawn_utils::single_step},
+    eval::hce::{HceParams, TaperValue},
+    move_iter::pawn,
+    piece::piece_type,
+    position::PieceInfo,
+    search::{
+        data::{ReplacementStrategy, TTKey, TranspositionTable},
+        score::{AnyScore, Score},
+    },
+    zobrist,
+};
+
+#[cfg(test)] pub mod test;
+
+/// `(mg, eg)` bonus of a passed pawn that is not behind another of our passed
+/// pawns.
+const PASSER: (i32, i32) = (30, 30);
+/// `(mg, eg)` bonus of a passed pawn that is protected by one of our pawns.
+const PROTECTED_PASSER: (i32, i32) = (50, 50);
+/// `(mg, eg)` bonus of a pawn that can become a passed pawn by advancing.
+const CANDIDATE: (i32, i32) = (10, 25);
+
+pub type PawnTable = TranspositionTable<PawnEntry, PawnReplace>;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PawnEntry {
+    key: zobrist::Hash,
+    passed: [Bitboard; colors::N_VARIANTS],
+    mg: [i32; colors::N_VARIANTS],
+    eg: [i32; colors::N_VARIANTS],
+}
+
+/// The default entry is the (correct) entry of a position without pawns.
+const impl Default for PawnEntry {
+    fn default() -> Self {
+        Self {
+            key: zobrist::Hash::default(),
+            passed: [Bitboard::empty(); colors::N_VARIANTS],
+            mg: [0; colors::N_VARIANTS],
+            eg: [0; colors::N_VARIANTS],
+        }
+    }
+}
+
+const impl TTKey for PawnEntry {
+    fn key(&self) -> zobrist::Hash { self.key }
+}
+
+impl PawnEntry {
+    pub fn new(pos: &PieceInfo, key: zobrist::Hash, params: &impl HceParams) -> Self {
+        let mut entry = Self { key, ..Default::default() };
+        for (c, features) in [
+            (colors::WHITE, PawnFeatures::of::<perspectives::White>(pos)),
+            (colors::BLACK, PawnFeatures::of::<perspectives::Black>(pos)),
+        ] {
+            let c = c.v() as usize;
+            entry.passed[c] = features.passed;
+            for (_, mg, eg) in features.terms(params) {
+                entry.mg[c] += mg;
+                entry.eg[c] += eg;
+            }
+        }
+        entry
+    }
+
+    /// The passed pawns of `color`.
+    #[inline]
+    pub fn passed(&self, color: Color) -> Bitboard { self.passed[color.v() as usize] }
+
+    /// The tapered pawn-structure score of `P`.
+    #[inline]
+    pub fn score<P: Perspective>(&self, phase: TaperValue) -> Score<P> {
+        let c = P::COLOR.v() as usize;
+        let score = phase.weighted_eval(AnyScore::new(self.mg[c]), AnyScore::new(self.eg[c]));
+        unsafe { score.interpret_as() }
+    }
+}
+
+/// The pawns of one side, by the pawn-structure terms they are subject to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PawnFeatures {
+    pub passed: Bitboard,
+    /// Passed pawns that are not behind another of our passed pawns.
+    pub primary_passed: Bitboard,
+    /// Passed pawns protected by one of our pawns.
+    pub protected_passed: Bitboard,
+    /// Not yet passed pawns on a half-open file, which have at least as many
+    /// helpers as sentries.
+    pub candidates: Bitboard,
+    /// Pawns without friendly pawns on the adjacent files.
+    pub isolated: Bitboard,
+    /// Pawns in front of another of our pawns.
+    pub doubled: Bitboard,
+    /// Pawns whose stop square is attacked by their pawns, and which cannot be
+    /// protected by our pawns advancing.
+    pub backward: Bitboard,
+}
+
+impl PawnFeatures {
+    pub fn of<P: Perspective>(pos: &PieceInfo) -> Self {
+        let us = P::COLOR;
+        let them = !us;
+
+        let our_pawns = pos.get_bitboard(piece_type::PAWN, us);
+        let our_attacks = pawn::compute_attacks(our_pawns, us);
+        let their_pawns = pos.get_bitboard(piece_type::PAWN, them);
+        let their_attacks = pawn::compute_attacks(their_pawns, them);
+
+        let passed = our_pawns & !front_fill(their_pawns | their_attacks, them);
+        let primary_passed = passed & !rear_span(passed, us);
+        let protected_passed = passed & our_attacks;
+
+        let isolated = our_pawns & !sideways(file_fill(our_pawns));
+
+        let doubled = our_pawns & front_span(our_pawns, us);
+
+        let stops = forward(our_pawns, us);
+        let unsupported_stops = stops & their_attacks & !front_fill(our_attacks, us);
+        let backward = our_pawns & forward(unsupported_stops, them) & !isolated;
+
+        let half_open = our_pawns & !passed & !front_span(their_pawns, them);
+        let candidates = half_open
+            .filter(|&sq| {
+                let pawn = Bitboard::from(sq);
+                let sentries = their_pawns & front_fill(pawn::compute_attacks(pawn, us), us);
+                let helpers = our_pawns & rear_fill(sideways(pawn), us);
+                helpers.pop_cnt() >= sentries.pop_cnt()
+            })
+            .map(Bitboard::from)
+            .aggregate();
+
+        Self {
+            passed,
+            primary_passed,
+            protected_passed,
+            candidates,
+            isolated,
+            doubled,
+            backward,
+        }
+    }
+
+    /// `(name, mg, eg)` of the individual pawn-structure terms.
+    pub fn terms(&self, params: &impl HceParams) -> [(&'static str, i32, i32); 6] {
+        let term = |name, pawns: Bitboard, (mg, eg): (i32, i32)| (name, pawns.pop_cnt() as i32 * mg, pawns.pop_cnt() as i32 * eg);
+        [
+            term("Passed Pawns", self.primary_passed, PASSER),
+            term("Protected Pass", self.protected_passed, PROTECTED_PASSER),
+            term("Candidates", self.candidates, CANDIDATE),
+            term("Isolated Pawns", self.isolated, (params.isolated_mg(), params.isolated_eg())),
+            term("Doubled Pawns", self.doubled, (params.doubled_mg(), params.doubled_eg())),
+            term("Backward Pawns", self.backward, (params.backward_mg(), params.backward_eg())),
+        ]
+    }
+}
+
+impl PawnTable {
+    /// Returns the pawn structure of `pos`, computing and caching it if it is
+    /// not in the table yet.
+    #[inline]
+    pub fn probe(&mut self, pos: &PieceInfo, key: zobrist::Hash, params: &impl HceParams) -> PawnEntry {
+        if let Some(entry) = self.get(key) {
+            return *entry;
+        }
+
+        let entry = PawnEntry::new(pos, key, params);
+        self.try_insert(entry);
+        entry
+    }
+}
+
+pub struct PawnReplace;
+
+impl ReplacementStrategy for PawnReplace {
+    type Data = PawnEntry;
+
+    fn should_replace(_old: &PawnEntry, _new: &PawnEntry) -> bool { true }
+}
+
+#[inline]
+fn forward(bb: Bitboard, c: Color) -> Bitboard { bb.shift(single_step(c)) }
+
+#[inline]
+pub(super) fn front_fill(bb: Bitboard, c: Color) -> Bitboard {
+    match c {
+        colors::WHITE => bb.fill::<8 /* north */>(),
+        colors::BLACK => bb.fill::<-8 /* south */>(),
+        _ => unreachable!(),
+    }
+}
+
+#[inline]
+pub(super) fn front_span(bb: Bitboard, c: Color) -> Bitboard {
+    match c {
+        colors::WHITE => bb.span::<8 /* north */>(),
+        colors::BLACK => bb.span::<-8 /* south */>(),
+        _ => unreachable!(),
+    }
+}
+
+#[inline]
+fn rear_fill(bb: Bitboard, c: Color) -> Bitboard { front_fill(bb, !c) }
+
+#[inline]
+pub(super) fn rear_span(bb: Bitboard, c: Color) -> Bitboard { front_span(bb, !c) }
+
+#[inline]
+fn file_fill(bb: Bitboard) -> Bitboard { front_fill(bb, colors::WHITE) | rear_fill(bb, colors::WHITE) }
+
+#[inline]
+fn sideways(bb: Bitboard) -> Bitboard {
+    bb.and_not_c(Bitboard::from(files::A)).shift_c::<-1 /* west */>() | bb.and_not_c(Bitboard::from(files::H)).shift_c::<1 /* east */>()
+}
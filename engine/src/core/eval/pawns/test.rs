@@ -0,0 +1,52 @@
+use super::*;
+use crate::core::{move_iter::sliding_piece::magics, params::C_IdHceParams, position::Position};
+
+/// `(mg, eg)` of white and black.
+fn terms(fen: &str) -> [(i32, i32); colors::N_VARIANTS] {
+    zobrist::init();
+    magics::init();
+
+    let pos = Position::from_fen(fen).unwrap();
+    let entry = PawnEntry::new(pos.piece_info(), pos.get_pawn_key(), &C_IdHceParams);
+    [0, 1].map(|c| (entry.mg[c], entry.eg[c]))
+}
+
+#[test]
+fn isolated_passers() {
+    // two isolated passed pawns, neither one behind the other
+    assert_eq!(terms("4k3/8/8/8/8/8/P1P5/4K3 w - - 0 1"), [(40, 30), (0, 0)]);
+}
+
+#[test]
+fn doubled_passers() {
+    // only the front pawn counts as passer, the rear one is the doubled one
+    assert_eq!(terms("4k3/8/8/8/8/P7/P7/4K3 w - - 0 1"), [(0, -20), (0, 0)]);
+}
+
+#[test]
+fn backward_pawn() {
+    // d3 cannot advance past c5 and has no pawn that could protect d4, e4 is a
+    // protected passer. c5 is isolated, which is not counted as backward too.
+    assert_eq!(terms("4k3/8/8/2p5/4P3/3P4/8/4K3 w - - 0 1"), [(72, 68), (-10, -15)]);
+}
+
+#[test]
+fn candidate_passer() {
+    // c3 is only stopped by b6, which b3 can trade off
+    assert_eq!(terms("4k3/8/1p6/8/8/1PP5/8/4K3 w - - 0 1"), [(10, 25), (-10, -15)]);
+}
+
+#[test]
+fn probe_caches_entries() {
+    zobrist::init();
+    magics::init();
+
+    let pos = Position::from_fen("4k3/8/8/2p5/4P3/3P4/8/4K3 w - - 0 1").unwrap();
+    let key = pos.get_pawn_key();
+    let mut table = PawnTable::new(1024);
+
+    assert!(table.get(key).is_none());
+    let entry = table.probe(pos.piece_info(), key, &C_IdHceParams);
+    assert_eq!(table.get(key), Some(&entry));
+    assert_eq!(table.probe(pos.piece_info(), key, &C_IdHceParams), entry);
+}
@@ -1,9 +1,10 @@
 use crate::core::{
     eval::{
-        StaticEvaluator,
+        EvalKeys, StaticEvaluator,
         hce::{self},
+        pawns::PawnFeatures,
     },
-    params::{IParams, MctsHceParams},
+    params::{IParams, MctsHceParams, MctsHceParamsRef},
     position::PieceInfoObserver,
     search::{
         id,
@@ -25,7 +26,10 @@ use crate::{
         depth::Depth,
         r#move::{Move, MoveList, SanParseError},
         piece::piece_type,
-        position::{EpdLineImport, EpdLineParseError, EpdOp, FenExport, FenImport, FenParseError, PgnImport, PgnImportError, Position, ReducedPgn},
+        position::{
+            EpdLineImport, EpdLineParseError, EpdOp, FenExport, FenImport, FenParseError, PgnImport, PgnImportError, Position, ReducedPgn,
+            Validation, pgn::PgnReader,
+        },
         search::{Command, PonderToken, SearchThread, SearchWorker, limit::UciLimit, mcts::node::WinRate},
     },
     misc::{CancellationToken, DebugMode, List, trim_newline},
@@ -86,10 +90,14 @@ impl Game {
 
     pub fn from_position(position: Position) -> Self { Self { position, ..Default::default() } }
 
-    pub fn from_fen(fen: FenImport<'_, '_>) -> Result<Self, FenParseError> { Ok(Self::from_position(Position::try_from(fen)?)) }
+    /// The game from the position of `fen`, which is validated in the given
+    /// `mode`.
+    pub fn from_fen(fen: FenImport<'_, '_>, mode: Validation) -> Result<Self, FenParseError> { Ok(Self::from_position(fen.parse(mode)?)) }
 
-    pub fn from_epd(epd: EpdLineImport<'_, '_>, obsv: &mut impl PieceInfoObserver) -> Result<Self, EpdImportError> {
-        let (pos, ops) = epd.try_into()?;
+    /// The game from the position of `epd`, which is validated in the given
+    /// `mode` before its `sm` move is played.
+    pub fn from_epd(epd: EpdLineImport<'_, '_>, mode: Validation, obsv: &mut impl PieceInfoObserver) -> Result<Self, EpdImportError> {
+        let (pos, ops) = epd.parse(mode)?;
         let mut game = Self::from_position(pos);
 
         if let Some(op) = ops.iter().find(|op| matches!(op.0.as_ref(), "sm")) {
@@ -101,10 +109,12 @@ impl Game {
         Ok(game)
     }
 
-    pub fn from_pgn(pgn: PgnImport<'_, '_>, obsv: &mut impl PieceInfoObserver) -> Result<Self, PgnImportError> {
-        let pgn = ReducedPgn::try_from(pgn.0)?;
+    /// The game of `pgn`, where a set-up position is validated in the given
+    /// `mode` before the moves are played.
+    pub fn from_pgn(pgn: PgnImport<'_, '_>, mode: Validation, obsv: &mut impl PieceInfoObserver) -> Result<Self, PgnImportError> {
+        let pgn = PgnReader::new(pgn.0).read_game()?.ok_or(PgnImportError::MissingGame)?;
 
-        let position = pgn.start_position()?;
+        let position = pgn.start_position(mode)?;
         let mut game = Self::from_position(position);
         for san in pgn.moves() {
             let mov = Move::from_san(san, &game.position)?;
@@ -299,6 +309,10 @@ pub fn execute_uci(engine: &mut Engine, command: impl Into<String>, cancellation
             let pieces = pos.piece_info();
             let ep_sq = pos.get_ep_target_square();
 
+            let config = engine.config.lock().expect("Config dead :(");
+            let pawn_hash = config.pawn_hash();
+            let hce_params = MctsHceParams::try_from_config(config)?;
+
             if matches!(cmd, None | Some("psqt")) {
                 println!("Phase:          {phase:?}");
             }
@@ -335,16 +349,67 @@ pub fn execute_uci(engine: &mut Engine, command: impl Into<String>, cancellation
                 println!("Open King File: {penalty_w:>16} - {penalty_b:<16}");
             }
 
-            if matches!(cmd, None | Some("passed_pawns")) {
-                let passed_pawns_w = hce::passed_pawns::<White>(pieces, ep_sq, turn);
-                let passed_pawns_b = hce::passed_pawns::<Black>(pieces, ep_sq, turn);
-                println!("Passed Pawns:   {passed_pawns_w:>16} - {passed_pawns_b:<16}");
+            let pawn_features_w = PawnFeatures::of::<White>(pieces);
+            let pawn_features_b = PawnFeatures::of::<Black>(pieces);
+            for ((name, mg_w, eg_w), (_, mg_b, eg_b)) in pawn_features_w.terms(&hce_params).into_iter().zip(pawn_features_b.terms(&hce_params)) {
+                if cmd.is_none_or(|cmd| cmd == name.to_lowercase().replace(' ', "_")) {
+                    let term_w = hce::tapered::<White>(phase, mg_w, eg_w);
+                    let term_b = hce::tapered::<Black>(phase, mg_b, eg_b);
+                    println!("{:<16}{term_w:>16} - {term_b:<16}", format!("{name}:"));
+                }
+            }
+
+            if matches!(cmd, None | Some("rooks_behind_passers")) {
+                let rooks_w = hce::rooks_behind_passers::<White>(pieces, pawn_features_w.passed);
+                let rooks_b = hce::rooks_behind_passers::<Black>(pieces, pawn_features_b.passed);
+                println!("Rooks/Passers:  {rooks_w:>16} - {rooks_b:<16}");
+            }
+
+            if matches!(cmd, None | Some("rook_files")) {
+                let rook_files_w = hce::rook_files::<White>(pieces, phase, &hce_params);
+                let rook_files_b = hce::rook_files::<Black>(pieces, phase, &hce_params);
+                println!("Rook Files:     {rook_files_w:>16} - {rook_files_b:<16}");
+            }
+
+            if matches!(cmd, None | Some("rook_on_seventh")) {
+                let rook_on_seventh_w = hce::rook_on_seventh::<White>(pieces, phase, &hce_params);
+                let rook_on_seventh_b = hce::rook_on_seventh::<Black>(pieces, phase, &hce_params);
+                println!("Rook On 7th:    {rook_on_seventh_w:>16} - {rook_on_seventh_b:<16}");
+            }
+
+            if matches!(cmd, None | Some("outposts")) {
+                let outposts_w = hce::outposts::<White>(pieces, phase, &hce_params);
+                let outposts_b = hce::outposts::<Black>(pieces, phase, &hce_params);
+                println!("Outposts:       {outposts_w:>16} - {outposts_b:<16}");
+            }
+
+            if matches!(cmd, None | Some("king_attack")) {
+                let king_attack_w = hce::king_attack::<White>(pieces, phase, &hce_params);
+                let king_attack_b = hce::king_attack::<Black>(pieces, phase, &hce_params);
+                println!("King Attack:    {king_attack_w:>16} - {king_attack_b:<16}");
+            }
+
+            if matches!(cmd, None | Some("threats")) {
+                let threats_w = hce::threats::<White>(pieces, phase, &hce_params);
+                let threats_b = hce::threats::<Black>(pieces, phase, &hce_params);
+                println!("Threats:        {threats_w:>16} - {threats_b:<16}");
+            }
+
+            if matches!(cmd, None | Some("hanging")) {
+                let hanging_w = hce::hanging::<White>(pieces, phase, &hce_params);
+                let hanging_b = hce::hanging::<Black>(pieces, phase, &hce_params);
+                println!("Hanging:        {hanging_w:>16} - {hanging_b:<16}");
+            }
+
+            if matches!(cmd, None | Some("space")) {
+                let space_w = hce::space::<White>(pieces, phase, &hce_params);
+                let space_b = hce::space::<Black>(pieces, phase, &hce_params);
+                println!("Space:          {space_w:>16} - {space_b:<16}");
             }
 
-            let config = engine.config.lock().expect("Config dead :(");
             let moves = pos.collect_legals(MoveList::new());
-            let hce_params = MctsHceParams::try_from_config(config)?;
-            let eval = mcts::eval::hce::EvalInfo::new(moves.clone(), &mut pos, hce_params);
+            let mut static_eval = mcts::eval::hce::StaticEvaluator::new(pawn_hash, MctsHceParamsRef::clone(&hce_params));
+            let eval = mcts::eval::hce::EvalInfo::new(moves.clone(), &mut pos, hce_params, &mut static_eval);
 
             if matches!(cmd, None | Some("centipawns")) {
                 let quality = eval.quality();
@@ -391,16 +456,27 @@ pub fn execute_uci(engine: &mut Engine, command: impl Into<String>, cancellation
             let ep_sq = pos.get_ep_target_square();
             let phase = hce::TaperValue::from_position(pieces);
 
-            let mut eval = id::NnueEvaluator::default();
-            eval.observe_forward().on_init(pieces);
+            // the network of the config, like the one of the search
+            let config = engine.config.lock().map_err(|e| e.to_string())?;
+            let mut eval: id::NnueEvaluator = id::NnueEvaluator::try_from_config(&*config).map_err(|e| e.to_string())?;
+            let mut hce_eval = id::HceEvaluator::try_from_config(&*config).map_err(|e| e.to_string())?;
 
-            let eval_w = eval.eval::<White>(pieces, turn, ep_sq, phase);
-            let eval_b = eval.eval::<Black>(pieces, turn, ep_sq, phase);
+            // the eval change when each piece is removed
+            let trace = eval.trace(pos);
+            println!("{trace}");
+
+            eval.observe_forward().on_init(pieces);
+            let eval_w = eval.eval::<White>(pieces, turn, ep_sq, phase, EvalKeys::of(pos));
+            let eval_b = eval.eval::<Black>(pieces, turn, ep_sq, phase, EvalKeys::of(pos));
 
             println!("NNUE Evaluation:");
             println!("  White: {eval_w}");
             println!("  Black: {eval_b}");
 
+            let hce_w = hce_eval.eval::<White>(pieces, turn, ep_sq, phase, EvalKeys::of(pos)).0;
+            println!("HCE Evaluation (White): {hce_w}");
+            println!("NNUE - HCE (White):     {}", trace.eval - hce_w);
+
             Ok(())
         }
         Some("position") => {
@@ -442,11 +518,13 @@ pub fn execute_uci(engine: &mut Engine, command: impl Into<String>, cancellation
             }
             // Otherwise, we have a diverging position string.
             else {
-                // 1. Parse the new base position into a temporary game state
+                // 1. Parse the new base position into a temporary game state. Illegal
+                // base positions are rejected before they can reach the search.
+                let validation = engine.config.lock().map(|c| c.position_validation()).unwrap_or_default();
                 let mut new_game = match tokenizer.next_token() {
-                    Some("pgn") => Game::from_pgn(PgnImport(&mut tokenizer), &mut ())?,
-                    Some("epd") => Game::from_epd(EpdLineImport(&mut tokenizer), &mut ())?,
-                    Some("fen") => Game::from_fen(FenImport(&mut tokenizer))?,
+                    Some("pgn") => Game::from_pgn(PgnImport(&mut tokenizer), validation, &mut ())?,
+                    Some("epd") => Game::from_epd(EpdLineImport(&mut tokenizer), validation, &mut ())?,
+                    Some("fen") => Game::from_fen(FenImport(&mut tokenizer), validation)?,
                     Some("startpos") => Game::from_position(Position::start_position()),
                     None => return Err(UciError::MissingArgument("value").into()),
                     Some(x) => {
@@ -568,6 +646,11 @@ pub fn execute_uci(engine: &mut Engine, command: impl Into<String>, cancellation
             engine.search_t.tx.send(Command::IsReady)?;
             Ok(())
         }
+        Some("bench") => {
+            let depth = tokenizer.next_token().map(str::parse::<Depth>).transpose()?.unwrap_or(id::BENCH_DEPTH);
+            engine.search_t.tx.send(Command::Bench(depth))?;
+            Ok(())
+        }
         Some("perf") => {
             execute_uci(engine, "go".to_owned(), cancellation_token.clone())?;
             execute_uci(engine, "go".to_owned(), cancellation_token.clone())?;
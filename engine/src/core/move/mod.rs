@@ -449,7 +449,7 @@ impl<'a, 'b> TryFrom<StandardAlgebraicNotationParser<'a, 'b>> for Move {
         // and "O-O-O" for queenside castling. While the FIDE handbook uses the
         // digit zero, the SAN PGN-Standard requires the capital letter 'O' for its
         // export format.
-        match san {
+        match san.trim_end_matches(['+', '#']) {
             "O-O" | "0-0" => {
                 return Ok(Move::new(
                     Square::from((files::E, rank0)),
@@ -329,6 +329,16 @@ pub mod opt {
         fn legal() -> bool { true }
     }
 
+    pub struct QuietChecks;
+    const impl Options for QuietChecks {
+        fn quiet_checks() -> bool { true }
+        fn quiet_nochecks() -> bool { false }
+        fn capture_checks() -> bool { false }
+        fn capture_nochecks() -> bool { false }
+        fn promo_checks() -> bool { false }
+        fn promo_nochecks() -> bool { false }
+    }
+
     pub struct Checks;
     impl Options for Checks {
         fn quiet_checks() -> bool { true }
@@ -7,7 +7,7 @@ use crate::{
         chrono::ChronoParams,
         config::{ConfigBuilder, Configuration},
         depth::Depth,
-        eval::hce::TaperValue,
+        eval::hce::{HceParams, TaperValue},
         search::{
             id::{IdParams, ScorerParams},
             mcts::{eval::hce::PolicyParams, node::VisitCount, search::MctsParams, select::puct::PuctParams},
@@ -133,6 +133,14 @@ macro_rules! tunable_params {
                     )*
                 }
 
+                impl [<$trait_name Group>] {
+                    /// Calls `f` with the getter name and a mutable reference of
+                    /// every field, e.g. to tune them from outside the engine.
+                    pub fn visit_fields_mut(&mut self, mut f: impl FnMut(&'static str, &mut dyn std::any::Any)) {
+                        $( f(stringify!($getter), &mut self.$field); )*
+                    }
+                }
+
                 // individual group config struct
                 #[derive(Debug, Clone)]
                 pub struct [<$trait_name ConfigGroup>] {
@@ -283,6 +291,26 @@ tunable_params! {
             to_raw: |t: TaperValue| t.v(),
             from_raw: |v: &i32| TaperValue::new(*v),
         },
+        see_margin: AnyScore {
+            uci: "qs-see-margin",
+            unit: UciInteger,
+            default: AnyScore::new(0),
+            min: AnyScore::new(0),
+            max: AnyScore::new(300),
+            getter: see_margin,
+            to_raw: |s: AnyScore| s.v(),
+            from_raw: |v: &i32| AnyScore::new(*v),
+        },
+        quiet_check_depth: Depth {
+            uci: "qs-quiet-check-depth",
+            unit: UciInteger,
+            default: Depth::new(1),
+            min: Depth::new(0),
+            max: Depth::new(4),
+            getter: quiet_check_depth,
+            to_raw: |d: Depth| d.v() as i32,
+            from_raw: |v: &i32| Depth::new(*v as u8),
+        },
     },
 
     policy: PolicyParams {
@@ -488,6 +516,136 @@ tunable_params! {
             to_raw: |v: i32| v,
             from_raw: |v: &i32| *v,
         },
+        rfp_margin: AnyScore {
+            uci: "id-rfp-margin",
+            unit: UciInteger,
+            default: AnyScore::new(80),
+            min: AnyScore::new(20),
+            max: AnyScore::new(250),
+            getter: rfp_margin,
+            to_raw: |s: AnyScore| s.v(),
+            from_raw: |v: &i32| AnyScore::new(*v),
+        },
+        rfp_max_depth: Depth {
+            uci: "id-rfp-max-depth",
+            unit: UciInteger,
+            default: Depth::new(7),
+            min: Depth::new(1),
+            max: Depth::new(15),
+            getter: rfp_max_depth,
+            to_raw: |d: Depth| d.v() as i32,
+            from_raw: |v: &i32| Depth::new(*v as u8),
+        },
+        razor_margin: AnyScore {
+            uci: "id-razor-margin",
+            unit: UciInteger,
+            default: AnyScore::new(250),
+            min: AnyScore::new(50),
+            max: AnyScore::new(600),
+            getter: razor_margin,
+            to_raw: |s: AnyScore| s.v(),
+            from_raw: |v: &i32| AnyScore::new(*v),
+        },
+        razor_max_depth: Depth {
+            uci: "id-razor-max-depth",
+            unit: UciInteger,
+            default: Depth::new(3),
+            min: Depth::new(1),
+            max: Depth::new(8),
+            getter: razor_max_depth,
+            to_raw: |d: Depth| d.v() as i32,
+            from_raw: |v: &i32| Depth::new(*v as u8),
+        },
+        fp_margin: AnyScore {
+            uci: "id-fp-margin",
+            unit: UciInteger,
+            default: AnyScore::new(100),
+            min: AnyScore::new(0),
+            max: AnyScore::new(400),
+            getter: fp_margin,
+            to_raw: |s: AnyScore| s.v(),
+            from_raw: |v: &i32| AnyScore::new(*v),
+        },
+        fp_depth_margin: i32 {
+            uci: "id-fp-depth-margin",
+            unit: UciInteger,
+            default: 90,
+            min: 0,
+            max: 300,
+            getter: fp_depth_margin,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        fp_max_depth: Depth {
+            uci: "id-fp-max-depth",
+            unit: UciInteger,
+            default: Depth::new(6),
+            min: Depth::new(1),
+            max: Depth::new(12),
+            getter: fp_max_depth,
+            to_raw: |d: Depth| d.v() as i32,
+            from_raw: |v: &i32| Depth::new(*v as u8),
+        },
+        lmp_base: u8 {
+            uci: "id-lmp-base",
+            unit: UciInteger,
+            default: 3,
+            min: 1,
+            max: 20,
+            getter: lmp_base,
+            to_raw: |v: u8| v as i32,
+            from_raw: |v: &i32| *v as u8,
+        },
+        lmp_max_depth: Depth {
+            uci: "id-lmp-max-depth",
+            unit: UciInteger,
+            default: Depth::new(6),
+            min: Depth::new(1),
+            max: Depth::new(12),
+            getter: lmp_max_depth,
+            to_raw: |d: Depth| d.v() as i32,
+            from_raw: |v: &i32| Depth::new(*v as u8),
+        },
+        probcut_margin: AnyScore {
+            uci: "id-probcut-margin",
+            unit: UciInteger,
+            default: AnyScore::new(200),
+            min: AnyScore::new(50),
+            max: AnyScore::new(500),
+            getter: probcut_margin,
+            to_raw: |s: AnyScore| s.v(),
+            from_raw: |v: &i32| AnyScore::new(*v),
+        },
+        probcut_min_depth: Depth {
+            uci: "id-probcut-min-depth",
+            unit: UciInteger,
+            default: Depth::new(5),
+            min: Depth::new(3),
+            max: Depth::new(15),
+            getter: probcut_min_depth,
+            to_raw: |d: Depth| d.v() as i32,
+            from_raw: |v: &i32| Depth::new(*v as u8),
+        },
+        probcut_reduction: Depth {
+            uci: "id-probcut-reduction",
+            unit: UciInteger,
+            default: Depth::new(4),
+            min: Depth::new(2),
+            max: Depth::new(8),
+            getter: probcut_reduction,
+            to_raw: |d: Depth| d.v() as i32,
+            from_raw: |v: &i32| Depth::new(*v as u8),
+        },
+        lmr_history_divisor: i32 {
+            uci: "id-lmr-history-divisor",
+            unit: UciInteger,
+            default: 8192,
+            min: 1024,
+            max: 32768,
+            getter: lmr_history_divisor,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
     },
 
     scorer: ScorerParams {
@@ -503,6 +661,259 @@ tunable_params! {
         },
     },
 
+    hce: HceParams {
+        rook_open_file_mg: i32 {
+            uci: "hce-rook-open-file-mg",
+            unit: UciInteger,
+            default: 25,
+            min: 0,
+            max: 100,
+            getter: rook_open_file_mg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        rook_open_file_eg: i32 {
+            uci: "hce-rook-open-file-eg",
+            unit: UciInteger,
+            default: 10,
+            min: 0,
+            max: 100,
+            getter: rook_open_file_eg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        rook_semi_open_file_mg: i32 {
+            uci: "hce-rook-semi-open-file-mg",
+            unit: UciInteger,
+            default: 12,
+            min: 0,
+            max: 100,
+            getter: rook_semi_open_file_mg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        rook_semi_open_file_eg: i32 {
+            uci: "hce-rook-semi-open-file-eg",
+            unit: UciInteger,
+            default: 5,
+            min: 0,
+            max: 100,
+            getter: rook_semi_open_file_eg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        rook_seventh_mg: i32 {
+            uci: "hce-rook-seventh-mg",
+            unit: UciInteger,
+            default: 20,
+            min: 0,
+            max: 100,
+            getter: rook_seventh_mg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        rook_seventh_eg: i32 {
+            uci: "hce-rook-seventh-eg",
+            unit: UciInteger,
+            default: 30,
+            min: 0,
+            max: 100,
+            getter: rook_seventh_eg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        knight_outpost_mg: i32 {
+            uci: "hce-knight-outpost-mg",
+            unit: UciInteger,
+            default: 20,
+            min: 0,
+            max: 100,
+            getter: knight_outpost_mg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        knight_outpost_eg: i32 {
+            uci: "hce-knight-outpost-eg",
+            unit: UciInteger,
+            default: 10,
+            min: 0,
+            max: 100,
+            getter: knight_outpost_eg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        bishop_outpost_mg: i32 {
+            uci: "hce-bishop-outpost-mg",
+            unit: UciInteger,
+            default: 12,
+            min: 0,
+            max: 100,
+            getter: bishop_outpost_mg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        bishop_outpost_eg: i32 {
+            uci: "hce-bishop-outpost-eg",
+            unit: UciInteger,
+            default: 6,
+            min: 0,
+            max: 100,
+            getter: bishop_outpost_eg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        king_attack_knight: i32 {
+            uci: "hce-king-attack-knight",
+            unit: UciInteger,
+            default: 2,
+            min: 0,
+            max: 10,
+            getter: king_attack_knight,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        king_attack_bishop: i32 {
+            uci: "hce-king-attack-bishop",
+            unit: UciInteger,
+            default: 2,
+            min: 0,
+            max: 10,
+            getter: king_attack_bishop,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        king_attack_rook: i32 {
+            uci: "hce-king-attack-rook",
+            unit: UciInteger,
+            default: 3,
+            min: 0,
+            max: 10,
+            getter: king_attack_rook,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        king_attack_queen: i32 {
+            uci: "hce-king-attack-queen",
+            unit: UciInteger,
+            default: 5,
+            min: 0,
+            max: 10,
+            getter: king_attack_queen,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        threat_mg: i32 {
+            uci: "hce-threat-mg",
+            unit: UciInteger,
+            default: 30,
+            min: 0,
+            max: 150,
+            getter: threat_mg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        threat_eg: i32 {
+            uci: "hce-threat-eg",
+            unit: UciInteger,
+            default: 20,
+            min: 0,
+            max: 150,
+            getter: threat_eg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        hanging_mg: i32 {
+            uci: "hce-hanging-mg",
+            unit: UciInteger,
+            default: 20,
+            min: 0,
+            max: 150,
+            getter: hanging_mg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        hanging_eg: i32 {
+            uci: "hce-hanging-eg",
+            unit: UciInteger,
+            default: 15,
+            min: 0,
+            max: 150,
+            getter: hanging_eg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        space_mg: i32 {
+            uci: "hce-space-mg",
+            unit: UciInteger,
+            default: 2,
+            min: 0,
+            max: 20,
+            getter: space_mg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        isolated_mg: i32 {
+            uci: "hce-isolated-mg",
+            unit: UciInteger,
+            default: -10,
+            min: -100,
+            max: 0,
+            getter: isolated_mg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        isolated_eg: i32 {
+            uci: "hce-isolated-eg",
+            unit: UciInteger,
+            default: -15,
+            min: -100,
+            max: 0,
+            getter: isolated_eg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        doubled_mg: i32 {
+            uci: "hce-doubled-mg",
+            unit: UciInteger,
+            default: -10,
+            min: -100,
+            max: 0,
+            getter: doubled_mg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        doubled_eg: i32 {
+            uci: "hce-doubled-eg",
+            unit: UciInteger,
+            default: -20,
+            min: -100,
+            max: 0,
+            getter: doubled_eg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        backward_mg: i32 {
+            uci: "hce-backward-mg",
+            unit: UciInteger,
+            default: -8,
+            min: -100,
+            max: 0,
+            getter: backward_mg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+        backward_eg: i32 {
+            uci: "hce-backward-eg",
+            unit: UciInteger,
+            default: -12,
+            min: -100,
+            max: 0,
+            getter: backward_eg,
+            to_raw: |v: i32| v,
+            from_raw: |v: &i32| *v,
+        },
+    },
+
     lmr: LmrParams {
         offset: f32 {
             uci: "lmr-offset",
@@ -575,10 +986,39 @@ const_params! {
         qsearch: QSearchParams {
             fn futility_margin(&self) -> AnyScore { AnyScore::new(166) }
             fn delta_pruning_threshold(&self) -> TaperValue { TaperValue::new(16) }
+            fn see_margin(&self) -> AnyScore { AnyScore::new(0) }
+            fn quiet_check_depth(&self) -> Depth { Depth::new(0) }
         },
         policy: PolicyParams {
             fn policy_temperature(&self) -> f32 { 24.58 }
         },
+        hce: HceParams {
+            fn rook_open_file_mg(&self) -> i32 { 25 }
+            fn rook_open_file_eg(&self) -> i32 { 10 }
+            fn rook_semi_open_file_mg(&self) -> i32 { 12 }
+            fn rook_semi_open_file_eg(&self) -> i32 { 5 }
+            fn rook_seventh_mg(&self) -> i32 { 20 }
+            fn rook_seventh_eg(&self) -> i32 { 30 }
+            fn knight_outpost_mg(&self) -> i32 { 20 }
+            fn knight_outpost_eg(&self) -> i32 { 10 }
+            fn bishop_outpost_mg(&self) -> i32 { 12 }
+            fn bishop_outpost_eg(&self) -> i32 { 6 }
+            fn king_attack_knight(&self) -> i32 { 2 }
+            fn king_attack_bishop(&self) -> i32 { 2 }
+            fn king_attack_rook(&self) -> i32 { 3 }
+            fn king_attack_queen(&self) -> i32 { 5 }
+            fn threat_mg(&self) -> i32 { 30 }
+            fn threat_eg(&self) -> i32 { 20 }
+            fn hanging_mg(&self) -> i32 { 20 }
+            fn hanging_eg(&self) -> i32 { 15 }
+            fn space_mg(&self) -> i32 { 2 }
+            fn isolated_mg(&self) -> i32 { -10 }
+            fn isolated_eg(&self) -> i32 { -15 }
+            fn doubled_mg(&self) -> i32 { -10 }
+            fn doubled_eg(&self) -> i32 { -20 }
+            fn backward_mg(&self) -> i32 { -8 }
+            fn backward_eg(&self) -> i32 { -12 }
+        },
         chrono: ChronoParams {
             fn base_soft_mult(&self) -> f32 { 0.50 }
             fn clamp_lower(&self) -> f32 { 0.30 }
@@ -659,6 +1099,8 @@ const_params! {
         qsearch: QSearchParams {
             fn futility_margin(&self) -> AnyScore { AnyScore::new(166) }
             fn delta_pruning_threshold(&self) -> TaperValue { TaperValue::new(16) }
+            fn see_margin(&self) -> AnyScore { AnyScore::new(0) }
+            fn quiet_check_depth(&self) -> Depth { Depth::new(1) }
         },
         id: IdParams {
             fn nmp_reduction(&self) -> Depth { Depth::new(2) }
@@ -667,10 +1109,50 @@ const_params! {
             fn nmp_phase_factor(&self) -> u32 { 7 }
             fn nmp_margin(&self) -> AnyScore { AnyScore::new(48) }
             fn nmp_depth_margin(&self) -> i32 { 15 }
+            fn rfp_margin(&self) -> AnyScore { AnyScore::new(80) }
+            fn rfp_max_depth(&self) -> Depth { Depth::new(7) }
+            fn razor_margin(&self) -> AnyScore { AnyScore::new(250) }
+            fn razor_max_depth(&self) -> Depth { Depth::new(3) }
+            fn fp_margin(&self) -> AnyScore { AnyScore::new(100) }
+            fn fp_depth_margin(&self) -> i32 { 90 }
+            fn fp_max_depth(&self) -> Depth { Depth::new(6) }
+            fn lmp_base(&self) -> u8 { 3 }
+            fn lmp_max_depth(&self) -> Depth { Depth::new(6) }
+            fn probcut_margin(&self) -> AnyScore { AnyScore::new(200) }
+            fn probcut_min_depth(&self) -> Depth { Depth::new(5) }
+            fn probcut_reduction(&self) -> Depth { Depth::new(4) }
+            fn lmr_history_divisor(&self) -> i32 { 8192 }
         },
         scorer: ScorerParams {
             fn hh_weight(&self) -> i32 { 64 }
         },
+        hce: HceParams {
+            fn rook_open_file_mg(&self) -> i32 { 25 }
+            fn rook_open_file_eg(&self) -> i32 { 10 }
+            fn rook_semi_open_file_mg(&self) -> i32 { 12 }
+            fn rook_semi_open_file_eg(&self) -> i32 { 5 }
+            fn rook_seventh_mg(&self) -> i32 { 20 }
+            fn rook_seventh_eg(&self) -> i32 { 30 }
+            fn knight_outpost_mg(&self) -> i32 { 20 }
+            fn knight_outpost_eg(&self) -> i32 { 10 }
+            fn bishop_outpost_mg(&self) -> i32 { 12 }
+            fn bishop_outpost_eg(&self) -> i32 { 6 }
+            fn king_attack_knight(&self) -> i32 { 2 }
+            fn king_attack_bishop(&self) -> i32 { 2 }
+            fn king_attack_rook(&self) -> i32 { 3 }
+            fn king_attack_queen(&self) -> i32 { 5 }
+            fn threat_mg(&self) -> i32 { 30 }
+            fn threat_eg(&self) -> i32 { 20 }
+            fn hanging_mg(&self) -> i32 { 20 }
+            fn hanging_eg(&self) -> i32 { 15 }
+            fn space_mg(&self) -> i32 { 2 }
+            fn isolated_mg(&self) -> i32 { -10 }
+            fn isolated_eg(&self) -> i32 { -15 }
+            fn doubled_mg(&self) -> i32 { -10 }
+            fn doubled_eg(&self) -> i32 { -20 }
+            fn backward_mg(&self) -> i32 { -8 }
+            fn backward_eg(&self) -> i32 { -12 }
+        },
         lmr: LmrParams {
             fn offset(&self) -> f32 { 0.99 }
             fn scale(&self) -> f32 { 3.14 }
@@ -695,6 +1177,8 @@ const_params! {
         qsearch: QSearchParams {
             fn futility_margin(&self) -> AnyScore { AnyScore::new(177) }
             fn delta_pruning_threshold(&self) -> TaperValue { TaperValue::new(2) }
+            fn see_margin(&self) -> AnyScore { AnyScore::new(0) }
+            fn quiet_check_depth(&self) -> Depth { Depth::new(1) }
         },
         id: IdParams {
             fn nmp_reduction(&self) -> Depth { Depth::new(2) }
@@ -703,6 +1187,19 @@ const_params! {
             fn nmp_phase_factor(&self) -> u32 { 7 }
             fn nmp_margin(&self) -> AnyScore { AnyScore::new(50) }
             fn nmp_depth_margin(&self) -> i32 { 12 }
+            fn rfp_margin(&self) -> AnyScore { AnyScore::new(80) }
+            fn rfp_max_depth(&self) -> Depth { Depth::new(7) }
+            fn razor_margin(&self) -> AnyScore { AnyScore::new(250) }
+            fn razor_max_depth(&self) -> Depth { Depth::new(3) }
+            fn fp_margin(&self) -> AnyScore { AnyScore::new(100) }
+            fn fp_depth_margin(&self) -> i32 { 90 }
+            fn fp_max_depth(&self) -> Depth { Depth::new(6) }
+            fn lmp_base(&self) -> u8 { 3 }
+            fn lmp_max_depth(&self) -> Depth { Depth::new(6) }
+            fn probcut_margin(&self) -> AnyScore { AnyScore::new(200) }
+            fn probcut_min_depth(&self) -> Depth { Depth::new(5) }
+            fn probcut_reduction(&self) -> Depth { Depth::new(4) }
+            fn lmr_history_divisor(&self) -> i32 { 8192 }
         },
         scorer: ScorerParams {
             fn hh_weight(&self) -> i32 { 100 }
@@ -24,7 +24,7 @@ use thiserror::Error;
 use crate::{
     core::{
         bitboard::Bitboard,
-        castling::{CastlingRights, CastlingSideTokenizationError},
+        castling::{CastlingRights, CastlingSideTokenizationError, castling_sides},
         color::{Color, ColorTokenizationError, Perspective, colors, perspectives},
         coordinates::{
             EpTargetSquareTokenizationError, File, Rank, RankParseError, Square, castling::castling_rank, files, pawn_utils, ranks, squares,
@@ -52,6 +52,7 @@ use super::{
     coordinates::{EpCaptureSquare, EpTargetSquare},
     ply::{FullMoveCount, Ply},
 };
+use pgn::{PgnReadError, PgnReader};
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub enum CheckState {
@@ -63,6 +64,8 @@ pub enum CheckState {
 
 #[cfg(test)] mod test;
 
+pub mod pgn;
+
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
 pub struct StateInfo {
     // Memoized state
@@ -78,9 +81,33 @@ pub struct StateInfo {
     pub castling: CastlingRights,
     pub captured_piece: Piece,
     pub key: zobrist::Hash,
+    /// Key of the pawns only.
+    pub pawn_key: zobrist::Hash,
+    /// Keys of all non-pawn pieces (including the king) of each color.
+    pub non_pawn_keys: [zobrist::Hash; colors::N_VARIANTS],
+    /// Key of the number of pieces of each kind.
+    pub material_key: zobrist::Hash,
 }
 
 impl StateInfo {
+    /// Toggles `piece` on `sq` in the position key and the auxiliary keys.
+    #[inline]
+    fn toggle_piece_sq(&mut self, sq: Square, piece: Piece) {
+        self.key.toggle_piece_sq(sq, piece);
+        if piece.piece_type() == piece_type::PAWN {
+            self.pawn_key.toggle_piece_sq(sq, piece);
+        }
+        else {
+            self.non_pawn_keys[piece.color().v() as usize].toggle_piece_sq(sq, piece);
+        }
+    }
+
+    #[inline]
+    fn move_piece_sq(&mut self, from: Square, to: Square, piece: Piece) {
+        self.toggle_piece_sq(from, piece);
+        self.toggle_piece_sq(to, piece);
+    }
+
     // todo:
     // these are really only needed when generating more moves from the
     // position that we are currently in. maybe make this lazy or something? (only
@@ -545,10 +572,103 @@ impl Position {
             state: StateStack::new(state),
         };
         let key = zobrist::Hash::from(&position);
-        position.state.get_current_mut().key = key;
+        let pawn_key = zobrist::Hash::pawn_key(&position.piece_info);
+        let non_pawn_keys = [colors::WHITE, colors::BLACK].map(|c| zobrist::Hash::non_pawn_key(&position.piece_info, c));
+        let material_key = zobrist::Hash::material_key(&position.piece_info);
+
+        let state = position.state.get_current_mut();
+        state.key = key;
+        state.pawn_key = pawn_key;
+        state.non_pawn_keys = non_pawn_keys;
+        state.material_key = material_key;
         position
     }
 
+    /// Checks that the position could have arisen in a legal game, as far as
+    /// the engine relies on it.
+    ///
+    /// In [`Validation::Lenient`] mode, the side not to move beeing in check is
+    /// accepted, and stale castling rights or en passant squares are dropped
+    /// instead of rejected. Both kings are required in either mode.
+    pub fn validate(&mut self, mode: Validation) -> Result<(), PositionValidationError> {
+        type E = PositionValidationError;
+
+        let strict = mode == Validation::Strict;
+        let stm = self.get_turn();
+        let nstm = !stm;
+
+        for color in [colors::WHITE, colors::BLACK] {
+            match self.get_bitboard(piece_type::KING, color).pop_cnt() {
+                0 => return Err(E::MissingKing(color)),
+                2.. => return Err(E::MultipleKings(color)),
+                _ => {}
+            }
+        }
+
+        let back_ranks = Bitboard::from(ranks::_1) | Bitboard::from(ranks::_8);
+        if let Some(sq) = (self.get_piece_bb(piece_type::PAWN) & back_ranks).lsb() {
+            return Err(E::PawnOnBackRank(sq));
+        }
+
+        if strict
+            && let Some(king_sq) = self.get_bitboard(piece_type::KING, nstm).lsb()
+            && self.piece_info.attackers_to_exist(king_sq, stm, self.get_occupancy())
+        {
+            return Err(E::OpponentInCheck);
+        }
+
+        // Castling rights require the king and the respective rook on their home
+        // squares.
+        let mut castling = self.get_castling();
+        for color in [colors::WHITE, colors::BLACK] {
+            let rank = castling_rank(color);
+            let king = Piece::from((color, piece_type::KING));
+            let rook = Piece::from((color, piece_type::ROOK));
+            for (side, rook_file) in [(castling_sides::KING_SIDE, files::H), (castling_sides::QUEEN_SIDE, files::A)] {
+                if castling.is_true(side, color)
+                    && (self.get_piece(Square::from((files::E, rank))) != king || self.get_piece(Square::from((rook_file, rank))) != rook)
+                {
+                    castling.set_false(side, color);
+                }
+            }
+        }
+        if castling != self.get_castling() && strict {
+            return Err(E::InvalidCastlingRights(self.get_castling()));
+        }
+
+        // The en passant target square has to be right behind a pawn which could have
+        // just made a double push.
+        let mut ep_capture_square = self.get_ep_capture_square();
+        if let Some(target) = self.get_ep_target_square().v() {
+            let (target_rank, capture_rank, origin_rank) = match stm {
+                colors::WHITE => (ranks::_6, ranks::_5, ranks::_7),
+                _ => (ranks::_3, ranks::_4, ranks::_2),
+            };
+            let file = File::from(target);
+            let valid = Rank::from(target) == target_rank
+                && self.get_piece(Square::from((file, capture_rank))) == Piece::from((nstm, piece_type::PAWN))
+                && self.get_piece(target) == Piece::default()
+                && self.get_piece(Square::from((file, origin_rank))) == Piece::default();
+
+            if !valid && strict {
+                return Err(E::InvalidEpSquare(target));
+            }
+            if !valid {
+                ep_capture_square = EpCaptureSquare::none();
+            }
+        }
+
+        if castling != self.get_castling() || ep_capture_square != self.get_ep_capture_square() {
+            let state = self.state.get_current_mut();
+            state.castling = castling;
+            state.ep_capture_square = ep_capture_square;
+            let key = zobrist::Hash::from(&*self);
+            self.state.get_current_mut().key = key;
+        }
+
+        Ok(())
+    }
+
     pub fn collect_legals<C: Extend<Move>>(&self, list: C) -> C {
         match self.get_turn() {
             colors::WHITE => self.collect_legals_for::<perspectives::White, _>(list),
@@ -610,6 +730,15 @@ impl Position {
     #[inline]
     pub fn get_key(&self) -> zobrist::Hash { self.state.get_current().key }
 
+    #[inline]
+    pub fn get_pawn_key(&self) -> zobrist::Hash { self.state.get_current().pawn_key }
+
+    #[inline]
+    pub fn get_material_key(&self) -> zobrist::Hash { self.state.get_current().material_key }
+
+    #[inline]
+    pub fn get_non_pawn_key(&self, color: Color) -> zobrist::Hash { self.state.get_current().non_pawn_keys[color.v() as usize] }
+
     #[inline]
     pub fn get_check_state(&self) -> CheckState { self.state.get_current().check_state }
 
@@ -712,11 +841,91 @@ impl Position {
         false
     }
 
+    /// Whether the side to move has a reversible move that leads back into a
+    /// position of the current line, i.e. can force a repetition.
+    ///
+    /// `rel_ply` is the distance to the search root. Positions before the root
+    /// only count if they have already been repeated once.
+    ///
+    /// [Upcoming repetition detection](https://www.chessprogramming.org/Repetitions#Cuckoo)
+    pub fn has_upcoming_repetition(&self, rel_ply: Depth) -> bool {
+        let plys50 = self.plys_50().v as usize;
+        if plys50 < 3 {
+            return false;
+        }
+
+        let current_key = self.get_key();
+        let occupancy = self.get_occupancy();
+        // a single move away means an odd distance, and three is the shortest cycle
+        let mut i = 3;
+        while let Some(state) = self.state.get_prev(i)
+            && i <= plys50
+        {
+            if let Some(mov) = zobrist::cuckoo::lookup(current_key ^ state.key.v())
+                && (Bitboard::between(mov.get_from(), mov.get_to()) & occupancy).is_empty()
+            {
+                if rel_ply.index() > i {
+                    return true;
+                }
+
+                // Both directions of a move share an entry, so make sure it is
+                // actually us who can play it.
+                let moving_sq = if occupancy.contains(Bitboard::from(mov.get_from())) {
+                    mov.get_from()
+                }
+                else {
+                    mov.get_to()
+                };
+                if self.get_piece(moving_sq).color() == self.get_turn()
+                    && (i + 4..=plys50)
+                        .step_by(2)
+                        .any(|j| self.state.get_prev(j).is_some_and(|prev| prev.key == state.key))
+                {
+                    return true;
+                }
+            }
+            i += 2;
+        }
+        false
+    }
+
     #[inline]
     pub fn does_check(&self, mov: Move) -> CheckState { self.piece_info.does_check(self.get_turn(), mov) }
 
+    /// FIDE 9.6.2: The game is drawn when a position has arisen in which
+    /// neither player can checkmate the opponent's king with any series of
+    /// legal moves.
+    ///
+    /// By material alone, this is the case for bare kings, a single minor piece
+    /// against a bare king, and any number of bishops which all live on
+    /// squares of the same color.
     #[inline]
-    pub fn is_insufficient_material(&self) -> bool { self.piece_info.piece_counts.iter().sum::<i8>() <= 2 }
+    pub fn is_insufficient_material(&self) -> bool {
+        let majors_and_pawns = self.get_piece_bb(piece_type::PAWN) | self.get_piece_bb(piece_type::ROOK) | self.get_piece_bb(piece_type::QUEEN);
+        if !majors_and_pawns.is_empty() {
+            return false;
+        }
+
+        let knights = self.get_piece_bb(piece_type::KNIGHT);
+        let bishops = self.get_piece_bb(piece_type::BISHOP);
+        if (knights | bishops).pop_cnt() <= 1 {
+            return true;
+        }
+
+        knights.is_empty() && ((bishops & Bitboard::light_squares()).is_empty() || (bishops & Bitboard::dark_squares()).is_empty())
+    }
+
+    /// Positions in which a mate is still possible, but can not be forced.
+    ///
+    /// Currently this is only two knights against a bare king. This is not a
+    /// draw by the rules, but search may treat it as one.
+    pub fn is_likely_draw(&self) -> bool {
+        let kings = self.get_piece_bb(piece_type::KING);
+        let knights = self.get_piece_bb(piece_type::KNIGHT);
+        self.get_occupancy() == (kings | knights)
+            && knights.pop_cnt() == 2
+            && (knights == self.get_bitboard(piece_type::KNIGHT, colors::WHITE) || knights == self.get_bitboard(piece_type::KNIGHT, colors::BLACK))
+    }
 
     #[inline]
     pub fn plys_50(&self) -> Ply { self.state.get_current().plys50 }
@@ -780,10 +989,14 @@ impl Position {
         // regarding 2-fold usage:
         //   Apply the 2-fold heuristic deep in the tree.
         //   At the root, fall back to the strict 3-fold rule to catch actual draws.
+        //
+        // regarding likely draws:
+        //   Score them as draws, although the game could go on by the rules.
         else if (search_depth > Depth::ROOT && self.has_twofold_repetition())
             || (search_depth == Depth::ROOT && self.has_threefold_repetition())
             || self.fifty_move_rule()
             || self.is_insufficient_material()
+            || self.is_likely_draw()
         {
             Some(GameResult::Draw)
         }
@@ -1079,6 +1292,9 @@ impl Position {
             s.key = curr_state.key;
             s.key.toggle_ep_square(curr_state.ep_capture_square);
             s.key.toggle_turn();
+            s.pawn_key = curr_state.pawn_key;
+            s.non_pawn_keys = curr_state.non_pawn_keys;
+            s.material_key = curr_state.material_key;
             s.captured_piece = Piece::default();
             s
         };
@@ -1133,6 +1349,9 @@ impl Position {
             s.key = curr_state.key;
             s.key.toggle_ep_square(curr_state.ep_capture_square);
             s.key.toggle_turn();
+            s.pawn_key = curr_state.pawn_key;
+            s.non_pawn_keys = curr_state.non_pawn_keys;
+            s.material_key = curr_state.material_key;
             s.captured_piece = Piece::default();
 
             // castling
@@ -1156,7 +1375,10 @@ impl Position {
             self.piece_info.remove_piece(captured_sq, obsv);
 
             next_state.captured_piece = captured_piece;
-            next_state.key.toggle_piece_sq(captured_sq, captured_piece);
+            next_state.toggle_piece_sq(captured_sq, captured_piece);
+            next_state
+                .material_key
+                .toggle_piece_count(captured_piece, self.piece_info.get_piece_count(captured_piece));
             next_state.plys50 = Ply { v: 0 };
         }
 
@@ -1165,14 +1387,14 @@ impl Position {
             piece_type::KING => {
                 // move king
                 self.piece_info.move_piece(from, to, obsv);
-                next_state.key.move_piece_sq(from, to, moving_piece);
+                next_state.move_piece_sq(from, to, moving_piece);
 
                 // move rook if castle
                 let rank = castling_rank(P::COLOR);
                 let rook = Piece::from((P::COLOR, piece_type::ROOK));
                 if let Some((r_from, r_to)) = Move::rook_castling(flag, rank) {
                     self.piece_info.move_piece(r_from, r_to, obsv);
-                    next_state.key.move_piece_sq(r_from, r_to, rook);
+                    next_state.move_piece_sq(r_from, r_to, rook);
                 }
             }
             // pawns
@@ -1181,7 +1403,7 @@ impl Position {
                     move_flags::DOUBLE_PAWN_PUSH_C => {
                         // move the pawn
                         self.piece_info.move_piece(from, to, obsv);
-                        next_state.key.move_piece_sq(from, to, moving_piece);
+                        next_state.move_piece_sq(from, to, moving_piece);
 
                         // Safety: A double pawn push destination square is the definition of
                         // an en passant square.
@@ -1193,14 +1415,20 @@ impl Position {
                         let promo_t = unsafe { PromoPieceType::try_from(flag).unwrap_unchecked() };
                         let promo = Piece::from((P::COLOR, promo_t));
                         self.piece_info.remove_piece(from, obsv);
-                        next_state.key.toggle_piece_sq(from, moving_piece);
+                        next_state.toggle_piece_sq(from, moving_piece);
+                        next_state
+                            .material_key
+                            .toggle_piece_count(moving_piece, self.piece_info.get_piece_count(moving_piece));
                         self.piece_info.put_piece(to, promo, obsv);
-                        next_state.key.toggle_piece_sq(to, promo);
+                        next_state.toggle_piece_sq(to, promo);
+                        next_state
+                            .material_key
+                            .toggle_piece_count(promo, self.piece_info.get_piece_count(promo) - 1);
                     }
                     _ => {
                         // move the pawn
                         self.piece_info.move_piece(from, to, obsv);
-                        next_state.key.move_piece_sq(from, to, moving_piece);
+                        next_state.move_piece_sq(from, to, moving_piece);
                     }
                 }
 
@@ -1209,7 +1437,7 @@ impl Position {
             _ => {
                 // move the piece
                 self.piece_info.move_piece(from, to, obsv);
-                next_state.key.move_piece_sq(from, to, moving_piece);
+                next_state.move_piece_sq(from, to, moving_piece);
             }
         }
 
@@ -1221,6 +1449,26 @@ impl Position {
         // update state stack
         next_state.init(&self.piece_info);
         self.state.incr();
+
+        #[cfg(debug_assertions)]
+        self.assert_keys_consistent();
+    }
+
+    /// Recomputes the auxiliary keys from scratch and checks that the
+    /// incrementally updated ones match.
+    #[cfg(debug_assertions)]
+    fn assert_keys_consistent(&self) {
+        let state = self.state.get_current();
+        let pieces = &self.piece_info;
+        assert_eq!(state.pawn_key, zobrist::Hash::pawn_key(pieces), "pawn key out of sync");
+        for c in [colors::WHITE, colors::BLACK] {
+            assert_eq!(
+                state.non_pawn_keys[c.v() as usize],
+                zobrist::Hash::non_pawn_key(pieces, c),
+                "non-pawn key of {c:?} out of sync"
+            );
+        }
+        assert_eq!(state.material_key, zobrist::Hash::material_key(pieces), "material key out of sync");
     }
 
     pub fn unmake_move(&mut self, m: Move, obsv: &mut impl PieceInfoObserver) {
@@ -1633,17 +1881,23 @@ pub enum PgnImportError {
     #[error("Error parsing PGN: {0}")]
     PgnParseError(#[from] PgnParseError),
 
+    #[error("Error reading PGN: {0}")]
+    PgnReadError(#[from] PgnReadError),
+
     #[error("Invalid SAN move: {0}")]
     InvalidSanMove(#[from] SanParseError),
+
+    #[error("The PGN does not contain a game.")]
+    MissingGame,
 }
 
 impl<'a, 'b> TryFrom<PgnImport<'a, 'b>> for Position {
     type Error = PgnImportError;
 
     fn try_from(pgn: PgnImport<'a, 'b>) -> Result<Self, Self::Error> {
-        let pgn = ReducedPgn::try_from(pgn.0)?;
+        let pgn = PgnReader::new(pgn.0).read_game()?.ok_or(PgnImportError::MissingGame)?;
 
-        let mut position = pgn.start_position()?;
+        let mut position = pgn.start_position(Validation::Lenient)?;
 
         for san in pgn.moves() {
             position.make_move(Move::from_san(san, &position)?, &mut ());
@@ -1657,13 +1911,19 @@ impl<'a, 'b> TryFrom<PgnImport<'a, 'b>> for Position {
 // bracket and the tag name, there are no white space characters between the tag
 // value and the right bracket, and there is a single space character between
 // the tag name and the tag value.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PgnTagPair<A, B>(pub A, pub B);
 
 impl<A: fmt::Display, B: fmt::Display> fmt::Display for PgnTagPair<A, B> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // todo: we could check the length of fmt(self.a) or fmt(self.b), and introduce
         // a linebreak if that would overflow the 255 char limit.
-        write!(f, "[{} \"{}\"]", self.0, self.1)
+
+        // 7: A quote inside a string is represented by the backslash immediately
+        // followed by a quote. A backslash inside a string is represented by two
+        // adjacent backslashes.
+        let value = self.1.to_string().replace('\\', "\\\\").replace('"', "\\\"");
+        write!(f, "[{} \"{}\"]", self.0, value)
     }
 }
 
@@ -1682,7 +1942,7 @@ impl<A: fmt::Display, B: fmt::Display> fmt::Display for PgnTagPair<A, B> {
 // [Result "1-0"]
 // [Result "1/2-1/2"]
 // [Result "*"]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct PgnResultValue(pub Option<GameResult>);
 
 impl fmt::Display for PgnResultValue {
@@ -1977,13 +2237,53 @@ pub enum FenParseError {
 
     #[error("Failed to parse full move clock part: {0}")]
     FullMoveCountPart(FullMoveCountTokenizationError),
+
+    #[error("Illegal position: {0}")]
+    IllegalPosition(#[from] PositionValidationError),
+}
+
+/// How strictly [`Position::validate`] treats questionable positions.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Validation {
+    /// Reject everything that can not occur in a legal game.
+    #[default]
+    Strict,
+
+    /// Only reject what the engine can not handle, and repair the rest.
+    Lenient,
+}
+
+#[derive(Debug, Error)]
+pub enum PositionValidationError {
+    #[error("{} king is missing.", if *.0 == colors::WHITE { "White" } else { "Black" })]
+    MissingKing(Color),
+
+    #[error("{} has more than one king.", if *.0 == colors::WHITE { "White" } else { "Black" })]
+    MultipleKings(Color),
+
+    #[error("Pawn on back rank: {0}")]
+    PawnOnBackRank(Square),
+
+    #[error("The side not to move is in check.")]
+    OpponentInCheck,
+
+    #[error("Castling rights without king and rook on their home squares: {0}")]
+    InvalidCastlingRights(CastlingRights),
+
+    #[error("Impossible en passant target square: {0}")]
+    InvalidEpSquare(Square),
 }
 
 impl<'a, 'b> TryFrom<FenImport<'a, 'b>> for Position {
     type Error = FenParseError;
 
-    fn try_from(fen: FenImport<'a, 'b>) -> Result<Self, Self::Error> {
-        let fen = fen.0;
+    fn try_from(fen: FenImport<'a, 'b>) -> Result<Self, Self::Error> { fen.parse(Validation::Lenient) }
+}
+
+impl FenImport<'_, '_> {
+    /// Parses the position and validates it once in the given `mode`.
+    pub fn parse(self, mode: Validation) -> Result<Position, FenParseError> {
+        let fen = self.0;
 
         let pieces = fen::piece_placement(fen)?;
         let turn = fen::side_to_move(fen.skip_ws())?;
@@ -2001,7 +2301,9 @@ impl<'a, 'b> TryFrom<FenImport<'a, 'b>> for Position {
             ..Default::default()
         };
 
-        Ok(Position::init(pieces, state))
+        let mut pos = Position::init(pieces, state);
+        pos.validate(mode)?;
+        Ok(pos)
     }
 }
 
@@ -2019,8 +2321,14 @@ pub enum EpdLineParseError {
 impl<'a, 'b> TryFrom<EpdLineImport<'a, 'b>> for (Position, Vec<EpdOp>) {
     type Error = EpdLineParseError;
 
-    fn try_from(epd: EpdLineImport<'a, 'b>) -> Result<Self, Self::Error> {
-        let epd = epd.0;
+    fn try_from(epd: EpdLineImport<'a, 'b>) -> Result<Self, Self::Error> { epd.parse(Validation::Lenient) }
+}
+
+impl EpdLineImport<'_, '_> {
+    /// Parses the position with its operations and validates the position once
+    /// in the given `mode`.
+    pub fn parse(self, mode: Validation) -> Result<(Position, Vec<EpdOp>), EpdLineParseError> {
+        let epd = self.0;
 
         // ref: https://www.chessprogramming.org/Extended_Position_Description#EPD_Syntax
 
@@ -2082,7 +2390,8 @@ impl<'a, 'b> TryFrom<EpdLineImport<'a, 'b>> for (Position, Vec<EpdOp>) {
             ..Default::default()
         };
 
-        let pos = Position::init(pieces, state);
+        let mut pos = Position::init(pieces, state);
+        pos.validate(mode).map_err(FenParseError::from)?;
 
         Ok((pos, ops))
     }
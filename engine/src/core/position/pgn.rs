@@ -0,0 +1,657 @@
+use core::fmt;
+use std::io::{self, BufRead};
+
+use thiserror::Error;
+
+use super::{FenImport, FenParseError, PgnResultValue, PgnTagPair, Position, Validation};
+use crate::{
+    core::color::{Color, colors},
+    uci::tokens::Tokenizer,
+};
+
+// pgn spec: https://www.thechessdrum.net/PGN_Reference.txt
+
+/// A single game in PGN import format, including everything that
+/// [`super::ReducedPgn`] can not represent: arbitrary tags, commentary,
+/// recursive annotation variations and numeric annotation glyphs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnGame {
+    /// The tag pairs in the order they appeared in.
+    pub tags: Vec<PgnTagPair<String, String>>,
+
+    /// The main variation of the game.
+    pub mainline: PgnVariation,
+
+    /// The game termination marker.
+    pub result: PgnResultValue,
+}
+
+/// A sequence of moves, either the mainline or a RAV.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnVariation {
+    /// Commentary which appears before the first move of the variation.
+    pub comments: Vec<String>,
+
+    pub nodes: Vec<PgnNode>,
+}
+
+/// A move together with all its annotations.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnNode {
+    /// The move in standard algebraic notation (SAN).
+    pub san: String,
+
+    /// Numeric annotation glyphs, suffix annotations ("!", "?!", ...) are
+    /// stored as their NAG equivalents.
+    pub nags: Vec<u8>,
+
+    /// Commentary which follows the move.
+    pub comments: Vec<String>,
+
+    /// Alternatives to this move, each starting from the position before the
+    /// move was played.
+    pub variations: Vec<PgnVariation>,
+}
+
+impl PgnGame {
+    /// Returns the value of the first tag pair with the given name.
+    pub fn tag(&self, key: &str) -> Option<&str> { self.tags.iter().find(|PgnTagPair(k, _)| k == key).map(|PgnTagPair(_, v)| v.as_str()) }
+
+    /// 9.7: Alternative starting positions
+    ///
+    /// See [`super::ReducedPgn::start_position`]. A set-up position is
+    /// validated in the given `mode`.
+    pub fn start_position(&self, mode: Validation) -> Result<Position, FenParseError> {
+        match (self.tag("SetUp"), self.tag("FEN")) {
+            (Some("1"), Some(fen)) => FenImport(&mut Tokenizer::new(fen)).parse(mode),
+            _ => Ok(Position::start_position()),
+        }
+    }
+
+    /// Returns the mainline moves in SAN format.
+    pub fn moves(&self) -> impl Iterator<Item = &str> { self.mainline.nodes.iter().map(|node| node.san.as_str()) }
+
+    /// The move number and side to move of the first move, read from the FEN
+    /// tag if the game starts from a set-up position.
+    fn first_move_number(&self) -> (u16, Color) {
+        let mut fields = match (self.tag("SetUp"), self.tag("FEN")) {
+            (Some("1"), Some(fen)) => fen.split_whitespace(),
+            _ => return (1, colors::WHITE),
+        };
+        let stm = match fields.nth(1) {
+            Some("b") => colors::BLACK,
+            _ => colors::WHITE,
+        };
+        let fmc = fields.nth(3).and_then(|fmc| fmc.parse().ok()).unwrap_or(1);
+        (fmc, stm)
+    }
+}
+
+/// 8.2.3.8: SAN move suffix annotations
+///
+/// Import format PGN allows for the use of traditional suffix annotations for
+/// moves. There are exactly six such annotations available: "!", "?", "!!",
+/// "!?", "?!", and "??". At most one such suffix annotation may appear per
+/// move, and if present, it is always the last part of the move symbol.
+///
+/// When exported, a move suffix annotation is translated into the
+/// corresponding Numeric Annotation Glyph as described in a later section of
+/// this document.
+fn suffix_annotation_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+/// A source of characters for the [`PgnReader`].
+pub trait PgnSource {
+    fn peek_char(&mut self) -> io::Result<Option<char>>;
+
+    fn next_char(&mut self) -> io::Result<Option<char>>;
+}
+
+impl PgnSource for Tokenizer<'_> {
+    fn peek_char(&mut self) -> io::Result<Option<char>> { Ok(self.peek_next_char()) }
+
+    fn next_char(&mut self) -> io::Result<Option<char>> { Ok(self.consume_char()) }
+}
+
+impl<S: PgnSource + ?Sized> PgnSource for &mut S {
+    fn peek_char(&mut self) -> io::Result<Option<char>> { (**self).peek_char() }
+
+    fn next_char(&mut self) -> io::Result<Option<char>> { (**self).next_char() }
+}
+
+/// Reads a [`BufRead`] line by line, such that arbitrarily large PGN databases
+/// never have to be held in memory at once.
+pub struct PgnLines<R> {
+    src: R,
+    line: String,
+    cursor: usize,
+}
+
+impl<R: BufRead> PgnLines<R> {
+    pub fn new(src: R) -> Self {
+        Self {
+            src,
+            line: String::new(),
+            cursor: 0,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        if self.cursor >= self.line.len() {
+            self.line.clear();
+            self.cursor = 0;
+            self.src.read_line(&mut self.line)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> PgnSource for PgnLines<R> {
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        self.fill()?;
+        Ok(self.line[self.cursor..].chars().next())
+    }
+
+    fn next_char(&mut self) -> io::Result<Option<char>> {
+        let c = self.peek_char()?;
+        self.cursor += c.map_or(0, char::len_utf8);
+        Ok(c)
+    }
+}
+
+/// 7: Tokens
+#[derive(Clone, Debug, PartialEq)]
+enum PgnToken {
+    String(String),
+    Symbol(String),
+    Nag(u8),
+    Comment(String),
+    Period,
+    Asterisk,
+    TagOpen,
+    TagClose,
+    VariationOpen,
+    VariationClose,
+}
+
+#[derive(Debug, Error)]
+pub enum PgnReadError {
+    #[error("I/O error while reading PGN: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Line {0}: Unexpected character '{1}'")]
+    UnexpectedChar(usize, char),
+
+    #[error("Line {0}: Unterminated string")]
+    UnterminatedString(usize),
+
+    #[error("Line {0}: Unterminated comment")]
+    UnterminatedComment(usize),
+
+    #[error("Line {0}: Invalid numeric annotation glyph")]
+    InvalidNag(usize),
+
+    #[error("Line {0}: Invalid suffix annotation '{1}'")]
+    InvalidSuffixAnnotation(usize, String),
+
+    #[error("Line {0}: Malformed tag pair")]
+    MalformedTag(usize),
+
+    #[error("Line {0}: Annotation without a preceding move")]
+    DanglingAnnotation(usize),
+
+    #[error("Line {0}: Unbalanced variation parentheses")]
+    UnbalancedVariation(usize),
+}
+
+/// A streaming reader for PGN databases containing any number of games.
+///
+/// After an error, the reader skips ahead to the next line starting with a tag
+/// pair, such that a single broken game does not spoil the whole database.
+pub struct PgnReader<S> {
+    src: S,
+    peeked: Option<PgnToken>,
+    line: usize,
+    line_start: bool,
+    recover: bool,
+}
+
+impl<R: BufRead> PgnReader<PgnLines<R>> {
+    pub fn from_reader(src: R) -> Self { Self::new(PgnLines::new(src)) }
+}
+
+impl<S: PgnSource> PgnReader<S> {
+    pub fn new(src: S) -> Self {
+        Self {
+            src,
+            peeked: None,
+            line: 1,
+            line_start: true,
+            recover: false,
+        }
+    }
+
+    fn peek_char(&mut self) -> io::Result<Option<char>> { self.src.peek_char() }
+
+    fn next_char(&mut self) -> io::Result<Option<char>> {
+        let c = self.src.next_char()?;
+        self.line_start = c == Some('\n');
+        if self.line_start {
+            self.line += 1;
+        }
+        Ok(c)
+    }
+
+    fn skip_line(&mut self) -> io::Result<()> {
+        while let Some(c) = self.next_char()?
+            && c != '\n'
+        {}
+        Ok(())
+    }
+
+    /// Discards the input until the next line that starts a tag pair section.
+    fn skip_to_next_game(&mut self) -> io::Result<()> {
+        self.peeked = None;
+        loop {
+            match self.peek_char()? {
+                Some('[') if self.line_start => return Ok(()),
+                Some(_) => _ = self.next_char()?,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    fn peek_token(&mut self) -> Result<Option<&PgnToken>, PgnReadError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lex_token()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn next_token(&mut self) -> Result<Option<PgnToken>, PgnReadError> {
+        match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.lex_token(),
+        }
+    }
+
+    fn lex_token(&mut self) -> Result<Option<PgnToken>, PgnReadError> {
+        loop {
+            let Some(c) = self.peek_char()?
+            else {
+                return Ok(None);
+            };
+
+            // 6: Escape mechanism
+            //
+            // There is a special escape mechanism for PGN data. This mechanism is
+            // triggered by a percent sign character ("%") appearing in the first column
+            // of a line; the data on the rest of the line is ignored by publicly
+            // available PGN scanning software.
+            if c == '%' && self.line_start {
+                self.skip_line()?;
+                continue;
+            }
+
+            if c.is_whitespace() {
+                self.next_char()?;
+                continue;
+            }
+
+            let line = self.line;
+            self.next_char()?;
+
+            let token = match c {
+                '[' => PgnToken::TagOpen,
+                ']' => PgnToken::TagClose,
+                '(' => PgnToken::VariationOpen,
+                ')' => PgnToken::VariationClose,
+                '.' => PgnToken::Period,
+                '*' => PgnToken::Asterisk,
+
+                // 7: A string token is a sequence of zero or more printing characters
+                // delimited by a pair of quote characters. [...] A quote inside a
+                // string is represented by the backslash immediately followed by a
+                // quote. A backslash inside a string is represented by two adjacent
+                // backslashes.
+                '"' => {
+                    let mut value = String::new();
+                    loop {
+                        match self.next_char()? {
+                            Some('"') => break,
+                            Some('\\') => match self.next_char()? {
+                                Some(escaped) => value.push(escaped),
+                                None => return Err(PgnReadError::UnterminatedString(line)),
+                            },
+                            Some(c) => value.push(c),
+                            None => return Err(PgnReadError::UnterminatedString(line)),
+                        }
+                    }
+                    PgnToken::String(value)
+                }
+
+                // 5: Commentary
+                //
+                // Comment text may appear in PGN data. There are two kinds of comments.
+                // The first kind is the "rest of line" comment; this comment type
+                // starts with a semicolon character and continues to the end of the
+                // line. The second kind starts with a left brace character and
+                // continues to the next right brace character.
+                '{' => {
+                    let mut text = String::new();
+                    loop {
+                        match self.next_char()? {
+                            Some('}') => break,
+                            Some(c) => text.push(c),
+                            None => return Err(PgnReadError::UnterminatedComment(line)),
+                        }
+                    }
+                    PgnToken::Comment(text.trim().to_string())
+                }
+                ';' => {
+                    let mut text = String::new();
+                    while let Some(c) = self.next_char()?
+                        && c != '\n'
+                    {
+                        text.push(c);
+                    }
+                    PgnToken::Comment(text.trim().to_string())
+                }
+
+                // 7: A NAG (Numeric Annotation Glyph) is an integer token that is
+                // immediately preceded by a dollar sign ("$") character.
+                '$' => {
+                    let mut digits = String::new();
+                    while let Some(c) = self.peek_char()?
+                        && c.is_ascii_digit()
+                    {
+                        digits.push(c);
+                        self.next_char()?;
+                    }
+                    PgnToken::Nag(digits.parse().map_err(|_| PgnReadError::InvalidNag(line))?)
+                }
+
+                '!' | '?' => {
+                    let mut suffix = String::from(c);
+                    while let Some(c) = self.peek_char()?
+                        && matches!(c, '!' | '?')
+                    {
+                        suffix.push(c);
+                        self.next_char()?;
+                    }
+                    let nag = suffix_annotation_nag(&suffix).ok_or(PgnReadError::InvalidSuffixAnnotation(line, suffix))?;
+                    PgnToken::Nag(nag)
+                }
+
+                // 7: A symbol token starts with a letter or digit character and is
+                // immediately followed by a sequence of zero or more symbol
+                // continuation characters. These continuation characters are letter
+                // characters ("A-Za-z"), digit characters ("0-9"), the underscore
+                // ("_"), the plus sign ("+"), the octothorpe sign ("#"), the equal sign
+                // ("="), the colon (":"), and the hyphen ("-").
+                //
+                // The slash is accepted aswell, as it is part of the "1/2-1/2" game
+                // termination marker.
+                c if c.is_ascii_alphanumeric() => {
+                    let mut symbol = String::from(c);
+                    while let Some(c) = self.peek_char()?
+                        && (c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '#' | '=' | ':' | '-' | '/'))
+                    {
+                        symbol.push(c);
+                        self.next_char()?;
+                    }
+                    PgnToken::Symbol(symbol)
+                }
+
+                c => return Err(PgnReadError::UnexpectedChar(line, c)),
+            };
+
+            return Ok(Some(token));
+        }
+    }
+
+    /// Reads the next game, or `None` if the input is exhausted.
+    pub fn read_game(&mut self) -> Result<Option<PgnGame>, PgnReadError> {
+        if self.recover {
+            self.recover = false;
+            self.skip_to_next_game()?;
+        }
+
+        let game = self.parse_game();
+        self.recover = game.is_err();
+        game
+    }
+
+    fn parse_game(&mut self) -> Result<Option<PgnGame>, PgnReadError> {
+        let mut game = PgnGame::default();
+
+        // 8.1: Tag pair section
+        while self.peek_token()? == Some(&PgnToken::TagOpen) {
+            self.next_token()?;
+            let line = self.line;
+            match (self.next_token()?, self.next_token()?, self.next_token()?) {
+                (Some(PgnToken::Symbol(key)), Some(PgnToken::String(value)), Some(PgnToken::TagClose)) => {
+                    game.tags.push(PgnTagPair(key, value));
+                }
+                _ => return Err(PgnReadError::MalformedTag(line)),
+            }
+        }
+
+        // 8.2: Movetext section
+        let result = self.parse_variation(&mut game.mainline, 0)?;
+
+        if game.tags.is_empty() && game.mainline == PgnVariation::default() && result.is_none() {
+            return Ok(None);
+        }
+
+        // A missing game termination marker is tolerated, in which case we fall back
+        // to the Result tag.
+        game.result = result
+            .or_else(|| game.tag("Result").and_then(|r| PgnResultValue::try_from(r).ok()))
+            .unwrap_or_default();
+
+        Ok(Some(game))
+    }
+
+    /// Parses moves into `variation` until its end. Returns the game
+    /// termination marker, if the variation was ended by one.
+    fn parse_variation(&mut self, variation: &mut PgnVariation, depth: usize) -> Result<Option<PgnResultValue>, PgnReadError> {
+        loop {
+            let line = self.line;
+
+            // The start of the next game's tag pair section also ends this game.
+            if depth == 0 && self.peek_token()? == Some(&PgnToken::TagOpen) {
+                return Ok(None);
+            }
+
+            match self.next_token()? {
+                None if depth == 0 => return Ok(None),
+                None => return Err(PgnReadError::UnbalancedVariation(line)),
+
+                // 8.2.6: Game Termination Markers
+                Some(PgnToken::Asterisk) if depth == 0 => return Ok(Some(PgnResultValue(None))),
+                Some(PgnToken::Symbol(s)) if let Ok(result) = PgnResultValue::try_from(s.as_str()) => {
+                    if depth > 0 {
+                        return Err(PgnReadError::UnbalancedVariation(line));
+                    }
+                    return Ok(Some(result));
+                }
+                Some(PgnToken::Asterisk) => return Err(PgnReadError::UnbalancedVariation(line)),
+
+                Some(PgnToken::VariationClose) if depth > 0 => return Ok(None),
+                Some(PgnToken::VariationClose) => return Err(PgnReadError::UnbalancedVariation(line)),
+
+                // 8.2.2: Movetext move number indications
+                //
+                // Import format is somewhat relaxed and it makes move number indicators
+                // optional, they are regenerated on export.
+                Some(PgnToken::Period) => {}
+                Some(PgnToken::Symbol(s)) if s.bytes().all(|b| b.is_ascii_digit()) => {}
+
+                Some(PgnToken::Symbol(san)) => variation.nodes.push(PgnNode { san, ..Default::default() }),
+
+                Some(PgnToken::Comment(text)) => match variation.nodes.last_mut() {
+                    Some(node) => node.comments.push(text),
+                    None => variation.comments.push(text),
+                },
+
+                Some(PgnToken::Nag(nag)) => {
+                    let node = variation.nodes.last_mut().ok_or(PgnReadError::DanglingAnnotation(line))?;
+                    node.nags.push(nag);
+                }
+
+                // 8.2.5: Movetext RAV (Recursive Annotation Variation)
+                //
+                // A RAV (Recursive Annotation Variation) is a sequence of movetext
+                // containing one or more moves enclosed in parentheses. A RAV is used to
+                // represent an alternative variation. The alternate move sequence given
+                // by an RAV is one that may be legally played by first unplaying the
+                // move that appears immediately prior to the RAV.
+                Some(PgnToken::VariationOpen) => {
+                    let mut rav = PgnVariation::default();
+                    self.parse_variation(&mut rav, depth + 1)?;
+                    let node = variation.nodes.last_mut().ok_or(PgnReadError::DanglingAnnotation(line))?;
+                    node.variations.push(rav);
+                }
+
+                Some(PgnToken::String(_) | PgnToken::TagOpen | PgnToken::TagClose) => return Err(PgnReadError::MalformedTag(line)),
+            }
+        }
+    }
+}
+
+impl<S: PgnSource> Iterator for PgnReader<S> {
+    type Item = Result<PgnGame, PgnReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> { self.read_game().transpose() }
+}
+
+#[derive(Debug, Error)]
+pub enum PgnWriteError {
+    #[error("Comment with both a right brace and a newline can not be exported: {0:?}")]
+    UnrepresentableComment(String),
+
+    #[error("Failed to write PGN: {0}")]
+    Fmt(#[from] fmt::Error),
+}
+
+/// The export format token of `comment`. A brace comment ends at the first
+/// right brace, so a comment with one is exported as a rest of line comment,
+/// unless it spans multiple lines.
+fn export_comment(comment: &str) -> Result<String, PgnWriteError> {
+    match (comment.contains('}'), comment.contains('\n')) {
+        (false, _) => Ok(format!("{{{comment}}}")),
+        (true, false) => Ok(format!("; {comment}")),
+        (true, true) => Err(PgnWriteError::UnrepresentableComment(comment.to_string())),
+    }
+}
+
+/// Collects the export format tokens of a variation.
+fn export_variation(tokens: &mut Vec<String>, variation: &PgnVariation, (mut fmc, mut stm): (u16, Color)) -> Result<(), PgnWriteError> {
+    for comment in &variation.comments {
+        tokens.push(export_comment(comment)?);
+    }
+
+    // 8.2.2.2: Export format move number indications
+    //
+    // All white move elements have a preceding move number indication. A black
+    // move element has a preceding move number indication only in two cases:
+    // first, if there is intervening annotation or commentary between the black
+    // move and the previous white move; and second, if there is no previous
+    // white move in the special case where a game starts from a position where
+    // Black is the active player.
+    let mut interrupted = true;
+
+    for node in &variation.nodes {
+        if stm == colors::WHITE {
+            tokens.push(format!("{fmc}."));
+        }
+        else if interrupted {
+            tokens.push(format!("{fmc}..."));
+        }
+
+        tokens.push(node.san.clone());
+        tokens.extend(node.nags.iter().map(|nag| format!("${nag}")));
+        for comment in &node.comments {
+            tokens.push(export_comment(comment)?);
+        }
+
+        for rav in &node.variations {
+            tokens.push("(".to_string());
+            export_variation(tokens, rav, (fmc, stm))?;
+            tokens.push(")".to_string());
+        }
+
+        interrupted = !node.comments.is_empty() || !node.variations.is_empty();
+
+        if stm == colors::BLACK {
+            fmc += 1;
+        }
+        stm = !stm;
+    }
+    Ok(())
+}
+
+impl PgnGame {
+    /// The game in PGN export format. Unlike [`fmt::Display`], this names the
+    /// comment that can not be exported.
+    pub fn export(&self) -> Result<String, PgnWriteError> {
+        let mut pgn = String::new();
+        self.write(&mut pgn)?;
+        Ok(pgn)
+    }
+
+    fn write(&self, f: &mut impl fmt::Write) -> Result<(), PgnWriteError> {
+        for tag_pair in &self.tags {
+            writeln!(f, "{tag_pair}")?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = vec![];
+        export_variation(&mut tokens, &self.mainline, self.first_move_number())?;
+        tokens.push(self.result.to_string());
+
+        // 8.2.1: Movetext line justification
+        //
+        // In PGN export format, tokens in the movetext are placed left justified on
+        // successive text lines each of which has less than 80 printing characters.
+        // A single space character appears between any two adjacent symbol tokens on
+        // the same line in the movetext.
+        //
+        // Parentheses are kept tight to the variation they enclose, like most
+        // exporters do. A rest of line comment always ends its line.
+        let mut width = 0;
+        let mut prev: Option<&str> = None;
+        for token in &tokens {
+            let spaced = prev.is_some_and(|prev| prev != "(" && token != ")");
+            if prev.is_some_and(|prev| prev.starts_with(';')) || (spaced && width + 1 + token.len() >= 80) {
+                f.write_str("\n")?;
+                width = 0;
+            }
+            else if spaced {
+                f.write_str(" ")?;
+                width += 1;
+            }
+            f.write_str(token)?;
+            width += token.len();
+            prev = Some(token);
+        }
+
+        writeln!(f)?;
+        writeln!(f)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.write(f).map_err(|_| fmt::Error) }
+}
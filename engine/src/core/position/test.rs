@@ -162,6 +162,146 @@ fn pgn_encoding_san_castling() {
     );
 }
 
+const ANNOTATED_PGN: &str = r#"% exported by a match runner, ignored
+[Event "Casual \"Blitz\" game"]
+[Site "https://lichess.org/abcdefgh"]
+[Result "1-0"]
+
+{Opening comment} 1. e4 e5 2. Nf3 $1 Nc6 (2... d6 {Philidor} 3. d4 (3. Bc4) 3... Nf6)
+3. Bb5!? a6 ; the Morphy defence
+4. Ba4 Nf6 5. O-O Be7 1-0
+
+[Event "Second game"]
+[Result "*"]
+
+1. d4 d5 2. c4 *
+"#;
+
+#[test]
+fn pgn_reader_multiple_games() {
+    use pgn::*;
+
+    let games = PgnReader::from_reader(ANNOTATED_PGN.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Should read all games");
+    assert_eq!(games.len(), 2);
+
+    let game = &games[0];
+    assert_eq!(game.tag("Event"), Some("Casual \"Blitz\" game"));
+    assert_eq!(game.tag("Site"), Some("https://lichess.org/abcdefgh"));
+    assert_eq!(game.result.to_string(), "1-0");
+    assert_eq!(
+        game.moves().collect::<Vec<_>>(),
+        ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O", "Be7"]
+    );
+    assert_eq!(game.mainline.comments, ["Opening comment"]);
+
+    let nf3 = &game.mainline.nodes[2];
+    assert_eq!(nf3.nags, [1]);
+
+    let nc6 = &game.mainline.nodes[3];
+    assert_eq!(nc6.variations.len(), 1);
+    let rav = &nc6.variations[0];
+    assert_eq!(rav.nodes.iter().map(|n| n.san.as_str()).collect::<Vec<_>>(), ["d6", "d4", "Nf6"]);
+    assert_eq!(rav.nodes[0].comments, ["Philidor"]);
+    assert_eq!(rav.nodes[1].variations[0].nodes[0].san, "Bc4");
+
+    let bb5 = &game.mainline.nodes[4];
+    assert_eq!(bb5.nags, [5]);
+    assert_eq!(game.mainline.nodes[5].comments, ["the Morphy defence"]);
+
+    assert_eq!(games[1].tag("Event"), Some("Second game"));
+    assert_eq!(games[1].moves().count(), 3);
+    assert_eq!(games[1].result, PgnResultValue(None));
+}
+
+#[test]
+fn pgn_reader_round_trip() {
+    use pgn::*;
+
+    for game in PgnReader::from_reader(ANNOTATED_PGN.as_bytes()) {
+        let game = game.expect("Should read the game");
+        let exported = game.to_string();
+
+        let mut reader = PgnReader::from_reader(exported.as_bytes());
+        let reimported = reader.read_game().expect("Should read exported game").expect("Should contain a game");
+        assert_eq!(reimported, game);
+        assert_eq!(reimported.to_string(), exported);
+        assert!(reader.read_game().unwrap().is_none());
+    }
+}
+
+#[test]
+fn pgn_reader_export_move_numbers() {
+    use pgn::*;
+
+    let pgn =
+        "[SetUp \"1\"]\n[FEN \"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 7\"]\n\n7... e5 {comment} 8. Nf3 (8. Nc3 Nc6) 8... Nc6 *";
+    let game = PgnReader::new(Tokenizer::new(pgn)).read_game().unwrap().unwrap();
+    let exported = game.to_string();
+    let movetext = exported.lines().rfind(|line| !line.is_empty()).unwrap();
+    assert_eq!(movetext, "7... e5 {comment} 8. Nf3 (8. Nc3 Nc6) 8... Nc6 *");
+}
+
+#[test]
+fn pgn_export_keeps_braces_in_comments() {
+    use pgn::*;
+
+    // a right brace would end a brace comment, so these comments are exported as
+    // rest of line comments.
+    let pgn = "1. e4 ; see {the} notes\n1... e5 (1... c5 ; {sicilian}\n) 2. Nf3 {a}; b}\n*";
+    let game = PgnReader::new(Tokenizer::new(pgn)).read_game().unwrap().unwrap();
+    assert_eq!(game.mainline.nodes[0].comments, ["see {the} notes"]);
+    assert_eq!(game.mainline.nodes[1].variations[0].nodes[0].comments, ["{sicilian}"]);
+    assert_eq!(game.mainline.nodes[2].comments, ["a", "b}"]);
+
+    let exported = game.export().unwrap();
+    let reimported = PgnReader::new(Tokenizer::new(&exported)).read_game().unwrap().unwrap();
+    assert_eq!(reimported, game);
+    assert_eq!(reimported.export().unwrap(), exported);
+
+    // but a rest of line comment can not span lines
+    let mut game = game;
+    game.mainline.nodes[0].comments.push("one}\ntwo".to_string());
+    assert!(matches!(game.export(), Err(PgnWriteError::UnrepresentableComment(comment)) if comment == "one}\ntwo"));
+}
+
+#[test]
+fn pgn_import_requires_a_game() {
+    zobrist::init();
+    magics::init();
+
+    for pgn in ["", "  \n\n"] {
+        assert!(matches!(Position::from_pgn(pgn), Err(PgnImportError::MissingGame)), "{pgn:?}");
+    }
+    assert!(Position::from_pgn("1. e4 ) *").is_err());
+}
+
+#[test]
+fn pgn_reader_recovers_after_error() {
+    use pgn::*;
+
+    let pgn = "[Event \"broken\"]\n\n1. e4 $ e5 *\n\n[Event \"fine\"]\n\n1. e4 *\n";
+    let mut reader = PgnReader::from_reader(pgn.as_bytes());
+    assert!(matches!(reader.read_game(), Err(PgnReadError::InvalidNag(3))));
+
+    let game = reader.read_game().unwrap().unwrap();
+    assert_eq!(game.tag("Event"), Some("fine"));
+    assert!(reader.read_game().unwrap().is_none());
+}
+
+#[test]
+fn pgn_import_annotated_game() {
+    zobrist::init();
+    magics::init();
+
+    let pos = Position::from_pgn(ANNOTATED_PGN).expect("Should import the first game");
+    assert_eq!(
+        format!("{}", FenExport(&pos)),
+        "r1bqk2r/1pppbppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 w kq - 4 6"
+    );
+}
+
 /// Helper to construct a position that has just reached a simple 2-fold
 /// repetition.
 fn build_twofold_repetition_position() -> Position {
@@ -371,3 +511,274 @@ fn epd_import_real_world_example() {
     assert_eq!(pos.get_piece(crate::core::coordinates::squares::H3).piece_type(), piece_type::ROOK);
     assert_eq!(pos.get_piece(crate::core::coordinates::squares::F6).piece_type(), piece_type::BISHOP);
 }
+
+fn validate_fen(fen: &str, mode: Validation) -> Result<Position, PositionValidationError> {
+    zobrist::init();
+    magics::init();
+
+    match FenImport(&mut Tokenizer::new(fen)).parse(mode) {
+        Ok(pos) => Ok(pos),
+        Err(FenParseError::IllegalPosition(err)) => Err(err),
+        Err(err) => panic!("{fen}: {err}"),
+    }
+}
+
+#[test]
+fn validate_accepts_legal_positions() {
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ];
+    for fen in fens {
+        assert!(validate_fen(fen, Validation::Strict).is_ok(), "{fen}");
+    }
+}
+
+fn assert_rejected(fen: &str, expected: impl Fn(&PositionValidationError) -> bool) {
+    let err = validate_fen(fen, Validation::Strict).expect_err(fen);
+    assert!(expected(&err), "{fen}: {err}");
+}
+
+#[test]
+fn validate_rejects_illegal_positions() {
+    use PositionValidationError as E;
+
+    assert_rejected("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w kq - 0 1", |e| {
+        matches!(e, E::MissingKing(colors::WHITE))
+    });
+    assert_rejected("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKKBNR w kq - 0 1", |e| {
+        matches!(e, E::MultipleKings(colors::WHITE))
+    });
+    assert_rejected("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNp w Qkq - 0 1", |e| {
+        matches!(e, E::PawnOnBackRank(_))
+    });
+    assert_rejected("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1", |e| matches!(e, E::OpponentInCheck));
+    assert_rejected("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1", |e| {
+        matches!(e, E::InvalidCastlingRights(_))
+    });
+    assert_rejected("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e3 0 1", |e| {
+        matches!(e, E::InvalidEpSquare(_))
+    });
+}
+
+#[test]
+fn validate_lenient_repairs_stale_state() {
+    let pos = validate_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBN1 w KQkq e3 0 1", Validation::Lenient).unwrap();
+    assert_eq!(pos.get_castling().to_string(), "Qkq");
+    assert_eq!(pos.get_ep_capture_square(), EpCaptureSquare::none());
+    assert_eq!(pos.get_key(), zobrist::Hash::from(&pos));
+
+    assert!(matches!(
+        Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKKBNR w kq - 0 1"),
+        Err(FenParseError::IllegalPosition(PositionValidationError::MultipleKings(colors::WHITE)))
+    ));
+    assert!(matches!(
+        Position::from_fen("rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1"),
+        Err(FenParseError::IllegalPosition(PositionValidationError::MissingKing(colors::BLACK)))
+    ));
+}
+
+#[test]
+fn importers_validate_in_the_given_mode() {
+    zobrist::init();
+    magics::init();
+
+    // the stale castling rights are only repaired when the mode allows it
+    let epd = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - id \"stale\";";
+    assert!(matches!(
+        EpdLineImport(&mut Tokenizer::new(epd)).parse(Validation::Strict),
+        Err(EpdLineParseError::FenError(FenParseError::IllegalPosition(
+            PositionValidationError::InvalidCastlingRights(_)
+        )))
+    ));
+    let (pos, _) = EpdLineImport(&mut Tokenizer::new(epd)).parse(Validation::Lenient).unwrap();
+    assert_eq!(pos.get_castling().to_string(), "Qkq");
+
+    let pgn = "[SetUp \"1\"]\n[FEN \"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e3 0 1\"]\n\n*";
+    let game = PgnReader::new(Tokenizer::new(pgn)).read_game().unwrap().unwrap();
+    assert!(matches!(
+        game.start_position(Validation::Strict),
+        Err(FenParseError::IllegalPosition(PositionValidationError::InvalidEpSquare(_)))
+    ));
+    assert_eq!(
+        game.start_position(Validation::Lenient).unwrap().get_ep_capture_square(),
+        EpCaptureSquare::none()
+    );
+}
+
+#[test]
+fn insufficient_material() {
+    zobrist::init();
+    magics::init();
+
+    let dead = [
+        "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+        "8/8/4k3/8/8/3K4/5B2/8 w - - 0 1",
+        "8/8/4k3/8/8/3K4/5N2/8 b - - 0 1",
+        "8/8/4k3/8/8/3KB3/8/8 w - - 0 1",
+        // same colored bishops, even spread over both sides
+        "8/2b5/4k3/8/8/3K4/5B2/8 w - - 0 1",
+        "8/8/4k3/8/8/3K4/5B2/4B3 w - - 0 1",
+    ];
+    for fen in dead {
+        let pos = Position::from_fen(fen).unwrap();
+        assert!(pos.is_insufficient_material(), "{fen}");
+        assert_eq!(pos.game_result(), Some(GameResult::Draw), "{fen}");
+    }
+
+    let alive = [
+        "8/8/4k3/8/8/3K4/5P2/8 w - - 0 1",
+        // opposite colored bishops can still mate in a corner
+        "8/1b6/4k3/8/8/3K4/5B2/8 w - - 0 1",
+        "8/8/4k3/8/8/3K4/5BN1/8 w - - 0 1",
+        "8/8/4k3/8/8/3K4/5NN1/8 w - - 0 1",
+        "8/8/2n1k3/8/8/3K4/5N2/8 w - - 0 1",
+    ];
+    for fen in alive {
+        let pos = Position::from_fen(fen).unwrap();
+        assert!(!pos.is_insufficient_material(), "{fen}");
+        assert_eq!(pos.game_result(), None, "{fen}");
+    }
+}
+
+#[test]
+fn likely_draw_is_only_a_search_result() {
+    zobrist::init();
+    magics::init();
+
+    let pos = Position::from_fen("8/8/4k3/8/8/3K4/5NN1/8 w - - 0 1").unwrap();
+    assert!(pos.is_likely_draw());
+    assert_eq!(pos.game_result(), None);
+    assert_eq!(pos.search_result(Depth::ROOT), Some(GameResult::Draw));
+
+    let pos = Position::from_fen("8/8/2n1k3/8/8/3K4/5N2/8 w - - 0 1").unwrap();
+    assert!(!pos.is_likely_draw());
+}
+
+#[test]
+fn upcoming_repetition_knight_shuffle() {
+    use move_flags::*;
+    use squares::*;
+
+    zobrist::init();
+    magics::init();
+
+    // 1. Nf3 Nf6 2. Ng1: black can go back to the start position with Ng8.
+    let mut pos = Position::start_position();
+    for mv in [Move::new(G1, F3, QUIET), Move::new(G8, F6, QUIET), Move::new(F3, G1, QUIET)] {
+        pos.make_move(mv, &mut ());
+    }
+    assert!(!pos.has_twofold_repetition());
+    assert!(pos.has_upcoming_repetition(Depth::new(4)));
+    // the repeated position is the root (or before it), which has only occurred
+    // once
+    assert!(!pos.has_upcoming_repetition(Depth::new(3)));
+    assert!(!pos.has_upcoming_repetition(Depth::ROOT));
+
+    // 2... Ng8 3. Nf3 Nf6 4. Ng1: the start position has now occurred twice, so
+    // going back counts even before the root.
+    for mv in [
+        Move::new(F6, G8, QUIET),
+        Move::new(G1, F3, QUIET),
+        Move::new(G8, F6, QUIET),
+        Move::new(F3, G1, QUIET),
+    ] {
+        pos.make_move(mv, &mut ());
+    }
+    assert!(pos.has_upcoming_repetition(Depth::ROOT));
+}
+
+#[test]
+fn upcoming_repetition_perpetual_check() {
+    use move_flags::*;
+    use squares::*;
+
+    zobrist::init();
+    magics::init();
+
+    // White is a rook down, but has a perpetual with Qf8+ and Qf5+.
+    let fen = "7k/6p1/7p/5Q2/8/8/r5PP/6K1 w - - 0 1";
+    let mut pos = Position::from_fen(fen).unwrap();
+    for mv in [Move::new(F5, F8, QUIET), Move::new(H8, H7, QUIET)] {
+        pos.make_move(mv, &mut ());
+        assert!(!pos.has_upcoming_repetition(Depth::MAX));
+    }
+    // 2. Qf5+: black can repeat with Kh8, and white then with Qf8+.
+    pos.make_move(Move::new(F8, F5, QUIET), &mut ());
+    assert!(pos.has_upcoming_repetition(Depth::MAX));
+    pos.make_move(Move::new(H7, H8, QUIET), &mut ());
+    assert!(pos.has_upcoming_repetition(Depth::MAX));
+
+    // A pawn move is irreversible, so nothing from before can come back.
+    let mut pos = Position::from_fen(fen).unwrap();
+    for mv in [
+        Move::new(F5, F8, QUIET),
+        Move::new(H8, H7, QUIET),
+        Move::new(F8, F5, QUIET),
+        Move::new(G7, G6, QUIET),
+    ] {
+        pos.make_move(mv, &mut ());
+    }
+    assert!(!pos.has_upcoming_repetition(Depth::MAX));
+}
+
+#[test]
+fn incremental_auxiliary_keys() {
+    use move_flags::*;
+    use squares::*;
+
+    zobrist::init();
+    magics::init();
+
+    let assert_keys = |pos: &Position| {
+        assert_eq!(pos.get_pawn_key(), zobrist::Hash::pawn_key(pos.piece_info()));
+        for c in [colors::WHITE, colors::BLACK] {
+            assert_eq!(pos.get_non_pawn_key(c), zobrist::Hash::non_pawn_key(pos.piece_info(), c));
+        }
+        assert_eq!(pos.get_material_key(), zobrist::Hash::material_key(pos.piece_info()));
+    };
+
+    // double push, en passant, castling, capture and promotion
+    let mut pos = Position::from_fen("r3k3/1P6/8/8/5p2/8/4P3/4K2R w Kq - 0 1").unwrap();
+    let moves = [
+        Move::new(E2, E4, DOUBLE_PAWN_PUSH),
+        Move::new(F4, E3, EN_PASSANT),
+        Move::new(E1, G1, KING_CASTLE),
+        Move::new(E8, D7, QUIET),
+        Move::new(B7, A8, CAPTURE_PROMOTION_QUEEN),
+    ];
+    assert_keys(&pos);
+    for mv in moves {
+        let (pawn_key, material_key) = (pos.get_pawn_key(), pos.get_material_key());
+        pos.make_move(mv, &mut ());
+        assert_keys(&pos);
+
+        // non-pawn moves leave the pawn key untouched
+        if mv.get_flag() == KING_CASTLE {
+            assert_eq!(pos.get_pawn_key(), pawn_key);
+        }
+        // only captures and promotions change the material
+        if !mv.get_flag().is_capture() && !mv.get_flag().is_promo() {
+            assert_eq!(pos.get_material_key(), material_key);
+        }
+    }
+
+    // null moves change neither key
+    let keys = (pos.get_pawn_key(), pos.get_non_pawn_key(colors::WHITE));
+    pos.make_null_move();
+    assert_eq!((pos.get_pawn_key(), pos.get_non_pawn_key(colors::WHITE)), keys);
+}
+
+#[test]
+fn material_key_ignores_placement() {
+    zobrist::init();
+    magics::init();
+
+    let a = Position::from_fen("4k3/pp6/8/8/8/8/1N3PP1/4K3 w - - 0 1").unwrap();
+    let b = Position::from_fen("3k4/7p/6p1/8/2N5/6P1/5P2/K7 b - - 0 1").unwrap();
+    let c = Position::from_fen("4k3/pp6/8/8/8/8/1B3PP1/4K3 w - - 0 1").unwrap();
+
+    assert_eq!(a.get_material_key(), b.get_material_key());
+    assert_ne!(a.get_material_key(), c.get_material_key());
+}
@@ -14,7 +14,7 @@ use crate::{
         coordinates::{Square, squares},
         depth::Depth,
         r#move::Move,
-        piece::{PieceType, piece_type},
+        piece::{Piece, PieceType, piece_type},
         search::{id, ordering::MoveScore, score::AnyScore},
         zobrist,
     },
@@ -412,6 +412,282 @@ impl PieceHistories {
     }
 }
 
+/// Number of distinct [Piece] values, including the colored [piece_type::NONE]
+/// pieces.
+const N_PIECES: usize = 14;
+
+/// The piece that was moved and its destination square. Used as the key into
+/// the continuation histories and counter moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceTo {
+    pub piece: Piece,
+    pub to: Square,
+}
+
+impl Default for PieceTo {
+    fn default() -> Self {
+        Self {
+            piece: Piece::default(),
+            to: squares::A1,
+        }
+    }
+}
+
+impl PieceTo {
+    pub const fn new(piece: Piece, to: Square) -> Self { Self { piece, to } }
+
+    #[inline]
+    const fn index(&self) -> usize { self.piece.v() as usize * squares::N_VARIANTS + self.to.index() }
+}
+
+#[derive(Clone)]
+pub struct CaptureHistory {
+    /// For each color, moving piece type, destination square and captured piece
+    /// type. Quiet promotions use [piece_type::NONE] as the captured piece.
+    scores: [[[[HistoryScore; piece_type::N_VARIANTS]; squares::N_VARIANTS]; piece_type::N_VARIANTS]; colors::N_VARIANTS],
+}
+
+const impl Default for CaptureHistory {
+    fn default() -> Self { Self::new() }
+}
+
+impl CaptureHistory {
+    pub const fn new() -> Self {
+        Self {
+            scores: [[[[HistoryScore::new(0); piece_type::N_VARIANTS]; squares::N_VARIANTS]; piece_type::N_VARIANTS]; colors::N_VARIANTS],
+        }
+    }
+
+    pub const fn clear(&mut self) { *self = Self::new(); }
+
+    pub const fn get(&self, c: Color, pt: PieceType, to: Square, captured: PieceType) -> HistoryScore {
+        match c {
+            colors::WHITE => self.get_for::<perspectives::White>(pt, to, captured),
+            colors::BLACK => self.get_for::<perspectives::Black>(pt, to, captured),
+            _ => unsafe { unreachable_unchecked() },
+        }
+    }
+
+    pub const fn get_for<P: Perspective>(&self, pt: PieceType, to: Square, captured: PieceType) -> HistoryScore {
+        let c = P::COLOR.v() as usize;
+        unsafe {
+            *self
+                .scores
+                .get_unchecked(c)
+                .get_unchecked(pt.v() as usize)
+                .get_unchecked(to.index())
+                .get_unchecked(captured.v() as usize)
+        }
+    }
+
+    pub const fn update_for<P: Perspective>(&mut self, pt: PieceType, to: Square, captured: PieceType, val: HistoryScore) {
+        let c = P::COLOR.v() as usize;
+        let curr_score = unsafe {
+            self.scores
+                .get_unchecked_mut(c)
+                .get_unchecked_mut(pt.v() as usize)
+                .get_unchecked_mut(to.index())
+                .get_unchecked_mut(captured.v() as usize)
+        };
+
+        curr_score.update(val);
+    }
+}
+
+/// History of quiet moves indexed by a previous move, i.e. how well a move
+/// does in response to (or as a follow up of) another move.
+#[derive(Clone)]
+pub struct ContinuationHistory {
+    /// For each previous [PieceTo] the scores of the current piece to its
+    /// destination square.
+    scores: Box<[[[HistoryScore; squares::N_VARIANTS]; N_PIECES]]>,
+}
+
+impl Default for ContinuationHistory {
+    fn default() -> Self { Self::new() }
+}
+
+impl ContinuationHistory {
+    pub fn new() -> Self {
+        Self {
+            scores: vec![[[HistoryScore::new(0); squares::N_VARIANTS]; N_PIECES]; N_PIECES * squares::N_VARIANTS].into_boxed_slice(),
+        }
+    }
+
+    pub fn clear(&mut self) { self.scores.fill([[HistoryScore::new(0); squares::N_VARIANTS]; N_PIECES]); }
+
+    #[inline]
+    pub fn get(&self, prev: PieceTo, curr: PieceTo) -> HistoryScore {
+        unsafe {
+            *self
+                .scores
+                .get_unchecked(prev.index())
+                .get_unchecked(curr.piece.v() as usize)
+                .get_unchecked(curr.to.index())
+        }
+    }
+
+    #[inline]
+    pub fn update(&mut self, prev: PieceTo, curr: PieceTo, val: HistoryScore) {
+        let curr_score = unsafe {
+            self.scores
+                .get_unchecked_mut(prev.index())
+                .get_unchecked_mut(curr.piece.v() as usize)
+                .get_unchecked_mut(curr.to.index())
+        };
+
+        curr_score.update(val);
+    }
+}
+
+/// The last quiet move that refuted a previous move.
+#[derive(Clone)]
+pub struct CounterMoves {
+    moves: [Move; N_PIECES * squares::N_VARIANTS],
+}
+
+const impl Default for CounterMoves {
+    fn default() -> Self { Self::new() }
+}
+
+impl CounterMoves {
+    pub const fn new() -> Self {
+        Self {
+            moves: [Move::null(); N_PIECES * squares::N_VARIANTS],
+        }
+    }
+
+    pub const fn clear(&mut self) { *self = Self::new(); }
+
+    #[inline]
+    pub const fn get(&self, prev: PieceTo) -> Move { unsafe { *self.moves.get_unchecked(prev.index()) } }
+
+    #[inline]
+    pub const fn set(&mut self, prev: PieceTo, mov: Move) { unsafe { *self.moves.get_unchecked_mut(prev.index()) = mov } }
+}
+
+const CORRECTION_SIZE: usize = 16384;
+
+/// Corrections are stored with this many units per centipawn.
+const CORRECTION_GRAIN: i32 = 256;
+
+/// Difference between the search result and the static eval of positions,
+/// keyed by a partial zobrist key of the position.
+#[derive(Clone)]
+pub struct CorrectionHistory {
+    /// For each side to move.
+    entries: Box<[HistoryScore]>,
+}
+
+impl Default for CorrectionHistory {
+    fn default() -> Self { Self::new() }
+}
+
+impl CorrectionHistory {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![HistoryScore::new(0); colors::N_VARIANTS * CORRECTION_SIZE].into_boxed_slice(),
+        }
+    }
+
+    pub fn clear(&mut self) { self.entries.fill(HistoryScore::new(0)); }
+
+    #[inline]
+    fn index(c: Color, key: zobrist::Hash) -> usize { c.v() as usize * CORRECTION_SIZE + key.index(CORRECTION_SIZE) }
+
+    #[inline]
+    pub fn get(&self, c: Color, key: zobrist::Hash) -> HistoryScore { unsafe { *self.entries.get_unchecked(Self::index(c, key)) } }
+
+    #[inline]
+    pub fn update(&mut self, c: Color, key: zobrist::Hash, val: HistoryScore) {
+        unsafe { self.entries.get_unchecked_mut(Self::index(c, key)) }.update(val);
+    }
+}
+
+/// The keys of a position that are used to look up its eval correction.
+#[derive(Clone, Copy)]
+pub struct CorrectionKeys {
+    pub pawn: zobrist::Hash,
+    pub non_pawn: [zobrist::Hash; colors::N_VARIANTS],
+}
+
+/// All move ordering and eval correction statistics that are kept between
+/// searches.
+#[derive(Default, Clone)]
+pub struct Histories {
+    pub quiet: PieceHistories,
+    pub capture: CaptureHistory,
+    pub continuation: ContinuationHistory,
+    pub counter_moves: CounterMoves,
+    pub pawn_correction: CorrectionHistory,
+    /// Keyed by the non-pawn pieces of each color.
+    pub non_pawn_correction: [CorrectionHistory; colors::N_VARIANTS],
+}
+
+impl Histories {
+    pub fn new() -> Self {
+        Self {
+            quiet: PieceHistories::new(),
+            capture: CaptureHistory::new(),
+            continuation: ContinuationHistory::new(),
+            counter_moves: CounterMoves::new(),
+            pawn_correction: CorrectionHistory::new(),
+            non_pawn_correction: [CorrectionHistory::new(), CorrectionHistory::new()],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.quiet.clear();
+        self.capture.clear();
+        self.continuation.clear();
+        self.counter_moves.clear();
+        self.pawn_correction.clear();
+        self.non_pawn_correction.iter_mut().for_each(CorrectionHistory::clear);
+    }
+
+    /// Sum of the piece history and the continuation histories of all
+    /// `prev_moves` for a quiet move.
+    #[inline]
+    pub fn quiet_score(&self, prev_moves: &[PieceTo], moved: PieceTo) -> i32 {
+        let (c, pt) = moved.piece.unpack();
+        let cont_score: i32 = prev_moves.iter().map(|&prev| self.continuation.get(prev, moved).v() as i32).sum();
+        self.quiet.get(c, pt, moved.to).v() as i32 + cont_score
+    }
+
+    /// The amount in centipawns by which the static eval of a position is
+    /// expected to be off, relative to `stm`.
+    #[inline]
+    pub fn eval_correction(&self, stm: Color, keys: CorrectionKeys) -> i32 {
+        let pawn = self.pawn_correction.get(stm, keys.pawn).v() as i32;
+        let non_pawn: i32 = iter::zip(&self.non_pawn_correction, keys.non_pawn)
+            .map(|(h, key)| h.get(stm, key).v() as i32)
+            .sum();
+        (pawn + non_pawn) / CORRECTION_GRAIN
+    }
+
+    /// `diff` is the search result minus the corrected static eval in
+    /// centipawns, relative to `stm`. Deeper searches are trusted more.
+    #[inline]
+    pub fn update_eval_correction(&mut self, stm: Color, keys: CorrectionKeys, diff: i32, depth: Depth) {
+        let max = MAX_HISTORY as i32 / 4;
+        let bonus = HistoryScore::new((diff * CORRECTION_GRAIN * depth.v() as i32 / 8).clamp(-max, max) as THistoryScore);
+
+        self.pawn_correction.update(stm, keys.pawn, bonus);
+        for (h, key) in iter::zip(&mut self.non_pawn_correction, keys.non_pawn) {
+            h.update(stm, key, bonus);
+        }
+    }
+
+    #[inline]
+    pub fn update_quiet(&mut self, prev_moves: &[PieceTo], moved: PieceTo, val: HistoryScore) {
+        let (c, pt) = moved.piece.unpack();
+        self.quiet.update(c, pt, moved.to, val);
+        for &prev in prev_moves {
+            self.continuation.update(prev, moved, val);
+        }
+    }
+}
+
 const LINE_CAP: usize = Depth::MAX.index();
 
 #[derive(Default, Clone, Debug)]
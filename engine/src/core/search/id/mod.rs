@@ -1,14 +1,13 @@
 use core::fmt;
 use std::{
     cmp::{Reverse, max, min},
-    convert::Infallible,
     ops::{ControlFlow, Deref},
-    path::PathBuf,
-    str::FromStr,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
-use saturating_cast::SaturatingCast;
+use static_assertions::const_assert;
+use uom::si::{information::mebibyte, u64::Information};
 
 use crate::{
     core::{
@@ -18,32 +17,33 @@ use crate::{
             perspectives::{Black, White},
         },
         config::Configuration,
-        coordinates::EpTargetSquare,
+        coordinates::{EpTargetSquare, squares},
         depth::{Depth, FractionalDepth},
         eval::{
-            GameResult, StaticEvaluator,
-            hce::{self, TaperValue, bishop_pair, hygge_king, king_safety, material, mobility, passed_pawns},
-            nnue::{self, AccumulatorStack, EagerAccUpdates},
+            EvalKeys, GameResult, StaticEvaluator, endgame,
+            hce::{self, TaperValue},
+            nnue::{self, AccUpdates, AccumulatorPair, AccumulatorStack, LazyAccUpdates, Network, trace::Trace},
+            pawns::PawnTable,
         },
         r#move::{MAX_LEGAL_MOVES, Move, MoveList},
         move_iter::{
             fold_moves,
             opt::{AllLegal, Captures},
         },
-        params::IParams,
-        piece::piece_type,
+        params::{CreateParamsError, IParams, IdHceParams, IdHceParamsRef, id_hce_params_default},
+        piece::{Piece, PieceType, piece_type},
         ply::Ply,
         position::{CheckState, PieceInfo, PieceInfoObserver, Position},
         search::{
             data::{
-                self, HistoryScore, Line, PieceHistories, RbSet, SearchStack, THistoryScore, TTBound, TTDepth, TTKey, TTMove, TTScore, TTStaticEval,
-                TranspositionTable,
+                self, CorrectionKeys, Histories, HistoryScore, Line, PieceTo, RbSet, SearchStack, THistoryScore, TTBound, TTDepth, TTKey, TTMove,
+                TTScore, TTStaticEval, TranspositionTable,
             },
             limit::UciLimit,
             mcts::eval::Quality,
             ordering::{self, MovePicker, MoveScore, MoveScorer, RtStage, ScoredMove, Stage},
             quiesce::{self, QSearchParams, QSearcher},
-            score::{AnyScore, Cp, Score, scores},
+            score::{AnyScore, Cp, RawScore, Score, scores},
             strat::{UciArg, UciCp, UciCurrmove, UciDepth, UciNodes, UciNps, UciPv, UciScore, UciSearchtime, UciSeldepth},
             tree::{NodeKind, NodeType, node_types::*},
         },
@@ -62,59 +62,121 @@ use crate::{
 /// confident position to produce a peaked (low-entropy) distribution.
 const ROOT_ENTROPY_TEMP: f32 = 0.3;
 
-#[derive(Default)]
-pub struct HceEvaluator;
+pub struct HceEvaluator {
+    pawns: PawnTable,
+    params: IdHceParamsRef,
+}
 
-impl StaticEvaluator for HceEvaluator {
-    fn eval<P: Perspective>(&mut self, pos: &PieceInfo, turn: Turn, ep_sq: EpTargetSquare, phase: TaperValue) -> Score<P> {
-        fn static_value<P: Perspective>(pos: &PieceInfo, ep_sq: EpTargetSquare, phase: TaperValue, turn: Turn) -> Score<P> {
-            material::<P>(pos)
-                + mobility::<P>(pos, phase)
-                + hce::psqt::<P>(pos, phase)
-                + bishop_pair::<P>(pos)
-                + king_safety::<P>(pos, ep_sq, turn, phase)
-                + passed_pawns::<P>(pos, ep_sq, turn)
-                + hygge_king::<P>(pos, phase)
+impl Default for HceEvaluator {
+    fn default() -> Self {
+        Self {
+            pawns: PawnTable::new_of_size(Information::new::<mebibyte>(1)),
+            params: id_hce_params_default(),
         }
+    }
+}
 
-        let (ep_w, ep_b) = if P::COLOR == colors::WHITE {
-            (ep_sq, EpTargetSquare::none())
-        }
-        else {
-            (EpTargetSquare::none(), ep_sq)
-        };
-        let w_q = static_value::<P>(pos, ep_w, phase, turn);
-        let b_q = static_value::<P::Opponent>(pos, ep_b, phase, turn);
-        w_q + !b_q
+impl StaticEvaluator for HceEvaluator {
+    fn eval<P: Perspective>(&mut self, pos: &PieceInfo, turn: Turn, ep_sq: EpTargetSquare, phase: TaperValue, keys: EvalKeys) -> Score<P> {
+        let pawns = self.pawns.probe(pos, keys.pawn, &self.params);
+        let score = hce::eval::<P>(pos, turn, ep_sq, phase, &pawns, &self.params);
+        endgame::adjust::<P>(pos, turn, keys.material, score)
     }
 
-    fn try_from_config<C: Deref<Target = Configuration>>(_cfg: C) -> Result<Self, Infallible> { Ok(Self) }
+    fn try_from_config<C: Deref<Target = Configuration>>(cfg: C) -> Result<Self, impl fmt::Display> {
+        Ok::<_, CreateParamsError>(Self {
+            pawns: PawnTable::new_of_size(cfg.pawn_hash()),
+            params: IdHceParams::try_from_config(cfg)?,
+        })
+    }
 }
 
-pub struct NnueEvaluator {
-    accs: AccumulatorStack<EagerAccUpdates>,
+/// Evaluates with the nnue, whose accumulators follow the search with the
+/// `Updates`.
+pub struct NnueEvaluator<Updates: AccUpdates = LazyAccUpdates> {
+    net: Arc<Network>,
+    accs: AccumulatorStack<Updates>,
     curr: Depth,
 }
 
-impl NnueEvaluator {
-    fn new() -> Self {
+impl<Updates: AccUpdates> NnueEvaluator<Updates> {
+    pub fn new(net: Arc<Network>) -> Self {
         Self {
-            accs: AccumulatorStack::default(),
+            accs: AccumulatorStack::new(Updates::for_network(&net), &net),
+            net,
             curr: Depth::ROOT,
         }
     }
 }
 
-impl Default for NnueEvaluator {
-    fn default() -> Self { Self::new() }
+impl<Updates: AccUpdates> Default for NnueEvaluator<Updates> {
+    fn default() -> Self { Self::new(nnue::default_nnue()) }
 }
 
-impl StaticEvaluator for NnueEvaluator {
-    fn eval<P: Perspective>(&mut self, _: &PieceInfo, _: Turn, _: EpTargetSquare, _: TaperValue) -> Score<P> {
-        let nnue = nnue::get_nnue();
-        let accs = self.accs.get_accs_mut(self.curr);
-        let (stm_acc, nstm_acc) = accs.get_mut_for::<P>();
-        let nnue_eval = nnue.forward(stm_acc, nstm_acc);
+impl<Updates: AccUpdates> NnueEvaluator<Updates>
+where
+    AccumulatorPair<Updates>: PieceInfoObserver,
+{
+    /// The eval of `pos` with `turn` to move from White's perspective, and the
+    /// raw output of the network.
+    fn white_eval(&mut self, pos: &PieceInfo, turn: Turn) -> (AnyScore, f32) {
+        let bucket = nnue::buckets::output_bucket(pos.get_occupancy());
+        let (acc_w, acc_b) = self.accs.get_mut_for::<White>(self.curr, &self.net);
+        let (stm_acc, nstm_acc) = if turn == colors::WHITE {
+            (acc_w, acc_b)
+        }
+        else {
+            (acc_b, acc_w)
+        };
+
+        let raw = self.net.raw_output(stm_acc, nstm_acc, bucket);
+        let eval = self.net.forward(stm_acc, nstm_acc, bucket);
+        if turn == colors::WHITE {
+            (eval, raw)
+        }
+        else {
+            (-eval, -raw)
+        }
+    }
+
+    /// Evaluates `pos`, and again without each of its pieces but the kings.
+    pub fn trace(&mut self, pos: &Position) -> Trace {
+        let pieces = pos.piece_info();
+        let turn = pos.get_turn();
+        self.observe_forward().on_init(pieces);
+        let (eval, raw) = self.white_eval(pieces, turn);
+
+        let mut board = [(Piece::default(), None); squares::N_VARIANTS];
+        for sq in pieces.get_occupancy() {
+            let piece = pieces.get_piece(sq);
+            let contribution = (piece.piece_type() != piece_type::KING).then(|| {
+                // the accumulators without the piece are updated from those of the position
+                self.forward();
+                let mut without = pieces.clone();
+                without.remove_piece(sq, self.observe_forward());
+                let (eval_without, _) = self.white_eval(&without, turn);
+                self.backward();
+                eval - eval_without
+            });
+            board[sq.v() as usize] = (piece, contribution);
+        }
+
+        Trace {
+            squares: board,
+            bucket: nnue::buckets::output_bucket(pieces.get_occupancy()),
+            raw,
+            eval,
+        }
+    }
+}
+
+impl<Updates: AccUpdates> StaticEvaluator for NnueEvaluator<Updates>
+where
+    AccumulatorPair<Updates>: PieceInfoObserver,
+{
+    fn eval<P: Perspective>(&mut self, pos: &PieceInfo, _: Turn, _: EpTargetSquare, _: TaperValue, _: EvalKeys) -> Score<P> {
+        let (stm_acc, nstm_acc) = self.accs.get_mut_for::<P>(self.curr, &self.net);
+        let nnue_eval = self.net.forward(stm_acc, nstm_acc, nnue::buckets::output_bucket(pos.get_occupancy()));
         // Safety: We picked `P` as side-to-move above.
         unsafe { nnue_eval.interpret_as() }
     }
@@ -132,18 +194,13 @@ impl StaticEvaluator for NnueEvaluator {
     fn observe_forward(&mut self) -> &mut impl PieceInfoObserver { self.accs.get_accs_mut(self.curr) }
     // observer backward does nothing, since we just pop to the latest state.
 
+    /// Loads the network of the config. Every search worker builds its
+    /// evaluator between searches, so a new network is used from the next
+    /// search on, without affecting other engines.
     fn try_from_config<C: Deref<Target = Configuration>>(cfg: C) -> Result<Self, impl fmt::Display> {
-        let nnue_str = cfg.nnue_path();
-        let nnue_bytes = if nnue_str.is_empty() {
-            nnue::DEFAULT_NNUE
-        }
-        else {
-            let nnue_path = PathBuf::from_str(nnue_str).expect("Infallible was returned??");
-            nnue::read_net_bytes(&nnue_path).map_err(|e| format!("Bad nnue file: {e}"))?
-        };
-        nnue::set_nnue(nnue_bytes).map_err(|e| format!("Unhealthy nnue: {e}"))?;
+        let net = nnue::nnue_from_path(cfg.nnue_path()).map_err(|e| format!("Invalid nnue: {e}"))?;
 
-        Ok::<_, String>(Self::new())
+        Ok::<_, String>(Self::new(net))
     }
 }
 
@@ -217,6 +274,19 @@ pub const trait IdParams {
     fn nmp_phase_factor(&self) -> u32;
     fn nmp_margin(&self) -> AnyScore;
     fn nmp_depth_margin(&self) -> i32;
+    fn rfp_margin(&self) -> AnyScore;
+    fn rfp_max_depth(&self) -> Depth;
+    fn razor_margin(&self) -> AnyScore;
+    fn razor_max_depth(&self) -> Depth;
+    fn fp_margin(&self) -> AnyScore;
+    fn fp_depth_margin(&self) -> i32;
+    fn fp_max_depth(&self) -> Depth;
+    fn lmp_base(&self) -> u8;
+    fn lmp_max_depth(&self) -> Depth;
+    fn probcut_margin(&self) -> AnyScore;
+    fn probcut_min_depth(&self) -> Depth;
+    fn probcut_reduction(&self) -> Depth;
+    fn lmr_history_divisor(&self) -> i32;
     fn aw_margin(&self) -> AnyScore { hce::piece_score(piece_type::PAWN) / 4 }
 }
 
@@ -231,6 +301,45 @@ pub fn go<X: IParams>(
     eval: &mut impl StaticEvaluator,
     params: X::Ref,
 ) -> Option<Move>
+where
+    X::Ref: ChronoParams + QSearchParams + ScorerParams + IdParams + Clone + fmt::Debug,
+{
+    go_with_stats(pos, limit, timeman, debug, true, ct, tt, hh, eval, params, &mut SearchStats::default()).map(|(mov, _)| mov)
+}
+
+/// Searches like [go], but without reporting to the gui. Returns the best move
+/// and its score, relative to the side to move.
+pub fn search<X: IParams>(
+    pos: &mut Position,
+    limit: UciLimit,
+    timeman: &mut TimeMan<X>,
+    ct: CancellationToken,
+    tt: &mut TT,
+    hh: &mut HH,
+    eval: &mut impl StaticEvaluator,
+    params: X::Ref,
+) -> Option<(Move, AnyScore)>
+where
+    X::Ref: ChronoParams + QSearchParams + ScorerParams + IdParams + Clone + fmt::Debug,
+{
+    let debug = DebugMode::off();
+    go_with_stats(pos, limit, timeman, &debug, false, ct, tt, hh, eval, params, &mut SearchStats::default())
+}
+
+/// `report`: Whether to print the info of each iteration.
+fn go_with_stats<X: IParams>(
+    pos: &mut Position,
+    limit: UciLimit,
+    timeman: &mut TimeMan<X>,
+    debug: &DebugMode,
+    report: bool,
+    ct: CancellationToken,
+    tt: &mut TT,
+    hh: &mut HH,
+    eval: &mut impl StaticEvaluator,
+    params: X::Ref,
+    stats: &mut SearchStats,
+) -> Option<(Move, AnyScore)>
 where
     X::Ref: ChronoParams + QSearchParams + ScorerParams + IdParams + Clone + fmt::Debug,
 {
@@ -243,8 +352,8 @@ where
     }
 
     let mut searcher = Searcher::<_, X>::new(pos, limit, timeman, ct, tt, hh, eval, params.clone());
-    let mut stats = SearchStats::default();
     let mut best_move = None;
+    let mut best_score = scores::ZERO;
     let mut last_best_move;
     let root_tt_entry = searcher.tt.get(pos.get_key()).cloned();
     let mut curr_score = root_tt_entry.as_ref().map(|e| e.score).unwrap_or(scores::ZERO);
@@ -253,10 +362,10 @@ where
         let iter_start = Instant::now();
 
         curr_score = if depth > Depth::new(3) {
-            searcher.aspire_root(pos, &mut stats, depth, curr_score)
+            searcher.aspire_root(pos, stats, depth, curr_score)
         }
         else {
-            searcher.search_root(pos, &mut stats, depth)
+            searcher.search_root(pos, stats, depth)
         };
 
         // make sure to break before messing up the order of the previous iteration with
@@ -270,10 +379,12 @@ where
         searcher.sort_root();
 
         best_move = searcher.root_best_move();
-        if let Some(best_move) = best_move
+        best_score = curr_score;
+        if report
+            && let Some(best_move) = best_move
             && let Some(search_time) = searcher.timeman.elapsed_search_time()
         {
-            uci_info(depth, &stats, curr_score, best_move, search_time, searcher.pv());
+            uci_info(depth, stats, curr_score, best_move, search_time, searcher.pv());
         }
 
         // update stats
@@ -297,22 +408,91 @@ where
         timeman.hint_time_target(stats.iter_time);
         timeman.hint_movestreak_target(stats.root_movestreak);
 
-        if searcher.should_stop(&stats) || searcher.timeman.reached_target() {
+        if searcher.should_stop(stats) || searcher.timeman.reached_target() {
             break;
         }
     }
 
-    best_move
+    best_move.map(|mov| (mov, best_score))
+}
+
+/// The depth [bench] searches to, if not specified otherwise.
+pub const BENCH_DEPTH: Depth = Depth::new(8);
+
+#[rustfmt::skip]
+const BENCH_POSITIONS: &[&str] = &[
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "r1bbk1nr/pp3p1p/2n5/1N4p1/2Np1B2/8/PPP2PPP/2KR1B1R w kq - 0 13",
+    "r1bq1rk1/ppp1nppp/4n3/3p3Q/3P4/1BP1B3/PP1N2PP/R4RK1 w - - 1 16",
+    "4r1k1/r1q2ppp/ppp2n2/4P3/5Rb1/1N1BQ3/PPP3PP/R5K1 w - - 1 17",
+    "2rqkb1r/ppp2p2/2npb1p1/1N1Nn2p/2P1PP2/8/PP2B1PP/R1BQK2R b KQ - 0 11",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/8 b - - 0 1",
+];
+
+/// Search each of the bench positions to a fixed `depth`, starting from a clean
+/// state every time.
+///
+/// Returns the total node count and the time it took. The node count is
+/// deterministic, so it serves as the signature of the search: every functional
+/// change of the search changes it.
+pub fn bench<X: IParams>(depth: Depth, tt: &mut TT, hh: &mut HH, eval: &mut impl StaticEvaluator, params: X::Ref) -> (u64, Duration)
+where
+    X::Ref: ChronoParams + QSearchParams + ScorerParams + IdParams + Clone + fmt::Debug,
+{
+    let start = Instant::now();
+    let mut nodes = 0;
+
+    for fen in BENCH_POSITIONS {
+        let mut pos = Position::from_fen(fen).expect("bench positions are valid");
+        let limit = UciLimit { depth, ..Default::default() };
+        let mut timeman = TimeMan::<X>::new_with_limits(&limit, &pos, params.clone());
+        let mut stats = SearchStats::default();
+
+        tt.clear();
+        hh.clear();
+        eval.init(pos.piece_info());
+
+        go_with_stats::<X>(
+            &mut pos,
+            limit,
+            &mut timeman,
+            &DebugMode::off(),
+            true,
+            CancellationToken::new(),
+            tt,
+            hh,
+            eval,
+            params.clone(),
+            &mut stats,
+        );
+        nodes += stats.nodes;
+    }
+
+    (nodes, start.elapsed())
 }
 
+// Every valid score fits into a `MoveScore`, so ordering the root moves by
+// their search scores doesn't lose any mate distances.
+const_assert!(scores::INF_C <= MoveScore::MAX as RawScore && -scores::INF_C >= MoveScore::MIN as RawScore);
+
 #[derive(Debug)]
 struct RootStats {
     mov: ScoredMove,
+    score: AnyScore,
 }
 
 impl RootStats {
     #[inline]
-    fn new(m: Move, score: MoveScore) -> Self { Self { mov: ScoredMove::new(m, score) } }
+    fn new(m: Move) -> Self {
+        Self {
+            mov: ScoredMove::new(m, 0),
+            score: scores::ZERO,
+        }
+    }
 
     #[inline]
     fn mov(&self) -> Move { self.mov.mov() }
@@ -321,10 +501,14 @@ impl RootStats {
     fn scored_move(&self) -> &ScoredMove { &self.mov }
 
     #[inline]
-    fn score(&self) -> MoveScore { self.mov.score() }
+    fn score(&self) -> AnyScore { self.score }
 
     #[inline]
-    fn set_score(&mut self, score: MoveScore) { self.mov.set_score(score); }
+    fn set_score(&mut self, score: AnyScore) {
+        debug_assert!(score.is_valid());
+        self.score = score;
+        self.mov.set_score(score.v() as MoveScore);
+    }
 }
 
 struct Searcher<'a, 'b, E: StaticEvaluator, X: IParams> {
@@ -359,7 +543,7 @@ where
     ) -> Self {
         let mut stats = List::<{ MAX_LEGAL_MOVES }, RootStats>::new();
         _ = fold_moves::<AllLegal, _, _, _>(pos, (), |_, m| {
-            stats.push(RootStats::new(m, 0));
+            stats.push(RootStats::new(m));
             ControlFlow::Continue::<(), ()>(())
         });
 
@@ -393,7 +577,7 @@ where
         let mut root_logits = List::<{ MAX_LEGAL_MOVES }, f32>::new();
         self.root_stats
             .iter()
-            .map(|x| Quality::from(Cp::new(x.score())).v())
+            .map(|x| Quality::from(Cp::from(x.score())).v())
             .collect_into(&mut root_logits);
         root_logits
     }
@@ -473,11 +657,31 @@ where
         }
     }
 
-    fn scorer_for<P: Perspective>(&mut self, tt_move: Move, killers: Killers, phase: TaperValue) -> Scorer<'_, X> {
+    /// The static eval corrected by the eval correction histories.
+    fn corrected_eval<P: Perspective>(&self, pos: &Position, static_eval: Score<P>) -> Score<P> {
+        let corrected = static_eval + self.hh.eval_correction(P::COLOR, correction_keys(pos));
+        corrected.clamp(-Score::MATE_IN_MAX + 1, Score::MATE_IN_MAX - 1)
+    }
+
+    /// The moves that lead to `rel_ply`, one and two plies ago.
+    fn prev_moves(&self, rel_ply: Depth) -> [PieceTo; 2] {
+        let prev = |n: u8| {
+            if rel_ply.v() >= n {
+                self.ss.get(rel_ply - n).moved
+            }
+            else {
+                PieceTo::default()
+            }
+        };
+        [prev(1), prev(2)]
+    }
+
+    fn scorer_for<P: Perspective>(&mut self, tt_move: Move, killers: Killers, prev_moves: [PieceTo; 2], phase: TaperValue) -> Scorer<'_, X> {
         Scorer {
             tt_move,
             killers,
             hh: self.hh,
+            prev_moves,
             color: P::COLOR,
             phase,
             params: self.params.clone(),
@@ -492,18 +696,25 @@ where
         stats: &mut SearchStats,
         depth: Depth,
         mut alpha: Score<P>,
-        beta: Score<P>,
+        mut beta: Score<P>,
     ) -> Score<P> {
         #[cfg(feature = "id-fhr")]
         let threatener = &HceThreatener;
 
         debug_assert!(alpha < beta);
 
+        // an aborted search only unwinds, e.g. past a re-search, without visiting new
+        // nodes
+        if self.aborted {
+            return -Score::INF;
+        }
+
         // incremment stats
         stats.nodes += 1;
 
-        // check if stop is requested or we have reached a limit
-        if stats.nodes.is_multiple_of(4096) && self.should_stop(stats) {
+        // check if stop is requested or we have reached a limit. the node limit is
+        // cheap enough to check at every node, such that it is exact.
+        if (self.limit.is_active() && stats.nodes >= self.limit.nodes) || (stats.nodes.is_multiple_of(4096) && self.should_stop(stats)) {
             self.aborted = true;
             return -Score::INF;
         }
@@ -529,6 +740,25 @@ where
             };
         }
 
+        // mate distance pruning: even mating right away can't beat a shorter mate
+        // that was already found.
+        if T::KIND != NodeKind::Root {
+            alpha = max(alpha, -Score::mate_in(rel_ply));
+            beta = min(beta, Score::mate_in(rel_ply + 1));
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        // the side to move can force a repetition, so this node is worth at least a
+        // draw
+        if T::KIND != NodeKind::Root && alpha < Score::DRAW && pos.has_upcoming_repetition(rel_ply) {
+            alpha = Score::DRAW;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
         // qsearch at the leaf nodes
         if depth == Depth::ROOT || rel_ply >= Depth::MAX {
             return QSearcher::new(pos, self.tt, &mut self.ss, self.root_ply).go::<P, T>(
@@ -544,22 +774,25 @@ where
         let kind = T::KIND;
         let key = pos.get_key();
         let orig_alpha = alpha;
+        let prev_moves = self.prev_moves(rel_ply);
 
         let tt_entry = self.tt.get(key).cloned();
+        let tt_score = tt_entry.as_ref().and_then(|e| e.score.validated()).map(|score| score.from_tt(rel_ply));
 
         // tt-cutoff
         if kind != NodeKind::Root
             // are we in SE verification search?
             && se_excluded_move == Move::null()
             && let Some(ref entry) = tt_entry
+            && let Some(tt_score) = tt_score
             && entry.depth >= depth
             && ((entry.bound == Bound::Exact)
-                || (entry.bound == Bound::Lower && entry.score >= beta.0)
-                || (entry.bound == Bound::Upper && entry.score <= alpha.0))
+                || (entry.bound == Bound::Lower && tt_score >= beta.0)
+                || (entry.bound == Bound::Upper && tt_score <= alpha.0))
         {
             // Safety: unless we've had a hash collision, this score is for the same
             // position and thus for the same player.
-            return unsafe { entry.score.interpret_as() };
+            return unsafe { tt_score.interpret_as() };
         }
 
         // todo: these 'is it already computed? if so, return it.' are not required.
@@ -567,27 +800,27 @@ where
         // features... find a clean way to solve this or make sure the compiler
         // can understand when they will already be computed...
 
-        #[cfg(any(feature = "id-fhr", feature = "id-nmp"))]
         let mut static_eval = Score::NULL;
-        #[cfg(not(any(feature = "id-fhr", feature = "id-nmp")))]
-        let static_eval = Score::<P>::NULL;
 
-        #[cfg(any(feature = "id-fhr", feature = "id-nmp"))]
+        // also needed without any of the pruning, to update the eval correction
+        // at the end of the node.
         let mut lazy_static_eval = |this: &mut Self, pos: &Position| {
-            // is it already computed? if so, return it.
-            if static_eval.0.is_valid() {
-                return static_eval;
+            // is it already computed? if not, compute it.
+            if !static_eval.0.is_valid() {
+                static_eval = tt_entry
+                    .as_ref()
+                    .and_then(|e| e.static_eval.validated())
+                    // Safety: unless we've had a hash collision, this score is for the same position
+                    .map(|e| unsafe { e.interpret_as() })
+                    .unwrap_or_else(|| {
+                        this.eval
+                            .eval(pos.piece_info(), P::COLOR, pos.get_ep_target_square(), phase, EvalKeys::of(pos))
+                    });
             }
 
-            let eval = tt_entry
-                .as_ref()
-                // Safety: unless we've had a hash collision, this score is for the same position
-                .map(|e| unsafe { e.static_eval.interpret_as() })
-                .unwrap_or_else(|| this.eval.eval(pos.piece_info(), P::COLOR, pos.get_ep_target_square(), phase));
-
-            static_eval = eval;
-
-            eval
+            // only the raw eval is stored in the tt, the correction might have changed
+            // since.
+            this.corrected_eval(pos, static_eval)
         };
 
         #[cfg(feature = "id-fhr")]
@@ -611,6 +844,59 @@ where
             score
         };
 
+        // razoring
+        // if the static eval is hopelessly below alpha, only a tactic can save this
+        // node, so let qsearch decide.
+        #[cfg(feature = "id-razor")]
+        {
+            let razor_margin = unsafe { (self.params.razor_margin() * depth.v() as i32).interpret_as() };
+
+            if kind != NodeKind::Root
+                && kind != NodeKind::Pv
+                // are we in SE verification search?
+                && se_excluded_move == Move::null()
+                && depth <= self.params.razor_max_depth()
+                && pos.get_check_state() == CheckState::None
+                && lazy_static_eval(self, pos) + razor_margin < alpha
+            {
+                let score = QSearcher::new(pos, self.tt, &mut self.ss, self.root_ply).go::<P, T>(
+                    pos,
+                    alpha,
+                    alpha + 1,
+                    self.params.clone(),
+                    self.eval,
+                    Depth::MAX - rel_ply,
+                );
+
+                if score <= alpha {
+                    return score;
+                }
+            }
+        }
+
+        // reverse futility pruning
+        // if the static eval beats beta by a depth dependent margin, assume that the
+        // opponent can't catch up anymore.
+        #[cfg(feature = "id-rfp")]
+        {
+            let rfp_margin = unsafe { (self.params.rfp_margin() * depth.v() as i32).interpret_as() };
+
+            if kind != NodeKind::Root
+                && kind != NodeKind::Pv
+                // are we in SE verification search?
+                && se_excluded_move == Move::null()
+                && depth <= self.params.rfp_max_depth()
+                && pos.get_check_state() == CheckState::None
+                // don't return static evals in place of mate scores
+                && beta < Score::MATE_IN_MAX
+            {
+                let eval = lazy_static_eval(self, pos);
+                if eval - rfp_margin >= beta {
+                    return eval;
+                }
+            }
+        }
+
         // null move pruning
         #[cfg(feature = "id-nmp")]
         {
@@ -646,6 +932,7 @@ where
             {
                 let nmp_depth = depth - nmp_r - 1;
 
+                self.ss.get_mut(rel_ply).moved = PieceTo::default();
                 pos.make_null_move();
 
                 let nm_score = !self.search::<P::Opponent, All>(pos, stats, nmp_depth, !beta, !beta + 1);
@@ -665,13 +952,85 @@ where
             }
         }
 
+        // probcut
+        // if a good capture beats beta by a margin in a reduced search, the full depth
+        // search will most likely fail high as well.
+        #[cfg(feature = "id-probcut")]
+        {
+            let probcut_beta = beta + unsafe { self.params.probcut_margin().interpret_as() };
+
+            if kind == NodeKind::Cut
+                // are we in SE verification search?
+                && se_excluded_move == Move::null()
+                && depth >= self.params.probcut_min_depth()
+                && pos.get_check_state() == CheckState::None
+                // don't cut mate scores
+                && probcut_beta < Score::MATE_IN_MAX
+            {
+                let probcut_depth = depth.saturating_sub(self.params.probcut_reduction() + 1);
+                let mut move_picker = MovePicker::new_with_max_stage(Move::null(), Killers::default(), RtStage::YieldGoodCapturesAndPromos);
+
+                while let Some(ScoredMove { mov: m, .. }) =
+                    move_picker.next_with_score_for::<P>(pos, &self.scorer_for::<P>(Move::null(), killers, prev_moves, phase))
+                {
+                    self.ss.get_mut(rel_ply).moved = PieceTo::new(pos.get_piece(m.get_from()), m.get_to());
+                    self.ss.propagate_forward(rel_ply, |s, next_s| next_s.phase = s.phase);
+                    self.eval.forward();
+                    pos.make_move_for::<P>(m, &mut (&mut self.ss.get_mut(rel_ply + 1).phase, self.eval.observe_forward()));
+
+                    // cheap verification with qsearch first
+                    let mut score = !QSearcher::new(pos, self.tt, &mut self.ss, self.root_ply).go::<P::Opponent, All>(
+                        pos,
+                        !probcut_beta,
+                        !(probcut_beta - 1),
+                        self.params.clone(),
+                        self.eval,
+                        Depth::MAX - (rel_ply + 1),
+                    );
+
+                    if score >= probcut_beta && probcut_depth > Depth::ROOT {
+                        score = !self.search::<P::Opponent, All>(pos, stats, probcut_depth, !probcut_beta, !(probcut_beta - 1));
+                    }
+
+                    pos.unmake_move_for::<P>(m, self.eval.observe_backward());
+                    self.eval.backward();
+
+                    if self.aborted {
+                        return -Score::INF;
+                    }
+
+                    if score >= probcut_beta {
+                        // the reduced search proved a lower bound, one ply deeper than it searched the
+                        // move. the cached evals of the node are kept.
+                        self.tt.try_insert(TTEntry {
+                            key,
+                            depth: probcut_depth + 1,
+                            score: score.0.to_tt(rel_ply),
+                            static_eval: tt_entry.as_ref().map_or(scores::NULL, |e| e.static_eval),
+                            #[cfg(feature = "id-fhr")]
+                            threat: tt_entry.as_ref().map_or(scores::NULL, |e| e.threat),
+                            bound: Bound::Lower,
+                            mov: m,
+                        });
+                        return score;
+                    }
+                }
+            }
+        }
+
         // move gen
         let tt_move = tt_entry.as_ref().map(|e| e.mov).unwrap_or(Move::null());
         let mut move_picker = if kind == NodeKind::Root {
             MovePicker::from_scored(self.root_stats.iter().map(|m| m.scored_move()).cloned())
         }
         else {
-            MovePicker::new(tt_move, killers)
+            let counter_move = if rel_ply > Depth::ROOT {
+                self.hh.counter_moves.get(prev_moves[0])
+            }
+            else {
+                Move::null()
+            };
+            MovePicker::new(tt_move, killers).with_counter_move(counter_move)
         };
 
         // fail-high reductions
@@ -703,9 +1062,10 @@ where
         let mut best_move = Move::null();
         let mut curr = 0;
         let mut hh_searched_quiets = MoveList::new();
+        let mut hh_searched_captures = MoveList::new();
 
         // todo: take killers by ref
-        while let Some(sm) = move_picker.next_with_score_for::<P>(pos, &self.scorer_for::<P>(tt_move, killers, phase)) {
+        while let Some(sm) = move_picker.next_with_score_for::<P>(pos, &self.scorer_for::<P>(tt_move, killers, prev_moves, phase)) {
             let ScoredMove { mov: m, score: s } = sm;
 
             if m == se_excluded_move {
@@ -715,6 +1075,40 @@ where
             let (from, to, flag) = m.into();
             let moving_piece = pos.get_piece(from);
             let moving_pt = moving_piece.piece_type();
+            let moved = PieceTo::new(moving_piece, to);
+            let is_quiet = !flag.is_capture() && !flag.is_promo();
+
+            // quiet move pruning, once we have found a move that doesn't get us mated.
+            // pruned moves still count, such that the later moves are reduced by their
+            // position in the move ordering.
+            #[cfg(any(feature = "id-lmp", feature = "id-fp"))]
+            if kind != NodeKind::Root
+                && curr > 0
+                && best_score > -Score::MATE_IN_MAX
+                && !flag.is_capture()
+                && !flag.is_promo()
+                && pos.get_check_state() == CheckState::None
+                && pos.does_check(m) == CheckState::None
+            {
+                // late move pruning
+                // with good move ordering, late quiet moves are very unlikely to be the best.
+                #[cfg(feature = "id-lmp")]
+                if depth <= self.params.lmp_max_depth() && curr >= self.params.lmp_base() as usize + depth.index().pow(2) {
+                    curr += 1;
+                    continue;
+                }
+
+                // futility pruning
+                // a quiet move won't raise the static eval by more than a margin.
+                #[cfg(feature = "id-fp")]
+                if depth <= self.params.fp_max_depth() && {
+                    let fp_margin = self.params.fp_margin() + self.params.fp_depth_margin() * depth.v() as i32;
+                    lazy_static_eval(self, pos) + unsafe { fp_margin.interpret_as() } <= alpha
+                } {
+                    curr += 1;
+                    continue;
+                }
+            }
 
             // singular extensions
             // if all but one move fail low, that move is singular and should be extended.
@@ -723,7 +1117,7 @@ where
                     && curr == 0
                     && depth >= Depth::new(6)
                     && se_excluded_move == Move::null()
-                    && let Some(tt_score) = tt_entry.as_ref().and_then(|e| e.score().validated())
+                    && let Some(tt_score) = tt_score
                     && tt_entry.as_ref().is_some_and(|e| e.depth() >= depth - 3)
                     && tt_entry.as_ref().is_some_and(|e| matches!(e.bound(), Bound::Lower | Bound::Exact))
                 {
@@ -748,6 +1142,7 @@ where
             };
 
             // make the move
+            self.ss.get_mut(rel_ply).moved = moved;
             self.ss.propagate_forward(rel_ply, |s, next_s| next_s.phase = s.phase);
             self.eval.forward();
             pos.make_move_for::<P>(m, &mut (&mut self.ss.get_mut(rel_ply + 1).phase, self.eval.observe_forward()));
@@ -771,6 +1166,12 @@ where
                 // late move reductions
                 if depth >= Depth::new(3) && curr > 1 {
                     r += 5 * lmr_u8(depth.v(), curr as u8) as i32;
+
+                    // reduce quiet moves with a good history less and ones with a bad history more
+                    if is_quiet {
+                        r -= self.hh.quiet_score(&prev_moves, moved) / self.params.lmr_history_divisor();
+                        r = FractionalDepth(r.0.max(0));
+                    }
                 }
 
                 (e, r)
@@ -841,8 +1242,7 @@ where
             if kind == NodeKind::Root {
                 // store the score for the root moves, such that we can use it for sorting in
                 // the next iteration.
-                // todo: don't just clamp, mate values will get lost etc.
-                self.root_stats.as_mut_slice()[curr].set_score(score.0.v().saturating_cast());
+                self.root_stats.as_mut_slice()[curr].set_score(score.0);
             }
 
             if score > best_score {
@@ -861,29 +1261,45 @@ where
                 line.extend_from_slice(1.., cline.as_slice());
 
                 if score >= beta {
+                    let hh_bonus = HistoryScore::new((depth.v() as THistoryScore).pow(2));
+
                     // mark quiet moves, fail-high as killer moves
-                    if !flag.is_capture() && !flag.is_promo() {
+                    if is_quiet {
                         // update killers
                         if m != tt_move {
                             self.ss.get_mut(rel_ply).killers._push(m);
                         }
 
+                        // update the counter move of the previous move
+                        if rel_ply > Depth::ROOT {
+                            self.hh.counter_moves.set(prev_moves[0], m);
+                        }
+
                         // update hh
                         {
-                            let hh_bonus = HistoryScore::new((depth.v() as THistoryScore).pow(2));
-
                             // penalty history heuristic that were expected but
                             // failed to cause a cutoff
                             for &searched_quiet in hh_searched_quiets.as_slice() {
                                 let (from, to, _) = searched_quiet.into();
-                                let moving_pt = pos.get_piece(from).piece_type();
-                                self.hh.update_for::<P>(moving_pt, to, -hh_bonus);
+                                self.hh.update_quiet(&prev_moves, PieceTo::new(pos.get_piece(from), to), -hh_bonus);
                             }
 
                             // reward history heuristic
-                            self.hh.update_for::<P>(moving_pt, to, hh_bonus);
+                            self.hh.update_quiet(&prev_moves, moved, hh_bonus);
                         }
                     }
+                    else {
+                        self.hh.capture.update_for::<P>(moving_pt, to, captured_pt(pos, m), hh_bonus);
+                    }
+
+                    // captures that were searched first but failed to cause a cutoff
+                    for &searched_capture in hh_searched_captures.as_slice() {
+                        let (from, to, _) = searched_capture.into();
+                        let moving_pt = pos.get_piece(from).piece_type();
+                        self.hh
+                            .capture
+                            .update_for::<P>(moving_pt, to, captured_pt(pos, searched_capture), -hh_bonus);
+                    }
 
                     // fail high
                     break;
@@ -897,15 +1313,37 @@ where
 
             // push any move whose statistic can be used to estimate a quiet moves score.
             // that includes killers and the hashmove.
-            if !flag.is_capture() && !flag.is_promo() {
+            if is_quiet {
                 hh_searched_quiets.push(m);
             }
+            else {
+                hh_searched_captures.push(m);
+            }
+        }
+
+        // update the eval correction with how far the static eval was off. captures
+        // and mate scores are not representative for the static eval.
+        if se_excluded_move == Move::null() // are we in SE verification search?
+            && pos.get_check_state() == CheckState::None
+            && !best_move.get_flag().is_capture()
+            && !best_move.get_flag().is_promo()
+            && !best_score.0.is_mate()
+            && !best_score.0.is_mated()
+        {
+            let corrected_eval = lazy_static_eval(self, pos);
+            let bound = Bound::from_scores(orig_alpha, beta, best_score);
+
+            // a bound only tells us something if it is on the other side of the eval
+            if !(bound == Bound::Lower && best_score <= corrected_eval) && !(bound == Bound::Upper && best_score >= corrected_eval) {
+                let diff = (best_score - corrected_eval).0.v();
+                self.hh.update_eval_correction(P::COLOR, correction_keys(pos), diff, depth);
+            }
         }
 
         self.tt.try_insert(TTEntry {
             key,
             depth,
-            score: best_score.0,
+            score: best_score.0.to_tt(rel_ply),
             static_eval: static_eval.0,
             #[cfg(feature = "id-fhr")]
             threat: threat.0,
@@ -917,7 +1355,7 @@ where
     }
 }
 
-pub type HH = PieceHistories;
+pub type HH = Histories;
 
 pub type TT = TranspositionTable<TTEntry, TTReplace>;
 
@@ -1055,6 +1493,8 @@ pub struct SearchEntry {
     pub phase: TaperValue,
     pub line: Box<Line>,
     pub se_excluded_move: Move,
+    /// The move made at this ply, [PieceTo::default] for null moves.
+    pub moved: PieceTo,
 }
 
 pub const trait ScorerParams {
@@ -1065,7 +1505,9 @@ pub const trait ScorerParams {
 pub struct Scorer<'a, X: IParams> {
     pub tt_move: Move,
     pub killers: Killers,
-    pub hh: &'a PieceHistories,
+    pub hh: &'a Histories,
+    /// The moves made one and two plies ago.
+    pub prev_moves: [PieceTo; 2],
     pub color: Color,
     pub phase: TaperValue,
     pub params: X::Ref,
@@ -1084,11 +1526,18 @@ where
                 0
             }
 
-            // captures and promos, ordered by see value.
+            // captures and promos, ordered by see value and capture history.
             RtStage::GenerateCapturesAndPromos | RtStage::YieldGoodCapturesAndPromos | RtStage::YieldBadCaptures => {
                 // todo: currently see evaluates the promo values, but we don't need a whole
                 // see for quiet promos, maybe that can be optimized...
-                ordering::see(pos.piece_info(), mov, self.color)
+                let see = ordering::see(pos.piece_info(), mov, self.color);
+
+                let piece_type = pos.get_piece(mov.get_from()).piece_type();
+                let capt_hist = self.hh.capture.get(self.color, piece_type, mov.get_to(), captured_pt(pos, mov));
+
+                // scaled down, such that the history only breaks ties between similar
+                // exchanges.
+                see + capt_hist.v() / 128
             }
 
             // score killer moves by their age
@@ -1102,6 +1551,9 @@ where
                 -(age as MoveScore)
             }
 
+            // there's only one counter move
+            RtStage::YieldCounterMove => 0,
+
             // score quiet moves by psqt diff or history heuristic
             RtStage::GenerateQuiets | RtStage::YieldQuiets => {
                 let (from, to, flag) = mov.into();
@@ -1109,7 +1561,8 @@ where
                 let piece = pieces.get_piece(from);
                 let piece_type = piece.piece_type();
 
-                let hh_score = self.hh.get(self.color, piece_type, to);
+                // average of the piece history and the continuation histories
+                let hh_score = self.hh.quiet_score(&self.prev_moves, PieceTo::new(piece, to)) / (self.prev_moves.len() as i32 + 1);
                 let psqt_score = ordering::psqt(self.phase, piece_type, from, to, flag, self.color);
 
                 // todo: interpolate by depth?
@@ -1118,7 +1571,7 @@ where
                 let hh_weight = self.params.hh_weight();
                 let total_weight = self.params.total_weight();
 
-                interpolate_i32(psqt_score as i32, hh_score.v() as i32, hh_weight, total_weight) as MoveScore
+                interpolate_i32(psqt_score as i32, hh_score, hh_weight, total_weight) as MoveScore
             }
 
             RtStage::Done => 0,
@@ -1126,6 +1579,17 @@ where
     }
 }
 
+fn correction_keys(pos: &Position) -> CorrectionKeys {
+    CorrectionKeys {
+        pawn: pos.get_pawn_key(),
+        non_pawn: [colors::WHITE, colors::BLACK].map(|c| pos.get_non_pawn_key(c)),
+    }
+}
+
+/// The piece type captured by `mov`, [piece_type::NONE] for quiet promotions.
+#[inline]
+fn captured_pt(pos: &Position, mov: Move) -> PieceType { mov.get_capture_sq().map_or(piece_type::NONE, |sq| pos.get_piece(sq).piece_type()) }
+
 fn uci_score(score: AnyScore) -> UciScore {
     if let Some(mate_score) = score.plies_til_mate() {
         let plies_til_mate = Ply::new(mate_score.v() as u16);
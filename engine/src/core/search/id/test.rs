@@ -2,7 +2,7 @@ use uom::si::{information::mebibyte, u64::Information};
 
 use super::*;
 use crate::{
-    core::{move_iter::sliding_piece::magics, params::C_IdHceParams, search::limit::UciLimit},
+    core::{coordinates::squares::*, move_iter::sliding_piece::magics, params::C_IdHceParams, search::limit::UciLimit},
     math::DefaultLmrParams,
 };
 
@@ -30,7 +30,7 @@ fn run_search(fen: &str, depth: u8) {
         ct,
         &mut tt,
         &mut hh,
-        &mut HceEvaluator,
+        &mut HceEvaluator::default(),
         C_IdHceParams,
     );
 }
@@ -47,3 +47,218 @@ fn no_segfault_in_check() {
     // Single check with many non-resolving pseudo-legal moves.
     run_search("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", 8);
 }
+
+/// Runs the iterative deepening loop like [go] and returns the score of the
+/// last iteration, relative to the side to move.
+fn search_score(fen: &str, depth: u8, tt: &mut TT) -> AnyScore {
+    math::init(DefaultLmrParams);
+    magics::init();
+    zobrist::init();
+
+    let mut pos = Position::from_fen(fen).unwrap();
+    let limit = UciLimit {
+        depth: Depth::new(depth),
+        ..Default::default()
+    };
+    let mut hh = HH::new();
+    let mut eval = HceEvaluator::default();
+    let mut timeman = TimeMan::new(C_IdHceParams);
+    eval.observe_forward().on_init(pos.piece_info());
+
+    let mut searcher = Searcher::<_, C_IdHceParams>::new(&pos, limit, &mut timeman, CancellationToken::new(), tt, &mut hh, &mut eval, C_IdHceParams);
+    let mut stats = SearchStats::default();
+    let mut score = scores::ZERO;
+    for depth in (Depth::ROOT + 1)..=Depth::new(depth) {
+        score = if depth > Depth::new(3) {
+            searcher.aspire_root(&mut pos, &mut stats, depth, score)
+        }
+        else {
+            searcher.search_root(&mut pos, &mut stats, depth)
+        };
+        searcher.sort_root();
+    }
+    score
+}
+
+#[test]
+fn tt_mate_score_round_trip() {
+    let mate = AnyScore::mate_in(Depth::new(7));
+    for score in [mate, -mate, scores::DRAW, AnyScore::new(-123)] {
+        let stored = score.to_tt(Depth::new(4));
+        assert_eq!(stored.from_tt(Depth::new(4)), score);
+    }
+    // seen from the node at ply 4, the mate is 3 plies away
+    assert_eq!(mate.to_tt(Depth::new(4)), AnyScore::mate_in(Depth::new(3)));
+    assert_eq!((-mate).to_tt(Depth::new(4)), -AnyScore::mate_in(Depth::new(3)));
+}
+
+#[test]
+fn exact_mate_scores() {
+    // (fen, plies until mate)
+    let mates = [
+        // 1. Ra8#
+        ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 1),
+        // 1. Rb7 Kf8 2. Ra8#
+        ("6k1/8/8/8/8/8/R7/1R4K1 w - - 0 1", 3),
+        // 1. Ra6 bxa6 2. b7#
+        ("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1", 3),
+        // 1... Kb8 2. Rh8#, from the side that gets mated
+        ("k7/8/1K6/8/8/8/8/7R b - - 0 1", 2),
+    ];
+
+    for (fen, plies) in mates {
+        let hash_size = Information::new::<mebibyte>(16);
+        let mut tt = TT::new_of_size(hash_size);
+
+        // the second search reuses the filled TT, so the mates are found via
+        // transpositions from different plies.
+        for _ in 0..2 {
+            let score = search_score(fen, 8, &mut tt);
+            assert_eq!(score.plies_til_mate(), Some(Depth::new(plies)), "{fen}: {score}");
+            assert_eq!(score.is_mate(), plies % 2 == 1, "{fen}: {score}");
+        }
+    }
+}
+
+#[test]
+fn node_limit_is_exact() {
+    math::init(DefaultLmrParams);
+    magics::init();
+    zobrist::init();
+
+    for nodes in [1000, 4097, 10_000] {
+        let mut pos = Position::start_position();
+        let limit = UciLimit { nodes, ..Default::default() };
+        let mut tt = TT::new_of_size(Information::new::<mebibyte>(16));
+        let mut hh = HH::new();
+        let mut timeman = TimeMan::new(C_IdHceParams);
+        let mut stats = SearchStats::default();
+        let best = go_with_stats::<C_IdHceParams>(
+            &mut pos,
+            limit,
+            &mut timeman,
+            &DebugMode::default(),
+            false,
+            CancellationToken::new(),
+            &mut tt,
+            &mut hh,
+            &mut HceEvaluator::default(),
+            C_IdHceParams,
+            &mut stats,
+        );
+
+        assert!(best.is_some());
+        assert_eq!(stats.nodes, nodes);
+    }
+}
+
+#[test]
+fn eval_correction_is_learned_without_pruning() {
+    math::init(DefaultLmrParams);
+    magics::init();
+    zobrist::init();
+
+    // the static eval is computed for the correction even if none of the pruning
+    // features asked for it.
+    let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+    let mut pos = Position::from_fen(fen).unwrap();
+    let limit = UciLimit {
+        depth: Depth::new(6),
+        ..Default::default()
+    };
+    let mut tt = TT::new_of_size(Information::new::<mebibyte>(16));
+    let mut hh = HH::new();
+    let mut timeman = TimeMan::new(C_IdHceParams);
+    go::<C_IdHceParams>(
+        &mut pos,
+        limit,
+        &mut timeman,
+        &DebugMode::default(),
+        CancellationToken::new(),
+        &mut tt,
+        &mut hh,
+        &mut HceEvaluator::default(),
+        C_IdHceParams,
+    );
+
+    assert_ne!(hh.eval_correction(pos.get_turn(), correction_keys(&pos)), 0);
+}
+
+/// A network that evaluates every position as `eval`, from the side to move.
+fn constant_network(eval: i32) -> Vec<u8> {
+    let mut bytes = vec![0; nnue::Architecture::Shallow.size()];
+    // the accumulator biases, which don't affect the output, keep the network from
+    // being all zero
+    let acc_biases = nnue::INPUT_SIZE * nnue::HIDDEN_SIZE * size_of::<nnue::TValue>();
+    for chunk in bytes[acc_biases..].chunks_exact_mut(size_of::<nnue::TValue>()).take(nnue::HIDDEN_SIZE) {
+        chunk.copy_from_slice(&nnue::TValue::to_ne_bytes(1));
+    }
+    let bias = (eval * i32::from(nnue::QA) * i32::from(nnue::QB) / i32::from(nnue::SCALE)) as nnue::TValue;
+    // the output biases are at the end of the network
+    for chunk in bytes.rchunks_exact_mut(size_of::<nnue::TValue>()).take(nnue::OUTPUT_SIZE) {
+        chunk.copy_from_slice(&bias.to_ne_bytes());
+    }
+    bytes
+}
+
+/// The network file of the shallow network `layers`, with the inputs of the
+/// engine.
+fn pack(layers: &[u8], compression: nnue::format::Compression) -> Vec<u8> {
+    nnue::format::encode(nnue::Architecture::Shallow, nnue::buckets::InputLayout::ENGINE, layers, compression)
+}
+
+#[test]
+fn nnue_evaluators_own_their_network() {
+    magics::init();
+    zobrist::init();
+
+    let pos = Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+    let eval = |evaluator: &mut NnueEvaluator| {
+        let pieces = pos.piece_info();
+        evaluator.observe_forward().on_init(pieces);
+        let phase = TaperValue::from_position(pieces);
+        evaluator
+            .eval::<White>(pieces, pos.get_turn(), pos.get_ep_target_square(), phase, EvalKeys::of(&pos))
+            .0
+    };
+
+    let mut a = NnueEvaluator::new(nnue::load_nnue(&pack(&constant_network(40), nnue::format::Compression::None)).unwrap());
+    let mut b = NnueEvaluator::new(nnue::load_nnue(&pack(&constant_network(-20), nnue::format::Compression::None)).unwrap());
+    assert_eq!(eval(&mut a), AnyScore::new(40));
+    assert_eq!(eval(&mut b), AnyScore::new(-20));
+
+    // loading a network through the config doesn't affect the other evaluators
+    let path = std::env::temp_dir().join(format!("nephrid-{}.nnue", std::process::id()));
+    std::fs::write(&path, pack(&constant_network(10), nnue::format::Compression::Zlib)).unwrap();
+    let mut config = Configuration::builder().build();
+    config.set("nnue-path", path.to_str().unwrap()).unwrap();
+    let mut c = NnueEvaluator::try_from_config(&config).unwrap_or_else(|e| panic!("{e}"));
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(eval(&mut c), AnyScore::new(10));
+    assert_eq!(eval(&mut a), AnyScore::new(40));
+}
+
+#[test]
+fn nnue_trace_removes_each_piece() {
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+    magics::init();
+    zobrist::init();
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let bytes: Vec<u8> = (0..nnue::Architecture::Shallow.size() / size_of::<nnue::TValue>())
+        .flat_map(|_| rng.random_range::<nnue::TValue, _>(-100..=100).to_ne_bytes())
+        .collect();
+    let net = nnue::load_nnue(&pack(&bytes, nnue::format::Compression::None)).unwrap();
+
+    let trace = |fen: &str| NnueEvaluator::<LazyAccUpdates>::new(net.clone()).trace(&Position::from_fen(fen).unwrap());
+    let full = trace("4k3/8/8/3q4/8/8/3R4/4K3 b - - 0 1");
+    let without_queen = trace("4k3/8/8/8/8/8/3R4/4K3 b - - 0 1");
+    let without_rook = trace("4k3/8/8/3q4/8/8/8/4K3 b - - 0 1");
+
+    assert_eq!(full.squares[D5.v() as usize].1, Some(full.eval - without_queen.eval));
+    assert_eq!(full.squares[D2.v() as usize].1, Some(full.eval - without_rook.eval));
+    assert_eq!(full.squares[E1.v() as usize].1, None);
+    assert_eq!(full.squares[A1.v() as usize].1, None);
+}
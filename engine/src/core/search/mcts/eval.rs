@@ -27,6 +27,7 @@ use crate::{
 
 pub mod hce;
 pub mod nn;
+pub mod nnue;
 pub mod playout;
 
 /// Evaluate a node's terminal state. If the node is terminal, return the
@@ -1,4 +1,6 @@
-use std::{convert::Infallible, ops::Deref};
+use std::{cell::RefCell, fmt, ops::Deref};
+
+use uom::si::u64::Information;
 
 use crate::{
     core::{
@@ -7,11 +9,12 @@ use crate::{
         coordinates::EpTargetSquare,
         depth::Depth,
         eval::{
-            self,
-            hce::{self, TaperValue, bishop_pair, hygge_king, king_safety, material, mobility, passed_pawns},
+            self, EvalKeys, endgame,
+            hce::{self, TaperValue},
+            pawns::PawnTable,
         },
         r#move::MAX_LEGAL_MOVES,
-        params::MctsHceParamsRef,
+        params::{CreateParamsError, IParams, MctsHceParams, MctsHceParamsRef},
         position::{CheckState, Position},
         search::{
             id,
@@ -40,32 +43,40 @@ use crate::core::{
     search::mcts::node::node_state::Branching,
 };
 
-struct StaticEvaluator;
+pub struct StaticEvaluator {
+    pawns: PawnTable,
+    params: MctsHceParamsRef,
+}
 
-impl eval::StaticEvaluator for StaticEvaluator {
-    fn eval<P: Perspective>(&mut self, pos: &PieceInfo, turn: Turn, ep_sq: EpTargetSquare, phase: TaperValue) -> Score<P> {
-        fn static_value<P: Perspective>(pos: &PieceInfo, ep_sq: EpTargetSquare, phase: TaperValue, turn: Turn) -> Score<P> {
-            material::<P>(pos)
-                + mobility::<P>(pos, phase)
-                + hce::psqt::<P>(pos, phase)
-                + bishop_pair::<P>(pos)
-                + king_safety::<P>(pos, ep_sq, turn, phase)
-                + passed_pawns::<P>(pos, ep_sq, turn)
-                + hygge_king::<P>(pos, phase)
+impl StaticEvaluator {
+    pub fn new(pawn_hash: Information, params: MctsHceParamsRef) -> Self {
+        Self {
+            pawns: PawnTable::new_of_size(pawn_hash),
+            params,
         }
+    }
+}
 
-        let (ep_w, ep_b) = if P::COLOR == colors::WHITE {
-            (ep_sq, EpTargetSquare::none())
-        }
-        else {
-            (EpTargetSquare::none(), ep_sq)
-        };
-        let w_q = static_value::<P>(pos, ep_w, phase, turn);
-        let b_q = static_value::<P::Opponent>(pos, ep_b, phase, turn);
-        w_q + !b_q
+impl fmt::Debug for StaticEvaluator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticEvaluator")
+            .field("pawns", &self.pawns.size())
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+impl eval::StaticEvaluator for StaticEvaluator {
+    fn eval<P: Perspective>(&mut self, pos: &PieceInfo, turn: Turn, ep_sq: EpTargetSquare, phase: TaperValue, keys: EvalKeys) -> Score<P> {
+        let pawns = self.pawns.probe(pos, keys.pawn, &self.params);
+        let score = hce::eval::<P>(pos, turn, ep_sq, phase, &pawns, &self.params);
+        endgame::adjust::<P>(pos, turn, keys.material, score)
     }
 
-    fn try_from_config<C: Deref<Target = Configuration>>(_: C) -> Result<Self, Infallible> { Ok(Self) }
+    fn try_from_config<C: Deref<Target = Configuration>>(cfg: C) -> Result<Self, impl fmt::Display> {
+        let pawn_hash = cfg.pawn_hash();
+        Ok::<_, CreateParamsError>(Self::new(pawn_hash, MctsHceParams::try_from_config(cfg)?))
+    }
 }
 
 #[derive(Debug, PartialEq, Default)]
@@ -112,7 +123,7 @@ pub struct EvalInfo<Moves: AsRef<[Move]>> {
 }
 
 impl<Moves: AsRef<[Move]>> EvalInfo<Moves> {
-    pub fn new(moves: Moves, pos: &mut Position, params: MctsHceParamsRef) -> Self {
+    pub fn new(moves: Moves, pos: &mut Position, params: MctsHceParamsRef, eval: &mut impl eval::StaticEvaluator) -> Self {
         let phase = TaperValue::from_position(pos.piece_info());
 
         // todo: store tt and ss somewhere
@@ -124,24 +135,10 @@ impl<Moves: AsRef<[Move]>> EvalInfo<Moves> {
 
         let quality: Cp = match pos.get_turn().v() {
             colors::WHITE_C => qsearcher
-                .go::<perspectives::White, node_types::Pv>(
-                    pos,
-                    -Score::INF,
-                    Score::INF,
-                    MctsHceParamsRef::clone(&params),
-                    &mut StaticEvaluator,
-                    Depth::new(30),
-                )
+                .go::<perspectives::White, node_types::Pv>(pos, -Score::INF, Score::INF, MctsHceParamsRef::clone(&params), eval, Depth::new(30))
                 .into(),
             colors::BLACK_C => qsearcher
-                .go::<perspectives::Black, node_types::Pv>(
-                    pos,
-                    -Score::INF,
-                    Score::INF,
-                    MctsHceParamsRef::clone(&params),
-                    &mut StaticEvaluator,
-                    Depth::new(30),
-                )
+                .go::<perspectives::Black, node_types::Pv>(pos, -Score::INF, Score::INF, MctsHceParamsRef::clone(&params), eval, Depth::new(30))
                 .into(),
             _ => unreachable!(),
         };
@@ -160,6 +157,9 @@ impl<Moves: AsRef<[Move]>> EvalInfo<Moves> {
     /// white.
     pub fn quality(&self) -> Quality { Quality::from(self.quality) }
 
+    /// The score of the quieted position, relative to white.
+    pub fn cp(&self) -> Cp { self.quality }
+
     pub fn policy(&self, buf: &mut List<218 /* inlined MAX_LEGAL_MOVES */, f32>) -> Policy {
         let pos = &self.pos;
         let phase = self.phase;
@@ -191,16 +191,19 @@ pub const trait PolicyParams {
     fn policy_temperature(&self) -> f32;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct HceEvaluator {
     policy_buf: Box<List<{ MAX_LEGAL_MOVES }, f32>>,
     params: MctsHceParamsRef,
+    /// Static evaluator of the q-search, shared between the traces.
+    eval: RefCell<StaticEvaluator>,
 }
 
 impl HceEvaluator {
-    pub fn new(params: MctsHceParamsRef) -> Self {
+    pub fn new(params: MctsHceParamsRef, pawn_hash: Information) -> Self {
         Self {
             policy_buf: Box::new(List::new()),
+            eval: RefCell::new(StaticEvaluator::new(pawn_hash, MctsHceParamsRef::clone(&params))),
             params,
         }
     }
@@ -215,6 +218,7 @@ impl Evaluator for HceEvaluator {
                 tree.branches(node).iter().map(|b| b.mov()).collect(),
                 pos,
                 MctsHceParamsRef::clone(&self.params),
+                &mut *self.eval.borrow_mut(),
             )
         })
     }
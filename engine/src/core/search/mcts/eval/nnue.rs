@@ -0,0 +1,85 @@
+use std::{cell::RefCell, sync::Arc};
+
+use crate::{
+    core::{
+        color::colors,
+        eval::{
+            StaticEvaluator,
+            nnue::{self, Network},
+        },
+        r#move::{MAX_LEGAL_MOVES, Move},
+        params::MctsHceParamsRef,
+        position::{PieceInfoObserver, Position},
+        search::{
+            id,
+            mcts::{
+                eval::{Evaluator, Guess, Quality, Value, hce::EvalInfo},
+                node::{
+                    NodeId, Tree,
+                    node_state::{Branching, HasBranches, Valid},
+                },
+                search::{BatchItem, Selection},
+            },
+            score::Cp,
+        },
+    },
+    misc::List,
+};
+
+/// Maps the score of the nnue to a value, through the sigmoid that the
+/// network was trained against.
+pub fn value_of(cp: Cp) -> Value { Value::new(1. / (1. + (-f32::from(cp.v()) / f32::from(nnue::SCALE)).exp())) }
+
+/// Evaluates the leafs with the q-search on top of the nnue, and the policy of
+/// the hand-crafted heuristics.
+pub struct NnueEvaluator {
+    policy_buf: Box<List<{ MAX_LEGAL_MOVES }, f32>>,
+    params: MctsHceParamsRef,
+    /// Static evaluator of the q-search, shared between the traces.
+    eval: RefCell<id::NnueEvaluator>,
+}
+
+impl NnueEvaluator {
+    pub fn new(params: MctsHceParamsRef, net: Arc<Network>) -> Self {
+        Self {
+            policy_buf: Box::new(List::new()),
+            params,
+            eval: RefCell::new(id::NnueEvaluator::new(net)),
+        }
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    type TraceData = Option<EvalInfo<Vec<Move>>>;
+
+    fn trace<S: const Valid + HasBranches>(&self, node: NodeId<S>, tree: &Tree, pos: &mut Position) -> Self::TraceData {
+        node.try_into::<Branching>().map(|node| {
+            let mut eval = self.eval.borrow_mut();
+            // the traces jump around in the tree, so the accumulators start over
+            // from each traced position
+            eval.observe_forward().on_init(pos.piece_info());
+            EvalInfo::new(
+                tree.branches(node).iter().map(|b| b.mov()).collect(),
+                pos,
+                MctsHceParamsRef::clone(&self.params),
+                &mut *eval,
+            )
+        })
+    }
+
+    fn eval_batch(
+        &mut self,
+        _tree: &Tree,
+        _selection: &Selection<Self::TraceData>,
+        leafs: &[&BatchItem<Self::TraceData>],
+    ) -> impl Iterator<Item = Guess> {
+        leafs.iter().filter_map(|&leaf| {
+            let eval_info = leaf.trace.as_ref()?;
+            Some(Guess {
+                relative_to: colors::WHITE,
+                quality: Quality::from(value_of(eval_info.cp())),
+                policy: eval_info.policy(&mut self.policy_buf),
+            })
+        })
+    }
+}
@@ -1,15 +1,19 @@
 use crate::core::{
     color::colors,
     coordinates::squares,
+    eval::nnue,
     r#move::{Move, move_flags},
     move_iter::sliding_piece::magics,
     position::Position,
-    search::mcts::{
-        eval::{self, GameResult},
-        node::{
-            Tree,
-            node_state::{Leaf, Terminal},
+    search::{
+        mcts::{
+            eval::{self, GameResult, Value, nnue::value_of},
+            node::{
+                Tree,
+                node_state::{Leaf, Terminal},
+            },
         },
+        score::Cp,
     },
     zobrist,
 };
@@ -90,3 +94,12 @@ fn three_fold_repetition() {
 
 #[test]
 fn insufficent_material() { test_fen("8/3k4/8/8/3K4/8/8/8 w - - 0 1", Some(GameResult::Draw)); }
+
+#[test]
+fn nnue_value_is_the_sigmoid_of_the_trainer() {
+    assert_eq!(value_of(Cp::new(0)), Value::draw());
+    // one `SCALE` of the network is a logit of one
+    let value = value_of(Cp::new(nnue::SCALE));
+    assert!((value.v() - 1. / (1. + (-1f32).exp())).abs() < 1e-6, "{value}");
+    assert!((value.v() + value_of(Cp::new(-nnue::SCALE)).v() - 1.).abs() < 1e-6);
+}
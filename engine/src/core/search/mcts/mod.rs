@@ -12,10 +12,11 @@ use thiserror::Error;
 use crate::{
     core::{
         config::Configuration,
+        eval::nnue::{self, LoadNnueError, Network},
         r#move::Move,
         position::Position,
         search::mcts::{
-            eval::{Evaluator, hce::HceEvaluator, nn::NNEvaluator, playout::PlayoutEvaluator},
+            eval::{Evaluator, hce::HceEvaluator, nn::NNEvaluator, nnue::NnueEvaluator, playout::PlayoutEvaluator},
             nn::{CheckModelHealthError, LoadNNError, Model},
             node::Tree,
             noise::{DirichletNoiser, Noiser, NullNoiser},
@@ -27,7 +28,8 @@ use crate::{
     misc::CheckHealth,
 };
 
-use std::{path::PathBuf, rc::Rc};
+use std::{path::PathBuf, rc::Rc, sync::Arc};
+use uom::si::u64::Information;
 
 use std::error::Error as StdError;
 
@@ -198,6 +200,7 @@ pub struct HceParts {
     epsilon: Ratio,
     cpuct: f32,
     params: <MctsHceParams as IParams>::Ref,
+    pawn_hash: Information,
 }
 
 impl MctsParts for HceParts {
@@ -207,7 +210,7 @@ impl MctsParts for HceParts {
 
     fn selector(&self) -> Self::Selector { PuctSelector::new(self.cpuct) }
 
-    fn evaluator(&self) -> Self::Evaluator { HceEvaluator::new(MctsHceParamsRef::clone(&self.params)) }
+    fn evaluator(&self) -> Self::Evaluator { HceEvaluator::new(MctsHceParamsRef::clone(&self.params), self.pawn_hash) }
 
     fn noiser(&self) -> Self::Noiser {
         let rng = SmallRng::from_os_rng();
@@ -237,7 +240,7 @@ impl TryFrom<&Configuration> for HceParts {
 
         let cpuct = params.select_cpuct();
 
-        Ok(Self::new(alpha, epsilon, cpuct, params))
+        Ok(Self::new(alpha, epsilon, cpuct, params, config.pawn_hash()))
     }
 }
 
@@ -254,7 +257,82 @@ impl Default for HceParts {
 }
 
 impl HceParts {
-    pub fn new(alpha: f32, epsilon: Ratio, cpuct: f32, params: <MctsHceParams as IParams>::Ref) -> Self { Self { alpha, epsilon, cpuct, params } }
+    pub fn new(alpha: f32, epsilon: Ratio, cpuct: f32, params: <MctsHceParams as IParams>::Ref, pawn_hash: Information) -> Self {
+        Self {
+            alpha,
+            epsilon,
+            cpuct,
+            params,
+            pawn_hash,
+        }
+    }
+}
+
+/// Mcts parts for mcts with puct + nnue analysis.
+pub struct NnueParts {
+    alpha: f32,
+    epsilon: Ratio,
+    cpuct: f32,
+    params: <MctsHceParams as IParams>::Ref,
+    net: Arc<Network>,
+}
+
+impl MctsParts for NnueParts {
+    type Selector = PuctSelector;
+    type Evaluator = NnueEvaluator;
+    type Noiser = DirichletNoiser;
+
+    fn selector(&self) -> Self::Selector { PuctSelector::new(self.cpuct) }
+
+    fn evaluator(&self) -> Self::Evaluator { NnueEvaluator::new(MctsHceParamsRef::clone(&self.params), self.net.clone()) }
+
+    fn noiser(&self) -> Self::Noiser {
+        let rng = SmallRng::from_os_rng();
+        DirichletNoiser::new(self.alpha, self.epsilon, rng)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CreateNnuePartsError {
+    #[error("Unhealthy epsilon: {0}")]
+    BadEpsilon(String),
+
+    #[error("Error while creating evaluator params: {0}")]
+    EvalParams(#[from] CreateParamsError),
+
+    #[error("Invalid nnue: {0}")]
+    Nnue(#[from] LoadNnueError),
+}
+
+impl TryFrom<&Configuration> for NnueParts {
+    type Error = CreateNnuePartsError;
+
+    fn try_from(config: &Configuration) -> Result<Self, Self::Error> {
+        let alpha = config.dirichlet_alpha();
+
+        let epsilon = Ratio::new(config.dirichlet_epsilon());
+        epsilon.check_health().map_err(Self::Error::BadEpsilon)?;
+
+        let params = MctsHceParams::try_from_config(config)?;
+
+        let cpuct = params.select_cpuct();
+
+        let net = nnue::nnue_from_path(config.nnue_path())?;
+
+        Ok(Self::new(alpha, epsilon, cpuct, params, net))
+    }
+}
+
+impl NnueParts {
+    pub fn new(alpha: f32, epsilon: Ratio, cpuct: f32, params: <MctsHceParams as IParams>::Ref, net: Arc<Network>) -> Self {
+        Self {
+            alpha,
+            epsilon,
+            cpuct,
+            params,
+            net,
+        }
+    }
 }
 
 /// Mcts parts for pure mcts.
@@ -1,15 +1,30 @@
-use crate::core::{
-    config::Configuration,
-    move_iter::sliding_piece::magics,
-    params::C_MctsHceParams,
-    position::Position,
-    search::mcts::{HceParts, MctsParts, NullNoiser, node::Tree, search::TreeSearcher, select::ucb::UcbSelector, test::DummyEvaluator},
-    zobrist,
+use crate::{
+    core::{
+        config::Configuration,
+        eval::nnue::test::random_network,
+        move_iter::sliding_piece::magics,
+        params::{C_MctsHceParams, IParams, MctsHceParams},
+        position::Position,
+        search::mcts::{
+            HceParts, MctsParts, NnueParts, NullNoiser,
+            node::Tree,
+            search::TreeSearcher,
+            select::{puct::PuctParams, ucb::UcbSelector},
+            test::DummyEvaluator,
+        },
+        zobrist,
+    },
+    math::Ratio,
 };
+use rand::{SeedableRng, rngs::SmallRng};
 
-use std::{error::Error, thread};
+use std::{error::Error, sync::Arc, thread};
 
-fn fuzz<const X: usize, P: MctsParts + Default>(pos: &'static str, rounds: usize) {
+fn fuzz<const X: usize, P: MctsParts + Default + 'static>(pos: &'static str, rounds: usize) { fuzz_with::<X, P>(pos, rounds, P::default) }
+
+/// Fuzzes the tree search with the parts made by `parts`, on the thread of the
+/// search.
+fn fuzz_with<const X: usize, P: MctsParts + 'static>(pos: &'static str, rounds: usize, parts: impl FnOnce() -> P + Send + 'static) {
     magics::init();
     zobrist::init();
 
@@ -22,7 +37,7 @@ fn fuzz<const X: usize, P: MctsParts + Default>(pos: &'static str, rounds: usize
 
             let mut pos_clone = pos.clone();
 
-            let parts = P::default();
+            let parts = parts();
 
             let mut searcher = TreeSearcher::<X, _, _, _, C_MctsHceParams>::new(
                 &mut pos_clone,
@@ -74,6 +89,28 @@ pub fn sa_fuzz_bs_64() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test]
+pub fn nnue_fuzz_bs_8() -> Result<(), Box<dyn Error>> {
+    let net = Arc::new(random_network(&mut SmallRng::seed_from_u64(0)));
+    fuzz_with::<8, _>("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 5_000, move || {
+        let config = Configuration::builder()
+            .qsearch(&C_MctsHceParams)
+            .policy(&C_MctsHceParams)
+            .puct(&C_MctsHceParams)
+            .mcts(&C_MctsHceParams)
+            .build();
+        let params = MctsHceParams::try_from_config(&config).unwrap();
+        NnueParts::new(
+            config.dirichlet_alpha(),
+            Ratio::new(config.dirichlet_epsilon()),
+            params.select_cpuct(),
+            params,
+            net,
+        )
+    });
+    Ok(())
+}
+
 #[derive(Default)]
 struct NoAnalysisParts;
 
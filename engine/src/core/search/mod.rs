@@ -7,7 +7,7 @@ use crate::{
             id::{IdParams, ScorerParams},
             mcts::search::MctsParams,
             quiesce::QSearchParams,
-            score::Cp,
+            score::{AnyScore, Cp},
         },
     },
     math::{self, LmrParams},
@@ -18,6 +18,7 @@ use crate::{
     core::{
         Game, Move,
         config::Configuration,
+        depth::Depth,
         move_iter::opt,
         search::{
             limit::UciLimit,
@@ -151,6 +152,12 @@ where
 
                 Ok(())
             }
+            Command::Bench(depth) => {
+                let (nodes, time) = id::bench::<X>(depth, &mut self.tt, &mut self.hh, &mut self.eval, self.params.clone());
+                let nps = nodes * 1000 / (time.as_millis() as u64).max(1);
+                println!("{nodes} nodes {nps} nps");
+                Ok(())
+            }
             Command::Ponder(_pos, _limit, _ct, _dbg, _ponder) => {
                 todo!()
             }
@@ -189,6 +196,29 @@ where
     }
 }
 
+impl<E: StaticEvaluator + Default, X: IParams + Default> IdWorker<E, X>
+where
+    X::Ref: IdParams + LmrParams + ChronoParams + QSearchParams + ScorerParams + fmt::Debug,
+{
+    /// Searches `pos` like [Command::Normal], but without reporting to the
+    /// gui. Returns the best move and its score, relative to the side to move.
+    pub fn search(&mut self, pos: &mut Position, limit: UciLimit) -> Option<(Move, AnyScore)> {
+        self.eval.init(pos.piece_info());
+        self.timeman.init_limits(&limit, pos);
+
+        id::search::<X>(
+            pos,
+            limit,
+            &mut self.timeman,
+            CancellationToken::new(),
+            &mut self.tt,
+            &mut self.hh,
+            &mut self.eval,
+            self.params.clone(),
+        )
+    }
+}
+
 /// Monte Carlo Tree Search worker.
 pub struct MctsWorker<const MPV: usize, C: MctsConfig, X: IParams> {
     mcts_parts: Option<C::Parts>,
@@ -239,6 +269,7 @@ where
                 }
                 Ok(())
             }
+            Command::Bench(_) => Err(ExecError::RuntimeError("bench is only supported by the id search".to_string())),
             Command::Normal(mut pos, limit, ct, debug) => {
                 let parts = self.mcts_parts.as_ref().ok_or(ExecError::UninitState())?;
                 let state = &mut self.mcts_state;
@@ -375,6 +406,7 @@ pub fn init<W: SearchWorker>(default_config: Arc<Mutex<Configuration>>) -> Searc
 #[derive(Debug, Clone)]
 pub enum Command {
     Perft(Position, UciLimit, CancellationToken, DebugMode, bool),
+    Bench(Depth),
     Normal(Position, UciLimit, CancellationToken, DebugMode),
     Ponder(Position, UciLimit, CancellationToken, DebugMode, PonderToken),
     AdvanceState(Move),
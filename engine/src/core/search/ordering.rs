@@ -185,6 +185,13 @@ impl MovePicker {
         }
     }
 
+    /// Yields `counter_move` right after the killers, if it is a legal quiet
+    /// move in the position.
+    pub fn with_counter_move(mut self, counter_move: Move) -> Self {
+        self.move_gen.counter_move = counter_move;
+        self
+    }
+
     pub fn next(&mut self, pos: &Position, scorer: &impl MoveScorer) -> Option<Move> {
         match pos.get_turn() {
             colors::WHITE => self.next_for::<perspectives::White>(pos, scorer),
@@ -276,6 +283,7 @@ pub enum RtStage {
     GenerateCapturesAndPromos,
     YieldGoodCapturesAndPromos,
     YieldKillers,
+    YieldCounterMove,
     YieldBadCaptures,
     GenerateQuiets,
     YieldQuiets,
@@ -292,10 +300,11 @@ impl TryFrom<u8> for RtStage {
             1 => Ok(Self::GenerateCapturesAndPromos),
             2 => Ok(Self::YieldGoodCapturesAndPromos),
             3 => Ok(Self::YieldKillers),
-            4 => Ok(Self::YieldBadCaptures),
-            5 => Ok(Self::GenerateQuiets),
-            6 => Ok(Self::YieldQuiets),
-            7 => Ok(Self::Done),
+            4 => Ok(Self::YieldCounterMove),
+            5 => Ok(Self::YieldBadCaptures),
+            6 => Ok(Self::GenerateQuiets),
+            7 => Ok(Self::YieldQuiets),
+            8 => Ok(Self::Done),
             _ => Err(()),
         }
     }
@@ -349,6 +358,11 @@ pub mod stages {
         fn stage() -> RtStage { RtStage::YieldKillers }
     }
 
+    pub struct YieldCounterMove;
+    impl Stage for YieldCounterMove {
+        fn stage() -> RtStage { RtStage::YieldCounterMove }
+    }
+
     pub struct YieldBadCaptures;
     impl Stage for YieldBadCaptures {
         fn stage() -> RtStage { RtStage::YieldBadCaptures }
@@ -385,6 +399,7 @@ pub struct MoveGenerator {
     stage: RtStage,
     hash_move: Move,
     killers: id::Killers,
+    counter_move: Move,
     buf: List<{ MAX_MOVES }, ScoredMove>,
     start_good_capt_and_promos: usize,
     num_good_capt_and_promos: usize,
@@ -401,6 +416,7 @@ impl MoveGenerator {
             stage: RtStage::YieldHashMove,
             hash_move,
             killers,
+            counter_move: Move::null(),
             start_good_capt_and_promos: 0,
             num_good_capt_and_promos: 0,
             num_capt_and_promos: 0,
@@ -414,6 +430,7 @@ impl MoveGenerator {
             stage,
             hash_move: Move::null(),
             killers: id::Killers::default(),
+            counter_move: Move::null(),
             start_good_capt_and_promos: 0,
             num_good_capt_and_promos: 0,
             num_capt_and_promos: 0,
@@ -427,7 +444,9 @@ impl MoveGenerator {
     fn generate_moves<const HASH: bool, const KILLERS: bool, P: Perspective, O: move_iter::Options>(&mut self, pos: &Position) {
         _ = fold_moves_for::<P, O, _, _, _>(pos, (), |_, m| {
             let is_hash = HASH && m == self.hash_move;
-            let is_killer = KILLERS && self.killers._position(&m).is_some();
+            // the counter move is yielded right after the killers, so it's skipped here as
+            // well.
+            let is_killer = KILLERS && (self.killers._position(&m).is_some() || m == self.counter_move);
 
             if !is_hash && !is_killer {
                 self.buf.push(ScoredMove::new(m, 0));
@@ -460,7 +479,7 @@ impl MoveGenerator {
                 let start = self.buf.len();
 
                 let has_hashmove = self.hash_move != Move::null();
-                let has_killers = !self.killers._is_empty();
+                let has_killers = !self.killers._is_empty() || self.counter_move != Move::null();
                 type Opts = GenerateCapturesAndPromos;
                 match (has_hashmove, has_killers) {
                     (true, true) => self.generate_moves::<true, true, P, Opts>(pos),
@@ -524,6 +543,25 @@ impl MoveGenerator {
                 self.stage.next();
                 Ok(start..end)
             }
+            RtStage::YieldCounterMove => {
+                let start = self.buf.len();
+                let counter = self.counter_move;
+
+                if counter != Move::null()
+                    && counter != self.hash_move
+                    && self.killers._position(&counter).is_none()
+                    && pos.is_pseudo_legal_for::<P>(counter)
+                    && (SKIP_LEGALITY_CHECK || pos.is_legal_for::<P>(counter))
+                {
+                    let s = scorer.score::<stages::YieldCounterMove>(pos, counter);
+                    self.buf.push(ScoredMove::new(counter, s));
+                }
+
+                let end = self.buf.len();
+
+                self.stage.next();
+                Ok(start..end)
+            }
             RtStage::YieldBadCaptures => {
                 let num = self.num_capt_and_promos;
 
@@ -542,7 +580,7 @@ impl MoveGenerator {
                 let start = self.buf.len();
 
                 let has_hashmove = self.hash_move != Move::null();
-                let has_killers = !self.killers._is_empty();
+                let has_killers = !self.killers._is_empty() || self.counter_move != Move::null();
                 type Opts = GenerateQuiets;
                 match (has_hashmove, has_killers) {
                     (true, true) => self.generate_moves::<true, true, P, Opts>(pos),
@@ -629,7 +667,7 @@ pub mod test {
     #[test]
     fn see_undefended_capture() {
         // White knight takes undefended black pawn on d5
-        let fen = "8/8/8/3p4/4N3/8/8/8 w - - 0 1";
+        let fen = "k7/8/8/3p4/4N3/8/8/7K w - - 0 1";
         let mov = Move::new(squares::E4, squares::D5, move_flags::CAPTURE);
 
         // Expected: Gains the pawn
@@ -639,7 +677,7 @@ pub mod test {
     #[test]
     fn see_equal_trade() {
         // White pawn takes black pawn on d5, black recaptures
-        let fen = "8/8/4p3/3p4/4P3/8/8/8 w - - 0 1";
+        let fen = "k7/8/4p3/3p4/4P3/8/8/7K w - - 0 1";
         let mov = Move::new(squares::E4, squares::D5, move_flags::CAPTURE);
 
         // Expected: +100 for PxP, but opponent recaptures for -100. Net is 0.
@@ -654,7 +692,7 @@ pub mod test {
     #[test]
     fn see_losing_capture() {
         // White queen takes defended black pawn on d5
-        let fen = "8/8/4p3/3p4/4Q3/8/8/8 w - - 0 1";
+        let fen = "7k/8/4p3/3p4/4Q3/8/8/K7 w - - 0 1";
         let mov = Move::new(squares::E4, squares::D5, move_flags::CAPTURE);
 
         // Expected: QxP (+100). Black plays PxQ (-800). Net: -700.
@@ -688,7 +726,7 @@ pub mod test {
     #[test]
     fn see_en_passant() {
         // White pawn on e5 captures d5 pawn en passant
-        let fen = "8/8/8/3pP3/8/8/8/8 w - d6 0 1";
+        let fen = "k7/8/8/3pP3/8/8/8/7K w - d6 0 1";
         // Ensure you pass the EN_PASSANT flag so your code knows it's an EP move!
         let mov = Move::new(squares::E5, squares::D6, move_flags::EN_PASSANT);
 
@@ -700,7 +738,7 @@ pub mod test {
     fn see_capture_promotion() {
         // White pawn on e7 captures Black Rook on d8 and promotes to Queen.
         // Black has a Rook on c8 ready to recapture the new Queen.
-        let fen = "2rr4/4P3/8/8/8/8/8/8 w - - 0 1";
+        let fen = "2rr4/4P3/7k/8/8/8/8/K7 w - - 0 1";
         // Pass the promotion-capture flag (e.g., PROMO_QUEEN_CAPTURE)
         let mov = Move::new(squares::E7, squares::D8, move_flags::CAPTURE_PROMOTION_QUEEN);
 
@@ -716,15 +754,50 @@ pub mod test {
         );
     }
 
+    #[test]
+    fn counter_move_follows_killers() {
+        magics::init();
+        zobrist::init();
+
+        let pos = Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+
+        let killer = Move::new(squares::G1, squares::F3, move_flags::QUIET);
+        let counter_move = Move::new(squares::B1, squares::C3, move_flags::QUIET);
+        let mut killers = Killers::new();
+        killers.push(killer);
+
+        let hh = id::HH::new();
+        let scorer = id::Scorer::<C_IdNnueParams> {
+            tt_move: Move::null(),
+            killers,
+            hh: &hh,
+            prev_moves: Default::default(),
+            color: colors::WHITE,
+            phase: TaperValue::from_position(pos.piece_info()),
+            params: C_IdNnueParams,
+        };
+
+        let mut picker = MovePicker::new(Move::null(), killers).with_counter_move(counter_move);
+        let mut moves = MoveList::new();
+        while let Some(m) = picker.next(&pos, &scorer) {
+            moves.push(m);
+        }
+
+        assert_eq!(moves.len(), 20);
+        assert_eq!(&moves.as_slice()[..2], &[killer, counter_move]);
+        assert_eq!(moves.iter().filter(|&&m| m == counter_move).count(), 1);
+    }
+
     fn test_does_pick_all_legal_moves(fen: &str, depth: Depth) {
         math::init(DefaultLmrParams);
         magics::init();
         zobrist::init();
 
         let mut pos = Position::from_fen(fen).unwrap();
-        recurse_test(&mut pos, &mut SmallRng::seed_from_u64(0), depth);
+        let hh = id::HH::new();
+        recurse_test(&mut pos, &mut SmallRng::seed_from_u64(0), &hh, depth);
 
-        fn recurse_test(pos: &mut Position, rng: &mut SmallRng, depth: Depth) -> u64 {
+        fn recurse_test(pos: &mut Position, rng: &mut SmallRng, hh: &id::HH, depth: Depth) -> u64 {
             if depth == Depth::new(0) {
                 return 1;
             }
@@ -744,14 +817,16 @@ pub mod test {
             killers.push(get_killer());
             killers.push(get_killer());
 
-            let mut hh = id::HH::new();
+            // may collide with the hash move or the killers, which must not yield it twice
+            let counter_move = *all_moves.as_slice().choose(rng).unwrap_or(&Move::null());
 
-            let mut picker = MovePicker::new(hash_move, killers);
+            let mut picker = MovePicker::new(hash_move, killers).with_counter_move(counter_move);
 
             let scorer = id::Scorer::<C_IdNnueParams> {
                 tt_move: hash_move,
                 killers,
-                hh: &mut hh,
+                hh,
+                prev_moves: Default::default(),
                 color: pos.get_turn(),
                 phase: TaperValue::from_position(pos.piece_info()),
                 params: C_IdNnueParams,
@@ -767,7 +842,7 @@ pub mod test {
                 got.push(mov);
                 cnt += 1;
                 pos.make_move(mov, &mut ());
-                recurse_test(pos, rng, depth - 1);
+                recurse_test(pos, rng, hh, depth - 1);
                 pos.unmake_move(mov, &mut ());
             }
 
@@ -2,10 +2,11 @@ use crate::core::{
     color::{Color, Perspective},
     depth::Depth,
     eval::{
-        StaticEvaluator,
+        EvalKeys, StaticEvaluator,
         hce::{TaperValue, piece_score, tapered_psqt},
     },
-    r#move::Move,
+    r#move::{Move, MoveList},
+    move_iter::opt::QuietChecks,
     piece::{PromoPieceType, piece_type},
     ply::Ply,
     position::{CheckState, Position},
@@ -13,15 +14,21 @@ use crate::core::{
         data::{ReplacementStrategy, TTBound, TTDepth, TTKey, TTMove, TTScore, TTStaticEval, TranspositionTable},
         id::{self, Bound},
         ordering::{self, MovePicker, MoveScore, RtStage, Stage},
-        score::{AnyScore, Score, scores},
+        score::{AnyScore, RawScore, Score, scores},
         tree::NodeType,
     },
     zobrist,
 };
 
+#[cfg(test)] mod test;
+
 pub const trait QSearchParams {
     fn futility_margin(&self) -> AnyScore;
     fn delta_pruning_threshold(&self) -> TaperValue;
+    /// Captures whose SEE is below `-see_margin` are pruned.
+    fn see_margin(&self) -> AnyScore;
+    /// Number of q-search plies in which quiet checks are searched as well.
+    fn quiet_check_depth(&self) -> Depth;
 }
 
 pub type TT<Data, Strat> = TranspositionTable<Data, Strat>;
@@ -46,6 +53,20 @@ impl<'a, E: From<TTEntry> + TTKey + TTBound + TTScore + TTMove + TTDepth + TTSta
     QSearcher<'a, E, R>
 {
     pub fn go<P: Perspective, T: NodeType>(
+        &mut self,
+        pos: &mut Position,
+        alpha: Score<P>,
+        beta: Score<P>,
+        params: impl QSearchParams + Clone,
+        eval: &mut impl StaticEvaluator,
+        depth: Depth,
+    ) -> Score<P> {
+        self.search::<P, T>(pos, alpha, beta, params, eval, depth, Depth::ROOT)
+    }
+
+    /// `qs_ply`: The number of plies since entering the q-search.
+    #[allow(clippy::too_many_arguments)]
+    fn search<P: Perspective, T: NodeType>(
         &mut self,
         pos: &mut Position,
         mut alpha: Score<P>,
@@ -53,6 +74,7 @@ impl<'a, E: From<TTEntry> + TTKey + TTBound + TTScore + TTMove + TTDepth + TTSta
         params: impl QSearchParams + Clone,
         eval: &mut impl StaticEvaluator,
         depth: Depth,
+        qs_ply: Depth,
     ) -> Score<P> {
         let mut best_score = -Score::INF;
 
@@ -61,6 +83,14 @@ impl<'a, E: From<TTEntry> + TTKey + TTBound + TTScore + TTMove + TTDepth + TTSta
         let rel_ply: Depth = (pos.ply() - self.root_ply).into();
         let &id::SearchEntry { phase, .. } = self.ss.get(rel_ply);
 
+        // the side to move can force a repetition
+        if alpha < Score::DRAW && pos.has_upcoming_repetition(rel_ply) {
+            alpha = Score::DRAW;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
         let mut static_eval = Score::<P>::NULL;
         let mut lazy_static_eval = |this: &mut Self, pos: &Position| {
             // is it already computed? if so, return it.
@@ -76,13 +106,13 @@ impl<'a, E: From<TTEntry> + TTKey + TTBound + TTScore + TTMove + TTDepth + TTSta
                     unsafe { score.interpret_as() }
                 }
                 else {
-                    let score = eval.eval(pos.piece_info(), P::COLOR, pos.get_ep_target_square(), phase);
+                    let score = eval.eval(pos.piece_info(), P::COLOR, pos.get_ep_target_square(), phase, EvalKeys::of(pos));
                     *score_ref = score.0;
                     score
                 }
             }
             else {
-                eval.eval(pos.piece_info(), P::COLOR, pos.get_ep_target_square(), phase)
+                eval.eval(pos.piece_info(), P::COLOR, pos.get_ep_target_square(), phase, EvalKeys::of(pos))
             };
 
             static_eval
@@ -143,7 +173,7 @@ impl<'a, E: From<TTEntry> + TTKey + TTBound + TTScore + TTMove + TTDepth + TTSta
             hash_move,
             // todo: killers if were in check (looking at quiets)?
             id::Killers::default(),
-            // if in check, we only want to search captures and promos, otherwise we want to search all moves.
+            // if in check, we have to search all evasions, otherwise we only want to search captures and promos.
             if in_check {
                 RtStage::Done
             }
@@ -152,14 +182,41 @@ impl<'a, E: From<TTEntry> + TTKey + TTBound + TTScore + TTMove + TTDepth + TTSta
             },
         );
 
+        // after the captures, quiet checks are searched in the first plies of the
+        // q-search, such that we can find mates that start with a quiet check.
+        let try_quiet_checks = !in_check && qs_ply < params.quiet_check_depth();
+        let mut quiet_checks: Option<MoveList> = None;
+        let mut next_quiet_check = 0;
+
         // recurse
         let mut best_move = Move::null();
         let mut num_legal_moves = 0;
-        while let Some(m) = move_picker.next_for::<P>(pos, &scorer) {
+        loop {
+            let m = match move_picker.next_for::<P>(pos, &scorer) {
+                Some(m) => m,
+                None if try_quiet_checks => {
+                    let checks = quiet_checks.get_or_insert_with(|| pos.collect_moves_for::<P, QuietChecks, _>(MoveList::new()));
+                    let Some(&m) = checks.as_slice().get(next_quiet_check)
+                    else {
+                        break;
+                    };
+                    next_quiet_check += 1;
+                    m
+                }
+                None => break,
+            };
+            let is_quiet_check = quiet_checks.is_some();
+
             num_legal_moves += 1;
 
+            // see pruning
+            // don't bother searching captures that lose material.
+            if !in_check && !is_quiet_check && (ordering::see(pos.piece_info(), m, P::COLOR) as RawScore) < -params.see_margin().v() {
+                continue;
+            }
+
             // delta pruning
-            if !in_check && phase < params.delta_pruning_threshold() {
+            if !in_check && !is_quiet_check && phase < params.delta_pruning_threshold() {
                 let (from, to, flag) = m.into();
 
                 let move_gain: Score<P> = {
@@ -199,7 +256,7 @@ impl<'a, E: From<TTEntry> + TTKey + TTBound + TTScore + TTMove + TTDepth + TTSta
             eval.forward();
             pos.make_move_for::<P>(m, &mut (self.ss.get_mut(rel_ply + 1).phase, eval.observe_forward()));
 
-            let score = !self.go::<P::Opponent, T>(pos, !beta, !alpha, params.clone(), eval, depth - 1);
+            let score = !self.search::<P::Opponent, T>(pos, !beta, !alpha, params.clone(), eval, depth - 1, qs_ply + 1);
 
             pos.unmake_move_for::<P>(m, eval.observe_backward());
             eval.backward();
@@ -221,7 +278,7 @@ impl<'a, E: From<TTEntry> + TTKey + TTBound + TTScore + TTMove + TTDepth + TTSta
         self.tt.try_insert(TTEntry {
             key,
             depth: Depth::NONE,
-            score: best_score.0,
+            score: best_score.0.to_tt(rel_ply),
             static_eval: static_eval.0,
             bound: Bound::None, // Bound::from_scores(beta - 1, beta, best_score),
             mov: best_move,
@@ -293,7 +350,9 @@ impl ordering::MoveScorer for MoveScorer {
 
                 ordering::see(pieces, mov, self.color) + ordering::psqt(self.phase, pt, from, to, flag, self.color)
             }
-            ordering::RtStage::YieldKillers => todo!("we don't yet have killers in qsearch"),
+            // the picker gets neither killers nor a counter move, so these stages yield no
+            // moves.
+            ordering::RtStage::YieldKillers | ordering::RtStage::YieldCounterMove => 0,
             ordering::RtStage::GenerateQuiets | ordering::RtStage::YieldQuiets => {
                 debug_assert!(
                     pos.get_check_state() != CheckState::None,
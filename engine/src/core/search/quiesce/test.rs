@@ -0,0 +1,57 @@
+use super::*;
+use crate::core::{
+    color::perspectives::White,
+    move_iter::sliding_piece::magics,
+    params::C_IdHceParams,
+    position::PieceInfoObserver,
+    search::{id::HceEvaluator, tree::node_types::Pv},
+};
+
+/// [C_IdHceParams] without quiet checks.
+#[derive(Clone)]
+struct NoQuietChecks;
+
+impl QSearchParams for NoQuietChecks {
+    fn futility_margin(&self) -> AnyScore { C_IdHceParams.futility_margin() }
+    fn delta_pruning_threshold(&self) -> TaperValue { C_IdHceParams.delta_pruning_threshold() }
+    fn see_margin(&self) -> AnyScore { C_IdHceParams.see_margin() }
+    fn quiet_check_depth(&self) -> Depth { Depth::ROOT }
+}
+
+fn qsearch(fen: &str, params: impl QSearchParams + Clone) -> Score<White> {
+    magics::init();
+    zobrist::init();
+
+    let mut pos = Position::from_fen(fen).unwrap();
+    let mut tt = id::TT::new(1024);
+    let mut ss = id::SS::from(vec![id::SearchEntry {
+        phase: TaperValue::from_position(pos.piece_info()),
+        ..Default::default()
+    }]);
+    let mut eval = HceEvaluator::default();
+    eval.observe_forward().on_init(pos.piece_info());
+
+    let root_ply = pos.ply();
+    QSearcher::new(&pos, &mut tt, &mut ss, root_ply).go::<White, Pv>(&mut pos, -Score::INF, Score::INF, params, &mut eval, Depth::new(30))
+}
+
+#[test]
+fn quiet_check_mate_in_one() {
+    // Ra8# is a quiet move.
+    let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+
+    assert_eq!(qsearch(fen, C_IdHceParams), Score::mate_in(Depth::new(1)));
+    assert!(qsearch(fen, NoQuietChecks) < Score::MATE_IN_MAX);
+}
+
+#[test]
+fn evasions_detect_mate() {
+    // white is mated, there are no evasions.
+    assert_eq!(
+        qsearch("6k1/5ppp/8/8/8/8/5PPP/r5K1 w - - 0 1", NoQuietChecks),
+        -Score::mate_in(Depth::ROOT)
+    );
+
+    // white is in check, but can capture the checking rook.
+    assert!(qsearch("6k1/5ppp/8/8/8/8/R4PPP/r5K1 w - - 0 1", NoQuietChecks) > -Score::MATE_IN_MAX);
+}
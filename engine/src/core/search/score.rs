@@ -68,6 +68,42 @@ impl AnyScore {
     #[inline(always)]
     pub const fn mate_in(rel_ply: Depth) -> Self { scores::MATE_IN_0 - rel_ply.v() as i32 }
 
+    #[inline(always)]
+    pub const fn is_mate(&self) -> bool { self.v >= scores::MATE_IN_MAX_C && self.v < scores::INF_C }
+
+    #[inline(always)]
+    pub const fn is_mated(&self) -> bool { self.v <= -scores::MATE_IN_MAX_C && self.v > -scores::INF_C }
+
+    /// Mate scores are relative to the root. Convert them to be relative to the
+    /// node at `rel_ply` before storing them in the transposition table, so
+    /// they stay correct when the position is reached via another path.
+    #[inline]
+    pub const fn to_tt(self, rel_ply: Depth) -> Self {
+        if self.is_mate() {
+            Self::new(self.v + rel_ply.v() as i32)
+        }
+        else if self.is_mated() {
+            Self::new(self.v - rel_ply.v() as i32)
+        }
+        else {
+            self
+        }
+    }
+
+    /// Inverse of [`AnyScore::to_tt`].
+    #[inline]
+    pub const fn from_tt(self, rel_ply: Depth) -> Self {
+        if self.is_mate() {
+            Self::new(self.v - rel_ply.v() as i32)
+        }
+        else if self.is_mated() {
+            Self::new(self.v + rel_ply.v() as i32)
+        }
+        else {
+            self
+        }
+    }
+
     #[inline(always)]
     pub fn plies_til_mate(&self) -> Option<Depth> {
         let abs = self.v().abs();
@@ -0,0 +1,106 @@
+//! Cuckoo tables of all reversible moves, keyed by the zobrist difference they
+//! cause.
+//!
+//! Used to detect upcoming repetitions: If the key difference between the
+//! current position and a previous one is the key of a single reversible move,
+//! the side to move might be able to reach the previous position again.
+//!
+//! [Marcel van Kervinck, Cuckoo hashing for repetition detection](https://web.archive.org/web/20201107002606/https://marcelk.net/2013-04-06/paper/upcoming-rep-v2.pdf)
+
+use std::mem;
+
+use crate::core::{
+    bitboard::Bitboard,
+    color::colors,
+    coordinates::Square,
+    r#move::{Move, move_flags},
+    move_iter::{bishop, king, knight, rook},
+    piece::{Piece, PieceType, piece_type},
+};
+
+use super::{Hash, Hasher};
+
+const SIZE: usize = 8192;
+
+/// The number of reversible moves of non-pawn pieces on an empty board.
+pub const MOVE_COUNT: usize = 3668;
+
+#[inline]
+const fn h1(key: u64) -> usize { (key & (SIZE as u64 - 1)) as usize }
+
+#[inline]
+const fn h2(key: u64) -> usize { ((key >> 16) & (SIZE as u64 - 1)) as usize }
+
+pub(super) static mut CUCKOO: Cuckoo = unsafe { mem::zeroed() };
+
+pub(super) struct Cuckoo {
+    keys: [u64; SIZE],
+    moves: [Move; SIZE],
+}
+
+impl Cuckoo {
+    /// Returns the number of inserted moves.
+    pub(super) fn init(&mut self, hasher: &Hasher) -> usize {
+        self.keys = [0; SIZE];
+        self.moves = [Move::null(); SIZE];
+
+        let mut count = 0;
+        for color in [colors::WHITE, colors::BLACK] {
+            for pt in [
+                piece_type::KNIGHT,
+                piece_type::BISHOP,
+                piece_type::ROOK,
+                piece_type::QUEEN,
+                piece_type::KING,
+            ] {
+                let piece = Piece::from((color, pt));
+                for from in Bitboard::full() {
+                    for to in attacks_0_occ(pt, from).filter(|to| to.v() > from.v()) {
+                        let key = hasher.piece_sq[from.index()][piece.v() as usize] ^ hasher.piece_sq[to.index()][piece.v() as usize] ^ hasher.stm;
+                        self.insert(key, Move::new(from, to, move_flags::QUIET));
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    fn insert(&mut self, mut key: u64, mut mov: Move) {
+        let mut i = h1(key);
+        loop {
+            mem::swap(&mut self.keys[i], &mut key);
+            mem::swap(&mut self.moves[i], &mut mov);
+            if mov == Move::null() {
+                return;
+            }
+            // push the evicted entry to its alternative slot
+            i = if i == h1(key) { h2(key) } else { h1(key) };
+        }
+    }
+
+    #[inline]
+    fn get(&self, key: u64) -> Option<Move> { [h1(key), h2(key)].into_iter().find(|&i| self.keys[i] == key).map(|i| self.moves[i]) }
+}
+
+fn attacks_0_occ(pt: PieceType, sq: Square) -> Bitboard {
+    match pt {
+        piece_type::KNIGHT => knight::lookup_attacks(sq),
+        piece_type::BISHOP => bishop::lookup_attacks_0_occ(sq),
+        piece_type::ROOK => rook::lookup_attacks_0_occ(sq),
+        piece_type::QUEEN => bishop::lookup_attacks_0_occ(sq) | rook::lookup_attacks_0_occ(sq),
+        piece_type::KING => king::lookup_attacks(sq),
+        _ => Bitboard::empty(),
+    }
+}
+
+/// Returns the reversible move whose zobrist difference is `diff`, if any.
+///
+/// The move is stored with `from < to`, regardless of which direction was
+/// actually played.
+#[inline]
+#[allow(static_mut_refs)]
+pub fn lookup(diff: Hash) -> Option<Move> {
+    debug_assert!(super::INIT.is_completed(), "Cuckoo tables not initialized!");
+    unsafe { CUCKOO.get(diff.v()) }
+}
@@ -2,17 +2,18 @@ use std::{fmt, mem, ops, sync::Once};
 
 use rand::{RngCore, SeedableRng, rngs::SmallRng};
 
-use crate::core::color::colors;
+use crate::core::color::{Color, colors};
 
 use super::{
     bitboard::Bitboard,
     castling::CastlingRights,
     coordinates::{EpCaptureSquare, File, Square},
-    piece::Piece,
-    position::Position,
+    piece::{Piece, piece_type},
+    position::{PieceInfo, Position},
     turn::Turn,
 };
 
+pub mod cuckoo;
 #[cfg(test)] pub mod test;
 
 /// Note: the default hash is equivalent to the hash of the default (empty)
@@ -79,6 +80,14 @@ impl Hash {
         *self
     }
 
+    /// Toggles the `n`-th (zero based) piece of kind `piece` in a material key.
+    #[inline]
+    pub fn toggle_piece_count(&mut self, piece: Piece, n: i8) -> Self {
+        debug_assert!(n >= 0, "Piece count must not be negative.");
+        self.v ^= hasher().piece_sq[n as usize][piece.v() as usize];
+        *self
+    }
+
     #[inline]
     pub fn move_piece_sq(&mut self, from: Square, to: Square, piece: Piece) -> Self {
         self.toggle_piece_sq(from, piece);
@@ -89,6 +98,32 @@ impl Hash {
 
 impl_op!(^ |l: Hash, r: u64| -> Hash { Hash { v: l.v ^ r } });
 
+impl Hash {
+    /// Key of the pawns of both colors.
+    pub fn pawn_key(pieces: &PieceInfo) -> Self { Self::of_pieces(pieces, pieces.get_piece_bb(piece_type::PAWN)) }
+
+    /// Key of all pieces of `color` except for the pawns.
+    pub fn non_pawn_key(pieces: &PieceInfo, color: Color) -> Self {
+        Self::of_pieces(pieces, pieces.get_color_bb(color) & !pieces.get_piece_bb(piece_type::PAWN))
+    }
+
+    /// Key of the number of pieces of each kind, regardless of where they are.
+    pub fn material_key(pieces: &PieceInfo) -> Self {
+        let mut key = Hash::default();
+        for color in [colors::WHITE, colors::BLACK] {
+            for pt in piece_type::PAWN..=piece_type::KING {
+                let piece = Piece::from((color, pt));
+                for n in 0..pieces.get_piece_count(piece) {
+                    key.toggle_piece_count(piece, n);
+                }
+            }
+        }
+        key
+    }
+
+    fn of_pieces(pieces: &PieceInfo, bb: Bitboard) -> Self { bb.fold(Hash::default(), |mut acc, sq| acc.toggle_piece_sq(sq, pieces.get_piece(sq))) }
+}
+
 impl From<&Position> for Hash {
     fn from(pos: &Position) -> Self {
         Bitboard::full()
@@ -103,11 +138,23 @@ static mut HASHER: Hasher = unsafe { mem::zeroed() };
 
 static INIT: Once = Once::new();
 
-#[allow(static_mut_refs)]
-pub fn init() { INIT.call_once(|| unsafe { HASHER.init(14278029879823863027) }); }
+pub fn init() { INIT.call_once(|| unsafe { init_tables(14278029879823863027) }); }
+
+pub fn force_init(seed: u64) { unsafe { init_tables(seed) }; }
 
+/// The cuckoo tables depend on the keys, so they are always regenerated
+/// together.
+///
+/// # Safety
+/// No other thread may access the tables at the same time.
 #[allow(static_mut_refs)]
-pub fn force_init(seed: u64) { unsafe { HASHER.init(seed) }; }
+unsafe fn init_tables(seed: u64) {
+    unsafe {
+        HASHER.init(seed);
+        let count = cuckoo::CUCKOO.init(&HASHER);
+        debug_assert_eq!(count, cuckoo::MOVE_COUNT);
+    }
+}
 
 struct Hasher {
     piece_sq: [[u64; 14]; 64],
@@ -152,3 +152,25 @@ fn place_piece_and_remove_out_of_order_hash_only() {
     let key_end = key;
     assert_eq!(key_begin, key_end);
 }
+
+#[test]
+fn cuckoo_lookup_reversible_moves() {
+    magics::init();
+    zobrist::init();
+
+    use move_flags::*;
+    use squares::*;
+
+    let mut pos = Position::start_position();
+    let key_begin = pos.get_key();
+
+    pos.make_move(Move::new(G1, F3, QUIET), &mut ());
+    let diff = key_begin ^ pos.get_key().v();
+    assert_eq!(zobrist::cuckoo::lookup(diff), Some(Move::new(G1, F3, QUIET)));
+
+    // pawn moves are not reversible
+    let key_before = pos.get_key();
+    pos.make_move(Move::new(E7, E5, DOUBLE_PAWN_PUSH), &mut ());
+    let diff = key_before ^ pos.get_key().v();
+    assert_eq!(zobrist::cuckoo::lookup(diff), None);
+}
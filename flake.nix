@@ -42,6 +42,7 @@
           variants = [
             ["mcts-nn"]
             ["mcts-hce"]
+            ["mcts-nnue"]
             ["id-hce"]
             ["id-nnue"]
           ];
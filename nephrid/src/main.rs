@@ -12,7 +12,7 @@ mod search {
     };
 
     pub type Worker = cfg_select! {
-        any(feature = "mcts-hce", feature = "mcts-pure", feature = "mcts-nn") => {
+        any(feature = "mcts-hce", feature = "mcts-nnue", feature = "mcts-pure", feature = "mcts-nn") => {
             search::MctsWorker<MPV, Config, Params>
         },
         feature = "id-hce" => {
@@ -26,6 +26,7 @@ mod search {
     #[allow(dead_code)]
     pub type Params = cfg_select! {
         feature = "mcts-hce"  => params::MctsHceParams,
+        feature = "mcts-nnue" => params::MctsHceParams,
         feature = "mcts-nn"   => params::MctsNnParams,
         feature = "mcts-pure" => params::MctsPureParams,
         feature = "id-hce"    => params::IdHceParams,
@@ -41,6 +42,7 @@ mod search {
     #[allow(dead_code)]
     const MPV: usize = cfg_select! {
         feature = "mcts-hce"  => 1,
+        feature = "mcts-nnue" => 1,
         feature = "mcts-pure" => 1,
         feature = "id-hce"    => 1,
         feature = "id-nnue"   => 1,
@@ -48,16 +50,18 @@ mod search {
         all(feature = "mcts-nn", feature = "nn-backend-ndarray") => 1,
     };
 
-    #[cfg(any(feature = "mcts-hce", feature = "mcts-pure", feature = "mcts-nn"))]
+    #[cfg(any(feature = "mcts-hce", feature = "mcts-nnue", feature = "mcts-pure", feature = "mcts-nn"))]
     impl search::mcts::MctsConfig for Config {
         type Parts = cfg_select! {
             feature = "mcts-hce"  => { search::mcts::HceParts },
+            feature = "mcts-nnue" => { search::mcts::NnueParts },
             feature = "mcts-pure" => { search::mcts::PureParts },
             all(feature = "mcts-nn", feature = "nn-backend-cuda") => { search::mcts::NNParts<burn_cuda::Cuda<f32>> },
             all(feature = "mcts-nn", feature = "nn-backend-ndarray") => { search::mcts::NNParts<burn::backend::NdArray> },
         };
         type Strat = cfg_select! {
             feature = "mcts-hce"  => { search::mcts::strategy::MctsUci::<Params> },
+            feature = "mcts-nnue" => { search::mcts::strategy::MctsUci::<Params> },
             feature = "mcts-pure" => { search::mcts::strategy::MctsUci::<Params> },
             all(feature = "mcts-nn", feature = "nn-backend-cuda") => { search::mcts::strategy::MctsUci::<Params> },
             all(feature = "mcts-nn", feature = "nn-backend-ndarray") => { search::mcts::strategy::MctsUci::<Params> },
@@ -70,6 +74,7 @@ fn config() -> Configuration {
 
     builder = cfg_select! {
         feature = "mcts-hce" => { mcts_hce_params_default().build_config(builder) },
+        feature = "mcts-nnue" => { mcts_hce_params_default().build_config(builder) },
         feature = "mcts-nn" => { mcts_nn_params_default().build_config(builder) },
         feature = "mcts-pure" => { mcts_pure_params_default().build_config(builder) },
         feature = "id-hce" => { id_hce_params_default().build_config(builder) },
@@ -68,7 +68,7 @@ fn write_engine_line(stdin: &mut ChildStdin, line: &str) {
 }
 
 #[cfg(test)]
-#[cfg(any(feature = "mcts-hce", feature = "mcts-pure", feature = "mcts-nn"))]
+#[cfg(any(feature = "mcts-hce", feature = "mcts-nnue", feature = "mcts-pure", feature = "mcts-nn"))]
 pub mod ponder_tests {
     use std::cmp::Reverse;
 
@@ -0,0 +1,82 @@
+use engine::core::{
+    color::{Color, colors},
+    coordinates::Square,
+    eval::GameResult,
+    piece::piece_type,
+    position::Position,
+};
+
+/// The size of a [ChessBoard] on disk.
+pub const RECORD_SIZE: usize = 32;
+
+/// A position in the `ChessBoard` layout of bulletformat, which bullet loads
+/// as is. Everything in it is seen from the side to move, so the board of
+/// black is flipped vertically and its colors are swapped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChessBoard {
+    pub occ: u64,
+    /// A nibble per occupied square, in the order of `occ`: whether the piece
+    /// is of the opponent, then the piece type from zero.
+    pub pcs: [u8; 16],
+    pub score: i16,
+    /// 0, 1 or 2 for a loss, a draw or a win of the side to move.
+    pub result: u8,
+    pub ksq: u8,
+    /// The square of the opponent king, flipped again.
+    pub opp_ksq: u8,
+}
+
+impl ChessBoard {
+    /// The board of `pos`, with the `score` relative to the side to move. The
+    /// result is a draw until [ChessBoard::with_result] knows better.
+    pub fn new(pos: &Position, score: i16) -> Self {
+        let stm = pos.get_turn();
+        let relative = |sq: Square| if stm == colors::WHITE { sq } else { sq.flip_v() };
+
+        // the pieces follow the order of the flipped squares
+        let mut squares: Vec<Square> = pos.get_occupancy().into_iter().collect();
+        squares.sort_by_key(|&sq| relative(sq).index());
+
+        let mut occ = 0;
+        let mut pcs = [0; 16];
+        for (idx, &sq) in squares.iter().enumerate() {
+            let (c, pt) = pos.get_piece(sq).unpack();
+            let nibble = u8::from(c != stm) << 3 | (pt.v() - 1);
+            occ |= 1 << relative(sq).index();
+            pcs[idx / 2] |= nibble << (4 * (idx % 2));
+        }
+
+        let king = |c: Color| relative(pos.get_bitboard(piece_type::KING, c).lsb().expect("Both kings should be on the board.")).index() as u8;
+        Self {
+            occ,
+            pcs,
+            score,
+            result: 1,
+            ksq: king(stm),
+            opp_ksq: king(!stm) ^ 56,
+        }
+    }
+
+    /// The board with the `result` of its game, where `stm` is the side to
+    /// move of the board.
+    pub fn with_result(self, result: GameResult, stm: Color) -> Self {
+        let result = match result {
+            GameResult::Win { relative_to } if relative_to == stm => 2,
+            GameResult::Win { .. } => 0,
+            GameResult::Draw => 1,
+        };
+        Self { result, ..self }
+    }
+
+    pub fn to_bytes(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0; RECORD_SIZE];
+        bytes[0..8].copy_from_slice(&self.occ.to_le_bytes());
+        bytes[8..24].copy_from_slice(&self.pcs);
+        bytes[24..26].copy_from_slice(&self.score.to_le_bytes());
+        bytes[26] = self.result;
+        bytes[27] = self.ksq;
+        bytes[28] = self.opp_ksq;
+        // the rest is padding
+        bytes
+    }
+}
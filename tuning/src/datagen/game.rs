@@ -0,0 +1,150 @@
+use engine::core::{
+    color::colors,
+    eval::GameResult,
+    r#move::{Move, MoveList},
+    params::IdNnueParams,
+    position::{CheckState, Position},
+    search::{
+        IdWorker,
+        id::NnueEvaluator,
+        limit::UciLimit,
+        score::{AnyScore, TCp},
+    },
+};
+use rand::{Rng, seq::IndexedRandom};
+
+use crate::format::ChessBoard;
+
+pub type Worker = IdWorker<NnueEvaluator, IdNnueParams>;
+
+/// Games that go on for longer are drawn.
+const MAX_PLIES: usize = 400;
+
+/// A game is won once the score stays beyond this for `WIN_PLIES` plies.
+const WIN_SCORE: i32 = 2000;
+const WIN_PLIES: usize = 6;
+
+/// A game is drawn once the score stays within this for `DRAW_PLIES` plies,
+/// starting with `DRAW_MIN_PLY`.
+const DRAW_SCORE: i32 = 10;
+const DRAW_PLIES: usize = 12;
+const DRAW_MIN_PLY: usize = 80;
+
+/// The scores that are kept, like the `nnue` trainer filters them.
+const MAX_SCORE: i32 = 10000;
+
+/// Ends the games that are decided long before the rules would end them.
+#[derive(Default)]
+pub struct Adjudicator {
+    win_plies: usize,
+    /// Whether the current run of winning scores is in favour of white.
+    white_winning: bool,
+    draw_plies: usize,
+}
+
+impl Adjudicator {
+    /// Takes the `score` of the search at `ply`, relative to white.
+    pub fn update(&mut self, ply: usize, score: AnyScore) -> Option<GameResult> {
+        let score = score.v();
+
+        if score.abs() >= WIN_SCORE {
+            let white_winning = score > 0;
+            self.win_plies = if self.win_plies > 0 && self.white_winning == white_winning {
+                self.win_plies + 1
+            }
+            else {
+                1
+            };
+            self.white_winning = white_winning;
+        }
+        else {
+            self.win_plies = 0;
+        }
+
+        self.draw_plies = if ply >= DRAW_MIN_PLY && score.abs() <= DRAW_SCORE {
+            self.draw_plies + 1
+        }
+        else {
+            0
+        };
+
+        if self.win_plies >= WIN_PLIES {
+            let winner = if self.white_winning {
+                colors::WHITE
+            }
+            else {
+                colors::BLACK
+            };
+            Some(GameResult::Win { relative_to: winner })
+        }
+        else if self.draw_plies >= DRAW_PLIES {
+            Some(GameResult::Draw)
+        }
+        else {
+            None
+        }
+    }
+}
+
+/// Whether the position is worth training on: quiet, with a quiet best move
+/// and a score that the network can learn.
+pub fn is_quiet(pos: &Position, best_move: Move, score: AnyScore) -> bool {
+    let flag = best_move.get_flag();
+    pos.get_check_state() == CheckState::None
+        && !flag.is_capture()
+        && !flag.is_promo()
+        && !score.is_mate()
+        && !score.is_mated()
+        && score.v().abs() <= MAX_SCORE
+}
+
+/// Plays `plies` random moves from `opening`, or none if the game ends in
+/// them.
+pub fn random_opening(opening: &Position, plies: usize, rng: &mut impl Rng) -> Option<Position> {
+    let mut pos = opening.clone();
+    for _ in 0..plies {
+        let moves = pos.collect_legals(MoveList::new());
+        let &mov = moves.as_slice().choose(rng)?;
+        pos.make_move(mov, &mut ());
+    }
+    pos.game_result().is_none().then_some(pos)
+}
+
+/// Plays a game from `pos` against itself with `nodes` per move, and returns
+/// the quiet positions of it with the result. The game is thrown away if the
+/// search runs out of nodes before it has a move.
+pub fn play(worker: &mut Worker, mut pos: Position, nodes: u64) -> Vec<ChessBoard> {
+    let mut boards = Vec::new();
+    let mut adjudicator = Adjudicator::default();
+
+    let mut ply = 0;
+    let result = loop {
+        if let Some(result) = pos.game_result() {
+            break result;
+        }
+        if ply >= MAX_PLIES {
+            break GameResult::Draw;
+        }
+
+        let limit = UciLimit { nodes, ..Default::default() };
+        let Some((best_move, score)) = worker.search(&mut pos, limit)
+        else {
+            return Vec::new();
+        };
+
+        let stm = pos.get_turn();
+        if is_quiet(&pos, best_move, score) {
+            boards.push((ChessBoard::new(&pos, score.v() as TCp), stm));
+        }
+
+        let white_score = if stm == colors::WHITE { score } else { -score };
+        if let Some(result) = adjudicator.update(ply, white_score) {
+            break result;
+        }
+
+        pos.make_move(best_move, &mut ());
+        ply += 1;
+    };
+
+    boards.into_iter().map(|(board, stm)| board.with_result(result, stm)).collect()
+}
@@ -0,0 +1,169 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Instant,
+};
+
+use clap::Parser;
+use crossbeam_channel::bounded;
+use engine::{
+    core::{
+        config::Configuration,
+        eval::nnue,
+        move_iter::sliding_piece::magics,
+        params::{IConfigBuilder, id_nnue_params_default},
+        position::Position,
+        search::{Command, SearchWorker},
+        zobrist,
+    },
+    math::{self, DefaultLmrParams},
+};
+use rand::{SeedableRng, rngs::SmallRng, seq::IndexedRandom};
+
+use crate::game::Worker;
+
+#[cfg(test)] pub mod test;
+
+pub mod format;
+pub mod game;
+
+/// Plays games of the nnue search against itself with a fixed number of nodes
+/// per move, and writes their quiet positions in bulletformat, with the score
+/// and the result from the side to move.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// The file the positions are appended to.
+    #[arg(long, default_value = "datagen.bullet")]
+    output: PathBuf,
+
+    #[arg(long, default_value_t = 1000)]
+    games: usize,
+
+    /// The nodes of each search, which it stops at exactly.
+    #[arg(long, default_value_t = 5000)]
+    nodes: u64,
+
+    /// An EPD file with the positions the games start from. Without one, they
+    /// start from the start position.
+    #[arg(long)]
+    openings: Option<PathBuf>,
+
+    /// Random moves played from the opening before the search takes over.
+    #[arg(long, default_value_t = 8)]
+    random_plies: usize,
+
+    /// The network of the search, instead of the default one.
+    #[arg(long)]
+    nnue: Option<String>,
+
+    #[arg(long, default_value_t = 4)]
+    threads: usize,
+
+    /// Seeds the random moves, such that the games can be played again.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+/// Games between the progress reports.
+const REPORT_INTERVAL: usize = 100;
+
+fn main() {
+    math::init(DefaultLmrParams);
+    magics::init();
+    zobrist::init();
+
+    let args = Args::parse();
+
+    let mut config = id_nnue_params_default().build_config(Configuration::builder()).build();
+    if let Some(path) = &args.nnue {
+        nnue::nnue_from_path(path).unwrap_or_else(|err| panic!("Failed to load {path}: {err}"));
+        config.set("nnue-path", path).expect("The nnue path should be a valid option.");
+    }
+
+    let openings = match &args.openings {
+        Some(path) => read_openings(&fs::read_to_string(path).unwrap_or_else(|err| panic!("Failed to read {}: {err}", path.display()))),
+        None => vec![Position::start_position()],
+    };
+    assert!(!openings.is_empty(), "There are no openings to play from.");
+
+    let file = File::options()
+        .create(true)
+        .append(true)
+        .open(&args.output)
+        .unwrap_or_else(|err| panic!("Failed to open {}: {err}", args.output.display()));
+    let mut writer = BufWriter::new(file);
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let started = AtomicUsize::new(0);
+    let (sender, receiver) = bounded(args.threads * 4);
+
+    thread::scope(|s| {
+        for thread_idx in 0..args.threads {
+            let (sender, config, openings, started, args) = (sender.clone(), config.clone(), &openings, &started, &args);
+            s.spawn(move || {
+                let mut rng = SmallRng::seed_from_u64(seed.wrapping_add(thread_idx as u64));
+                let mut worker = Worker::new();
+                worker
+                    .exec(Command::Configure(Arc::new(Mutex::new(config))))
+                    .unwrap_or_else(|err| panic!("Failed to configure the search: {err}"));
+
+                while started.fetch_add(1, Ordering::Relaxed) < args.games {
+                    let opening = openings.choose(&mut rng).expect("There are openings.");
+                    let pos = loop {
+                        if let Some(pos) = game::random_opening(opening, args.random_plies, &mut rng) {
+                            break pos;
+                        }
+                    };
+
+                    worker.exec(Command::ResetState).expect("Resetting the search cannot fail.");
+                    if sender.send(game::play(&mut worker, pos, args.nodes)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let start = Instant::now();
+        let (mut games, mut positions) = (0, 0);
+        for boards in receiver {
+            for board in &boards {
+                writer.write_all(&board.to_bytes()).expect("Failed to write the positions.");
+            }
+            games += 1;
+            positions += boards.len();
+
+            if games % REPORT_INTERVAL == 0 || games == args.games {
+                let elapsed = start.elapsed().as_secs_f64();
+                println!(
+                    "games {games}/{} | positions {positions} | {:.1} games/s | {:.0} pos/s",
+                    args.games,
+                    games as f64 / elapsed,
+                    positions as f64 / elapsed
+                );
+            }
+        }
+    });
+
+    writer.flush().expect("Failed to write the positions.");
+}
+
+/// The positions of the EPD lines in `epd`, where the move counters are
+/// optional.
+fn read_openings(epd: &str) -> Vec<Position> {
+    epd.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+            let fen = format!("{} 0 1", fields.join(" "));
+            Position::from_fen(&fen).unwrap_or_else(|err| panic!("Invalid opening {line}: {err}"))
+        })
+        .collect()
+}
@@ -0,0 +1,63 @@
+use engine::core::{color::colors, eval::GameResult, move_iter::sliding_piece::magics, position::Position, search::score::AnyScore, zobrist};
+
+use crate::{format::ChessBoard, game::Adjudicator, read_openings};
+
+fn init() {
+    magics::init();
+    zobrist::init();
+}
+
+#[test]
+fn start_position_is_encoded_like_bulletformat() {
+    init();
+    let board = ChessBoard::new(&Position::start_position(), 25);
+    let bytes = board.to_bytes();
+
+    assert_eq!(board.occ, 0xFFFF_0000_0000_FFFF);
+    assert_eq!(
+        &bytes[8..24],
+        &[0x13, 0x42, 0x25, 0x31, 0, 0, 0, 0, 0x88, 0x88, 0x88, 0x88, 0x9B, 0xCA, 0xAD, 0xB9]
+    );
+    assert_eq!(i16::from_le_bytes([bytes[24], bytes[25]]), 25);
+    assert_eq!((bytes[26], bytes[27], bytes[28]), (1, 4, 4));
+}
+
+#[test]
+fn black_to_move_is_flipped() {
+    init();
+    let black = Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+    let white = Position::from_fen("rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    assert_eq!(ChessBoard::new(&black, -30), ChessBoard::new(&white, -30));
+
+    let board = ChessBoard::new(&black, -30);
+    assert_eq!(board.with_result(GameResult::Win { relative_to: colors::WHITE }, colors::BLACK).result, 0);
+    assert_eq!(board.with_result(GameResult::Win { relative_to: colors::BLACK }, colors::BLACK).result, 2);
+    assert_eq!(board.with_result(GameResult::Draw, colors::BLACK).result, 1);
+}
+
+#[test]
+fn adjudicates_runs_of_scores() {
+    let mut adjudicator = Adjudicator::default();
+    // the run starts over when the side changes
+    for ply in 0..5 {
+        assert_eq!(adjudicator.update(ply, AnyScore::new(2500)), None);
+    }
+    assert_eq!(adjudicator.update(5, AnyScore::new(-2500)), None);
+    let results: Vec<_> = (6..11).map(|ply| adjudicator.update(ply, AnyScore::new(-2500))).collect();
+    assert_eq!(results[..4], [None; 4]);
+    assert_eq!(results[4], Some(GameResult::Win { relative_to: colors::BLACK }));
+
+    // even scores only draw late in the game
+    let mut adjudicator = Adjudicator::default();
+    assert!((0..40).all(|ply| adjudicator.update(ply, AnyScore::new(0)).is_none()));
+    let results: Vec<_> = (100..112).map(|ply| adjudicator.update(ply, AnyScore::new(5))).collect();
+    assert_eq!(results.last(), Some(&Some(GameResult::Draw)));
+}
+
+#[test]
+fn openings_take_epd_lines() {
+    init();
+    let openings = read_openings("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - bm e5; id \"e4\";\n\n4k3/8/8/8/8/8/8/4K2R w K -\n");
+    assert_eq!(openings.len(), 2);
+    assert_eq!(openings[0].get_turn(), colors::BLACK);
+}
@@ -50,7 +50,7 @@ use engine::{
         Game,
         color::Color,
         r#move::Move,
-        position::{FenImport, Position},
+        position::{FenImport, Position, Validation},
         search::mcts::{
             SearchState,
             eval::Guess,
@@ -227,7 +227,7 @@ where
 {
     log::debug!(target: "data", "[FEN {i:>2}/{n:<2}] Starting self-play with fen '{fen}'");
 
-    let game = Game::from_fen(FenImport(&mut Tokenizer::new(fen)))?;
+    let game = Game::from_fen(FenImport(&mut Tokenizer::new(fen)), Validation::Lenient)?;
     let root_hash = game.position().get_key();
     let SelfPlay { game, results } = self_play::<P>(i, n, game, limit.clone(), config, parts)?;
     if results.is_empty() {
@@ -1,8 +1,15 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use bullet::{
-    game::inputs::Chess768,
-    nn::optimiser::AdamW,
+    game::{
+        inputs::{ChessBuckets, ChessBucketsMirrored},
+        outputs::MaterialCount,
+    },
+    nn::{InitSettings, Shape, optimiser::AdamW},
     trainer::{
         save::SavedFormat,
         schedule::{TrainingSchedule, TrainingSteps, lr, wdl},
@@ -10,46 +17,230 @@ use bullet::{
     },
     value::{ValueTrainerBuilder, loader},
 };
-use engine::core::eval::nnue::{HIDDEN_SIZE, INPUT_SIZE, OUTPUT_SIZE, QA, QB, SCALE, TValue};
+use clap::{Parser, ValueEnum};
+use engine::core::eval::nnue::{
+    Architecture, FEATURES_PER_BUCKET, HIDDEN_SIZE, INPUT_SIZE, OUTPUT_SIZE, QA, QB, SCALE, TValue,
+    buckets::{self, InputLayout, N_KING_BUCKETS, N_OUTPUT_BUCKETS},
+    deep::{L1_SIZE, L2_SIZE, QL1},
+    format::{self, Compression},
+};
+
+/// Trains a network with bullet. The checkpoints are saved to `checkpoints`,
+/// with a `network.nnue` that the engine can load.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// The layers after the accumulators.
+    #[arg(short, long, value_enum, default_value_t = Arch::Shallow)]
+    arch: Arch,
+
+    /// Instead of training, packs the raw network of the trainer at this
+    /// path into a `network.nnue` next to it.
+    #[arg(long)]
+    pack: Option<PathBuf>,
+
+    /// Whether the network at `pack` was trained with mirrored inputs, if
+    /// not like the current trainer. The engine rejects it unless it is.
+    #[arg(long, requires = "pack")]
+    mirrored: Option<bool>,
+
+    /// The data to train on.
+    #[arg(long, default_value = "resources/datasets/binpack/test80-2024-02-feb-2tb7p.min-v2.v6.binpack")]
+    data: PathBuf,
+
+    /// The format of `data`, e.g. `bullet` for the output of `datagen`.
+    #[arg(long, value_enum, default_value_t = DataFormat::Binpack)]
+    format: DataFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Arch {
+    /// `(768 -> N)x2 -> 1`.
+    Shallow,
+    /// `(768 -> N)x2 -> L1 -> L2 -> 1`, with int8 weights in L1.
+    Deep,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DataFormat {
+    /// The binpacks of Stockfish, filtered to quiet positions.
+    Binpack,
+    /// The `ChessBoard` records of bulletformat that `datagen` writes.
+    Bullet,
+}
+
+impl From<Arch> for Architecture {
+    fn from(arch: Arch) -> Self {
+        match arch {
+            Arch::Shallow => Self::Shallow,
+            Arch::Deep => Self::Deep,
+        }
+    }
+}
+
+/// The inputs of bullet for each value of [buckets::MIRRORED], such that the
+/// engine and the trainer share the switch.
+trait SelectInputs {
+    type Inputs;
+    /// The layout of [SelectInputs::inputs].
+    const LAYOUT: InputLayout;
+    fn inputs() -> Self::Inputs;
+}
+
+struct Mirrored<const MIRRORED: bool>;
+
+impl SelectInputs for Mirrored<false> {
+    type Inputs = ChessBuckets;
+    const LAYOUT: InputLayout = InputLayout {
+        mirrored: false,
+        ..InputLayout::ENGINE
+    };
+
+    fn inputs() -> Self::Inputs { ChessBuckets::new(Self::LAYOUT.all_squares()) }
+}
+
+impl SelectInputs for Mirrored<true> {
+    type Inputs = ChessBucketsMirrored;
+    const LAYOUT: InputLayout = InputLayout {
+        mirrored: true,
+        ..InputLayout::ENGINE
+    };
+
+    fn inputs() -> Self::Inputs { ChessBucketsMirrored::new(Self::LAYOUT.king_buckets) }
+}
+
+type Trained = Mirrored<{ buckets::MIRRORED }>;
+
+/// The inputs the networks are trained with.
+const TRAINED_LAYOUT: InputLayout = <Trained as SelectInputs>::LAYOUT;
+
+const _: () = assert!(
+    TRAINED_LAYOUT.hash() == buckets::INPUT_LAYOUT_HASH,
+    "The engine has to evaluate the inputs the networks are trained with."
+);
+
+/// The settings of the trainer that all architectures share.
+macro_rules! trainer_builder {
+    () => {
+        ValueTrainerBuilder::default()
+            // makes `ntm_inputs` available below
+            .dual_perspective()
+            // standard optimiser used in NNUE
+            // the default AdamW params include clipping to range [-1.98, 1.98]
+            .optimiser(AdamW)
+            // piece-square inputs, bucketed by the king square and mirrored such
+            // that the king is always on the queen side if `buckets::MIRRORED`,
+            // like in the engine
+            .inputs(Trained::inputs())
+            // a head of the output layers for each range of piece counts, like
+            // `buckets::output_bucket` in the engine
+            .output_buckets(MaterialCount::<N_OUTPUT_BUCKETS>)
+            // map output into ranges [0, 1] to fit against our labels which
+            // are in the same range
+            // `target` == wdl * game_result + (1 - wdl) * sigmoid(search score in centipawns / SCALE)
+            // where `wdl` is determined by `wdl_scheduler`
+            .loss_fn(|output, target| output.sigmoid().squared_error(target))
+    };
+}
+
+/// The saved format of the accumulator layer.
+fn l0_format() -> Vec<SavedFormat> {
+    vec![
+        // merge the factoriser into the weights of every bucket
+        SavedFormat::id("l0w")
+            .transform(|store, weights| {
+                let factoriser = store.get("l0f").values.repeat(N_KING_BUCKETS);
+                weights.into_iter().zip(factoriser).map(|(a, b)| a + b).collect()
+            })
+            .round()
+            .quantise::<TValue>(QA),
+        SavedFormat::id("l0b").round().quantise::<TValue>(QA),
+    ]
+}
 
 fn main() {
-    let mut trainer = ValueTrainerBuilder::default()
-        // makes `ntm_inputs` available below
-        .dual_perspective()
-        // standard optimiser used in NNUE
-        // the default AdamW params include clipping to range [-1.98, 1.98]
-        .optimiser(AdamW)
-        // basic piece-square chessboard inputs
-        .inputs(Chess768)
-        // chosen such that inference may be efficiently implemented in-engine
-        .save_format(&[
-            SavedFormat::id("l0w").round().quantise::<TValue>(QA),
-            SavedFormat::id("l0b").round().quantise::<TValue>(QA),
-            SavedFormat::id("l1w").round().quantise::<TValue>(QB),
+    let args = Args::parse();
+
+    if let Some(layers) = args.pack {
+        let inputs = InputLayout {
+            mirrored: args.mirrored.unwrap_or(TRAINED_LAYOUT.mirrored),
+            ..TRAINED_LAYOUT
+        };
+        pack(&layers, args.arch.into(), inputs).expect("Failed to pack the network.");
+        return;
+    }
+
+    let shallow_trainer = || {
+        let mut save_format = l0_format();
+        save_format.extend([
+            // the engine expects the weights of each output bucket in a row
+            SavedFormat::id("l1w").round().quantise::<TValue>(QB).transpose(),
             SavedFormat::id("l1b").round().quantise::<TValue>(QA * QB),
-        ])
-        // map output into ranges [0, 1] to fit against our labels which
-        // are in the same range
-        // `target` == wdl * game_result + (1 - wdl) * sigmoid(search score in centipawns / SCALE)
-        // where `wdl` is determined by `wdl_scheduler`
-        .loss_fn(|output, target| output.sigmoid().squared_error(target))
-        // the basic `(768 -> N)x2 -> 1` inference
-        .build(|builder, stm_inputs, ntm_inputs| {
-            // weights
-            let l0 = builder.new_affine("l0", INPUT_SIZE, HIDDEN_SIZE);
-            let l1 = builder.new_affine("l1", 2 * HIDDEN_SIZE, OUTPUT_SIZE);
-
-            // inference
-            let stm_hidden = l0.forward(stm_inputs).screlu();
-            let ntm_hidden = l0.forward(ntm_inputs).screlu();
-            let hidden_layer = stm_hidden.concat(ntm_hidden);
-            l1.forward(hidden_layer)
-        });
+        ]);
+
+        trainer_builder!()
+            // chosen such that inference may be efficiently implemented in-engine
+            .save_format(&save_format)
+            // the `(768 * buckets -> N)x2 -> 1 * buckets` inference
+            .build(|builder, stm_inputs, ntm_inputs, output_buckets| {
+                // weights shared by all king buckets, so that rare buckets
+                // still learn the common piece-square knowledge
+                let l0f = builder.new_weights("l0f", Shape::new(HIDDEN_SIZE, FEATURES_PER_BUCKET), InitSettings::Zeroed);
+                let expanded_factoriser = l0f.repeat(N_KING_BUCKETS);
+
+                // weights
+                let mut l0 = builder.new_affine("l0", INPUT_SIZE, HIDDEN_SIZE);
+                l0.weights = l0.weights + expanded_factoriser;
+                let l1 = builder.new_affine("l1", 2 * HIDDEN_SIZE, OUTPUT_SIZE);
+
+                // inference
+                let stm_hidden = l0.forward(stm_inputs).screlu();
+                let ntm_hidden = l0.forward(ntm_inputs).screlu();
+                let hidden_layer = stm_hidden.concat(ntm_hidden);
+                l1.forward(hidden_layer).select(output_buckets)
+            })
+    };
+
+    let deep_trainer = || {
+        let mut save_format = l0_format();
+        save_format.extend([
+            // the engine multiplies the clipped accumulators with int8 weights
+            SavedFormat::id("l1w").round().quantise::<i8>(QL1),
+            SavedFormat::id("l1b"),
+            SavedFormat::id("l2w"),
+            SavedFormat::id("l2b"),
+            SavedFormat::id("l3w"),
+            SavedFormat::id("l3b"),
+        ]);
+
+        trainer_builder!()
+            .save_format(&save_format)
+            // the `(768 * buckets -> N)x2 -> L1 -> L2 -> 1 * buckets` inference
+            .build(|builder, stm_inputs, ntm_inputs, output_buckets| {
+                let l0f = builder.new_weights("l0f", Shape::new(HIDDEN_SIZE, FEATURES_PER_BUCKET), InitSettings::Zeroed);
+                let expanded_factoriser = l0f.repeat(N_KING_BUCKETS);
+
+                // weights
+                let mut l0 = builder.new_affine("l0", INPUT_SIZE, HIDDEN_SIZE);
+                l0.weights = l0.weights + expanded_factoriser;
+                let l1 = builder.new_affine("l1", 2 * HIDDEN_SIZE, OUTPUT_SIZE * L1_SIZE);
+                let l2 = builder.new_affine("l2", L1_SIZE, OUTPUT_SIZE * L2_SIZE);
+                let l3 = builder.new_affine("l3", L2_SIZE, OUTPUT_SIZE);
+
+                // inference, the accumulators are clipped like the bytes in the engine
+                let stm_hidden = l0.forward(stm_inputs).crelu();
+                let ntm_hidden = l0.forward(ntm_inputs).crelu();
+                let hidden_layer = stm_hidden.concat(ntm_hidden);
+                let hidden_layer = l1.forward(hidden_layer).select(output_buckets).screlu();
+                let hidden_layer = l2.forward(hidden_layer).select(output_buckets).screlu();
+                l3.forward(hidden_layer).select(output_buckets)
+            })
+    };
 
     let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis();
 
     let schedule = TrainingSchedule {
-        net_id: format!("nnue-{timestamp}-{INPUT_SIZE}-{HIDDEN_SIZE}_{SCALE}_{QA}-{QB}"),
+        net_id: format!("nnue-{timestamp}-{:?}-{INPUT_SIZE}-{HIDDEN_SIZE}_{SCALE}_{QA}-{QB}", args.arch).to_lowercase(),
         eval_scale: SCALE as f32,
         steps: TrainingSteps {
             batch_size: 16_384,
@@ -73,22 +264,55 @@ fn main() {
         batch_queue_size: 64,
     };
 
-    let data_loader = {
-        use loader::sfbinpack::{MoveType, PieceType, SfBinpackLoader, TrainingDataEntry};
-
-        let file_path = "resources/datasets/binpack/test80-2024-02-feb-2tb7p.min-v2.v6.binpack";
-        let buffer_size_mb = 1024;
-        let threads = 4;
-        fn filter(entry: &TrainingDataEntry) -> bool {
-            entry.ply >= 16
-                && !entry.pos.is_checked(entry.pos.side_to_move())
-                && entry.score.unsigned_abs() <= 10000
-                && entry.mv.mtype() == MoveType::Normal
-                && entry.pos.piece_at(entry.mv.to()).piece_type() == PieceType::None
+    let file_path = args.data.to_str().expect("The path of the data should be valid unicode.");
+
+    // the loaders have different types, so each trains on its own
+    macro_rules! run {
+        ($data_loader:expr) => {
+            match args.arch {
+                Arch::Shallow => shallow_trainer().run(&schedule, &settings, &$data_loader),
+                Arch::Deep => deep_trainer().run(&schedule, &settings, &$data_loader),
+            }
+        };
+    }
+
+    match args.format {
+        DataFormat::Binpack => {
+            use loader::sfbinpack::{MoveType, PieceType, SfBinpackLoader, TrainingDataEntry};
+
+            let buffer_size_mb = 1024;
+            let threads = 4;
+            fn filter(entry: &TrainingDataEntry) -> bool {
+                entry.ply >= 16
+                    && !entry.pos.is_checked(entry.pos.side_to_move())
+                    && entry.score.unsigned_abs() <= 10000
+                    && entry.mv.mtype() == MoveType::Normal
+                    && entry.pos.piece_at(entry.mv.to()).piece_type() == PieceType::None
+            }
+
+            run!(SfBinpackLoader::new(file_path, buffer_size_mb, threads, filter))
         }
+        // `datagen` only writes quiet positions, so they aren't filtered
+        DataFormat::Bullet => run!(loader::DirectSequentialDataLoader::new(&[file_path])),
+    }
 
-        SfBinpackLoader::new(file_path, buffer_size_mb, threads, filter)
-    };
+    for entry in fs::read_dir(settings.output_directory).expect("Failed to read the checkpoints.") {
+        let checkpoint = entry.expect("Failed to read the checkpoints.").path();
+        let layers = checkpoint.join("quantised.bin");
+        if checkpoint
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with(&schedule.net_id))
+            && layers.exists()
+        {
+            pack(&layers, args.arch.into(), TRAINED_LAYOUT).expect("Failed to pack the network.");
+        }
+    }
+}
 
-    trainer.run(&schedule, &settings, &data_loader);
+/// Saves the raw network of the trainer at `layers` in the format of the
+/// engine, with the header of its architecture and the `inputs` it was trained
+/// with, next to it.
+fn pack(layers: &Path, architecture: Architecture, inputs: InputLayout) -> io::Result<()> {
+    let bytes = format::encode(architecture, inputs, &fs::read(layers)?, Compression::Zlib);
+    fs::write(layers.with_file_name("network.nnue"), bytes)
 }
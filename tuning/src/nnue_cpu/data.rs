@@ -0,0 +1,183 @@
+use std::{
+    fs::File,
+    io::{BufReader, ErrorKind, Read},
+    ops::ControlFlow,
+    path::Path,
+    thread,
+};
+
+use clap::ValueEnum;
+use crossbeam_channel::{Receiver, bounded};
+use engine::core::{
+    color::colors,
+    eval::nnue::{self, SCALE, buckets},
+    piece::{self, Piece},
+    position::Position,
+};
+#[cfg(feature = "binpack")]
+use sfbinpack::{
+    CompressedTrainingDataEntryReader, TrainingDataEntry,
+    chess::{r#move::MoveType, piecetype::PieceType},
+};
+
+use crate::network::{MAX_FEATURES, Sample, sigmoid};
+
+/// The formats of the data the trainer reads.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DataFormat {
+    /// The binpacks of Stockfish, filtered like the `nnue` trainer does.
+    #[cfg(feature = "binpack")]
+    #[default]
+    Binpack,
+    /// The `ChessBoard` records of bulletformat that `datagen` writes.
+    #[cfg_attr(not(feature = "binpack"), default)]
+    Bullet,
+}
+
+/// The size of a `ChessBoard` record of bulletformat.
+pub const BULLET_RECORD_SIZE: usize = 32;
+
+/// The positions that the `nnue` trainer learns from.
+#[cfg(feature = "binpack")]
+pub fn filter(entry: &TrainingDataEntry) -> bool {
+    entry.ply >= 16
+        && !entry.pos.is_checked(entry.pos.side_to_move())
+        && entry.score.unsigned_abs() <= 10000
+        && entry.mv.mtype() == MoveType::Normal
+        && entry.pos.piece_at(entry.mv.to()).piece_type() == PieceType::None
+}
+
+/// The sample of `pos`, with the score and the result of the game from the
+/// side to move. The target blends them like the `nnue` trainer does with
+/// `wdl`.
+pub fn sample(pos: &Position, score: i16, result: i16, wdl: f32) -> Sample {
+    let pieces = pos.piece_info();
+    let stm = pos.get_turn();
+    let mut features = [[0; MAX_FEATURES]; 2];
+    for (features, perspective) in features.iter_mut().zip([stm, !stm]) {
+        for (slot, feature) in features.iter_mut().zip(nnue::active_features(pieces, perspective)) {
+            *slot = feature as u16;
+        }
+    }
+
+    let result = (f32::from(result) + 1.) / 2.;
+    let score = sigmoid(f32::from(score) / f32::from(SCALE));
+    Sample {
+        features,
+        n_features: pieces.get_occupancy().pop_cnt() as u8,
+        bucket: buckets::output_bucket(pieces.get_occupancy()) as u8,
+        target: wdl * result + (1. - wdl) * score,
+    }
+}
+
+/// Reads batches of the positions of the data at `path` on another thread,
+/// starting over at the end of the file.
+pub fn batches(path: &Path, format: DataFormat, batch_size: usize, wdl: f32) -> Receiver<Vec<Sample>> {
+    let (sender, receiver) = bounded(64);
+    let path = path.to_owned();
+
+    thread::spawn(move || {
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut push = |pos: &Position, score: i16, result: i16| {
+            batch.push(sample(pos, score, result, wdl));
+            if batch.len() == batch_size {
+                let full = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+                // the trainer is done once it stops receiving
+                if sender.send(full).is_err() {
+                    return ControlFlow::Break(());
+                }
+            }
+            ControlFlow::Continue(())
+        };
+
+        loop {
+            let read = match format {
+                #[cfg(feature = "binpack")]
+                DataFormat::Binpack => read_binpack(&path, &mut push),
+                DataFormat::Bullet => read_bullet(&path, &mut push),
+            };
+            match read {
+                ControlFlow::Break(()) => return,
+                ControlFlow::Continue(0) => panic!("The data has no positions."),
+                ControlFlow::Continue(_) => {}
+            }
+        }
+    });
+
+    receiver
+}
+
+/// Passes the filtered positions of the binpack at `path` to `each`, with
+/// their score and result, until it breaks. Returns the number of positions.
+#[cfg(feature = "binpack")]
+fn read_binpack(path: &Path, mut each: impl FnMut(&Position, i16, i16) -> ControlFlow<()>) -> ControlFlow<(), usize> {
+    let path = path.to_str().expect("The path of the data should be valid unicode.");
+    let mut reader = CompressedTrainingDataEntryReader::new(path).expect("Failed to open the data.");
+    let mut positions = 0;
+    while reader.has_next() {
+        let entry = reader.next();
+        if !filter(&entry) {
+            continue;
+        }
+
+        let fen = entry.pos.fen().expect("The positions of the data should be valid.");
+        let pos = Position::from_fen(&fen).expect("The positions of the data should be valid.");
+        each(&pos, entry.score, entry.result)?;
+        positions += 1;
+    }
+    ControlFlow::Continue(positions)
+}
+
+/// Passes the positions of the bulletformat file at `path` to `each`, with
+/// their score and result, until it breaks. Returns the number of positions.
+/// `datagen` only writes quiet positions, so they aren't filtered.
+fn read_bullet(path: &Path, mut each: impl FnMut(&Position, i16, i16) -> ControlFlow<()>) -> ControlFlow<(), usize> {
+    let mut reader = BufReader::new(File::open(path).expect("Failed to open the data."));
+    let mut record = [0; BULLET_RECORD_SIZE];
+    let mut positions = 0;
+    loop {
+        match reader.read_exact(&mut record) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return ControlFlow::Continue(positions),
+            Err(err) => panic!("Failed to read the data: {err}"),
+        }
+        let (pos, score, result) = bullet_position(&record);
+        each(&pos, score, result)?;
+        positions += 1;
+    }
+}
+
+/// The position of a `ChessBoard` record, with its score and result. The
+/// record is seen from the side to move, so it is white to move in the
+/// position, which has the same features.
+pub fn bullet_position(record: &[u8; BULLET_RECORD_SIZE]) -> (Position, i16, i16) {
+    let occ = u64::from_le_bytes(record[0..8].try_into().unwrap());
+    let mut board = ['.'; 64];
+    for (idx, sq) in (0..64).filter(|sq| occ >> sq & 1 == 1).enumerate() {
+        let nibble = record[8 + idx / 2] >> (4 * (idx % 2)) & 0xF;
+        let color = if nibble & 8 == 0 { colors::WHITE } else { colors::BLACK };
+        let pt = piece::PieceType::try_from((nibble & 7) + 1).expect("The pieces of the data should be valid.");
+        board[sq] = char::from(Piece::from((color, pt)));
+    }
+
+    let ranks: Vec<String> = board
+        .chunks(8)
+        .rev()
+        .map(|rank| {
+            let mut fen = String::new();
+            for run in rank.chunk_by(|a, b| (*a == '.') == (*b == '.')) {
+                match run[0] {
+                    '.' => fen.push_str(&run.len().to_string()),
+                    _ => fen.extend(run),
+                }
+            }
+            fen
+        })
+        .collect();
+    let pos = Position::from_fen(&format!("{} w - - 0 1", ranks.join("/"))).expect("The positions of the data should be valid.");
+
+    let score = i16::from_le_bytes([record[24], record[25]]);
+    // from 0, 1 or 2 to -1, 0 or 1 like the binpacks
+    let result = i16::from(record[26]) - 1;
+    (pos, score, result)
+}
@@ -0,0 +1,138 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use clap::Parser;
+use engine::core::{
+    eval::nnue::{
+        Architecture, HIDDEN_SIZE, INPUT_SIZE, QA, QB, SCALE,
+        buckets::InputLayout,
+        format::{self, Compression},
+    },
+    move_iter::sliding_piece::magics,
+    zobrist,
+};
+use rand::{SeedableRng, rngs::SmallRng};
+
+use crate::{
+    data::DataFormat,
+    network::{AdamW, Network},
+};
+
+#[cfg(test)] pub mod test;
+
+pub mod data;
+pub mod network;
+
+/// Trains the shallow network on the CPU, with the data, targets and schedule
+/// of the `nnue` trainer. The checkpoints are saved to `checkpoints`, with a
+/// `quantised.bin` and a `network.nnue` that the engine can load.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// The data to train on.
+    #[arg(long, default_value = "resources/datasets/binpack/test80-2024-02-feb-2tb7p.min-v2.v6.binpack")]
+    data: PathBuf,
+
+    /// The format of `data`, e.g. `bullet` for the output of `datagen`.
+    #[arg(long, value_enum, default_value_t = DataFormat::default())]
+    format: DataFormat,
+
+    /// The `quantised.bin` of a checkpoint to fine-tune, instead of starting
+    /// from random weights.
+    #[arg(long)]
+    init: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 16_384)]
+    batch_size: usize,
+
+    #[arg(long, default_value_t = 6104)]
+    batches_per_superbatch: usize,
+
+    #[arg(long, default_value_t = 40)]
+    superbatches: usize,
+
+    /// The learning rate, which drops by `LR_GAMMA` every `LR_STEP`
+    /// superbatches.
+    #[arg(long, default_value_t = 0.001)]
+    lr: f32,
+
+    /// How much the game result weighs against the score in the targets.
+    #[arg(long, default_value_t = 0.75)]
+    wdl: f32,
+
+    /// Superbatches between the checkpoints.
+    #[arg(long, default_value_t = 10)]
+    save_rate: usize,
+
+    #[arg(long, default_value_t = 4)]
+    threads: usize,
+}
+
+const LR_GAMMA: f32 = 0.1;
+const LR_STEP: usize = 18;
+
+const OUTPUT_DIRECTORY: &str = "checkpoints";
+
+fn main() {
+    magics::init();
+    zobrist::init();
+
+    let args = Args::parse();
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build_global()
+        .expect("Failed to build the thread pool.");
+
+    let mut net = match &args.init {
+        Some(path) => Network::load(path).unwrap_or_else(|err| panic!("Failed to load {}: {err}", path.display())),
+        None => Network::random(&mut SmallRng::from_os_rng()),
+    };
+    let mut optimiser = AdamW::default();
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis();
+    let net_id = format!("nnue-cpu-{timestamp}-{INPUT_SIZE}-{HIDDEN_SIZE}_{SCALE}_{QA}-{QB}");
+
+    let batches = data::batches(&args.data, args.format, args.batch_size, args.wdl);
+
+    for superbatch in 1..=args.superbatches {
+        let lr = args.lr * LR_GAMMA.powi(((superbatch - 1) / LR_STEP) as i32);
+        let start = Instant::now();
+        let mut loss = 0.;
+
+        for _ in 0..args.batches_per_superbatch {
+            let batch = batches.recv().expect("The data loader stopped.");
+            let (grad, batch_loss) = net.gradient(&batch);
+            optimiser.step(&mut net, &grad, lr);
+            loss += batch_loss;
+        }
+
+        let positions = args.batches_per_superbatch * args.batch_size;
+        println!(
+            "superbatch {superbatch} | lr {lr} | loss {:.6} | {:.0} pos/s",
+            loss / args.batches_per_superbatch as f32,
+            positions as f64 / start.elapsed().as_secs_f64()
+        );
+
+        if superbatch % args.save_rate == 0 || superbatch == args.superbatches {
+            let checkpoint = Path::new(OUTPUT_DIRECTORY).join(format!("{net_id}-{superbatch}"));
+            save(&net, &checkpoint).expect("Failed to save the checkpoint.");
+        }
+    }
+}
+
+/// Saves the raw network like bullet, and the network file of the engine next
+/// to it.
+fn save(net: &Network, checkpoint: &Path) -> io::Result<()> {
+    fs::create_dir_all(checkpoint)?;
+    let layers = net.quantised();
+    fs::write(checkpoint.join("quantised.bin"), &layers)?;
+    fs::write(
+        checkpoint.join("network.nnue"),
+        // the features are the ones of the engine
+        format::encode(Architecture::Shallow, InputLayout::ENGINE, &layers, Compression::Zlib),
+    )
+}
@@ -0,0 +1,222 @@
+use std::{array, fs, io, path::Path};
+
+use engine::core::eval::nnue::{HIDDEN_SIZE, INPUT_SIZE, OUTPUT_SIZE, QA, QB, TValue};
+use rand::Rng;
+use rayon::prelude::*;
+
+/// The most pieces on a board, and thus active features of a perspective.
+pub const MAX_FEATURES: usize = 32;
+
+/// The offsets of the layers in the parameters, in the order the engine loads
+/// them from a `quantised.bin`.
+const L0W: usize = 0;
+const L0B: usize = L0W + INPUT_SIZE * HIDDEN_SIZE;
+const L1W: usize = L0B + HIDDEN_SIZE;
+const L1B: usize = L1W + OUTPUT_SIZE * 2 * HIDDEN_SIZE;
+pub const N_PARAMS: usize = L1B + OUTPUT_SIZE;
+
+/// The trainer pads the networks it saves to a multiple of this many bytes,
+/// like bullet.
+const PADDING: usize = 64;
+
+/// A position of the training data.
+#[derive(Clone, Debug)]
+pub struct Sample {
+    /// The active features of the side to move, then of the other side.
+    pub features: [[u16; MAX_FEATURES]; 2],
+    pub n_features: u8,
+    pub bucket: u8,
+    /// The blend of the game result and the sigmoid of the score, from the
+    /// side to move.
+    pub target: f32,
+}
+
+/// The `(768 -> N)x2 -> 1` network with a SCReLU on the accumulators, in
+/// floats.
+#[derive(Clone)]
+pub struct Network {
+    pub params: Vec<f32>,
+}
+
+#[inline]
+fn screlu(x: f32) -> f32 { x.clamp(0., 1.).powi(2) }
+
+#[inline]
+pub fn sigmoid(x: f32) -> f32 { 1. / (1. + (-x).exp()) }
+
+/// The quantisation of the parameter at `idx`, like the one of the `nnue`
+/// trainer.
+fn quantisation(idx: usize) -> f32 {
+    match idx {
+        ..L1W => f32::from(QA),
+        L1W..L1B => f32::from(QB),
+        _ => f32::from(QA) * f32::from(QB),
+    }
+}
+
+impl Network {
+    /// Weights uniform in `±1 / sqrt(inputs)` of their layer, and zero
+    /// biases.
+    pub fn random(rng: &mut impl Rng) -> Self {
+        let mut params = vec![0.; N_PARAMS];
+        for (range, inputs) in [(L0W..L0B, INPUT_SIZE), (L1W..L1B, 2 * HIDDEN_SIZE)] {
+            let limit = (inputs as f32).sqrt().recip();
+            params[range].iter_mut().for_each(|p| *p = rng.random_range(-limit..limit));
+        }
+        Self { params }
+    }
+
+    /// Loads the raw network of a checkpoint, such that it can be fine-tuned.
+    pub fn load(path: &Path) -> io::Result<Self> { Self::load_bytes(&fs::read(path)?) }
+
+    pub fn load_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < N_PARAMS * size_of::<TValue>() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Expected {} bytes, but the file has {}", N_PARAMS * size_of::<TValue>(), bytes.len()),
+            ));
+        }
+
+        let params = bytes
+            .chunks_exact(size_of::<TValue>())
+            .take(N_PARAMS)
+            .enumerate()
+            .map(|(idx, chunk)| f32::from(TValue::from_ne_bytes([chunk[0], chunk[1]])) / quantisation(idx))
+            .collect();
+        Ok(Self { params })
+    }
+
+    /// The network in the layout of the engine, as saved by bullet.
+    pub fn quantised(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self
+            .params
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, p)| ((p * quantisation(idx)).round() as TValue).to_ne_bytes())
+            .collect();
+        bytes.resize(bytes.len().next_multiple_of(PADDING), 0);
+        bytes
+    }
+
+    fn accumulate(&self, features: &[u16]) -> [f32; HIDDEN_SIZE] {
+        let mut acc: [f32; HIDDEN_SIZE] = array::from_fn(|i| self.params[L0B + i]);
+        for &feature in features {
+            let weights = &self.params[L0W + usize::from(feature) * HIDDEN_SIZE..][..HIDDEN_SIZE];
+            acc.iter_mut().zip(weights).for_each(|(a, w)| *a += w);
+        }
+        acc
+    }
+
+    /// The accumulators of the side to move and of the other side.
+    fn accumulators(&self, sample: &Sample) -> [[f32; HIDDEN_SIZE]; 2] {
+        let n = usize::from(sample.n_features);
+        sample.features.map(|features| self.accumulate(&features[..n]))
+    }
+
+    fn forward(&self, accs: &[[f32; HIDDEN_SIZE]; 2], bucket: usize) -> f32 {
+        let weights = &self.params[L1W + bucket * 2 * HIDDEN_SIZE..][..2 * HIDDEN_SIZE];
+        let hidden = accs.iter().flatten().map(|&a| screlu(a));
+        self.params[L1B + bucket] + hidden.zip(weights).map(|(h, w)| h * w).sum::<f32>()
+    }
+
+    /// The output of the network before the sigmoid, from the side to move.
+    pub fn output(&self, sample: &Sample) -> f32 { self.forward(&self.accumulators(sample), usize::from(sample.bucket)) }
+
+    /// Adds the gradient of the squared error of `sample` to `grad`, and
+    /// returns the error.
+    fn backprop(&self, sample: &Sample, grad: &mut [f32]) -> f32 {
+        let n = usize::from(sample.n_features);
+        let bucket = usize::from(sample.bucket);
+        let l1w = L1W + bucket * 2 * HIDDEN_SIZE;
+        let accs = self.accumulators(sample);
+
+        let out = sigmoid(self.forward(&accs, bucket));
+        let err = out - sample.target;
+        let d_out = 2. * err * out * (1. - out);
+
+        grad[L1B + bucket] += d_out;
+        for (side, (acc, features)) in accs.iter().zip(&sample.features).enumerate() {
+            let weights = &self.params[l1w + side * HIDDEN_SIZE..][..HIDDEN_SIZE];
+            let d_acc: [f32; HIDDEN_SIZE] = array::from_fn(|i| {
+                grad[l1w + side * HIDDEN_SIZE + i] += d_out * screlu(acc[i]);
+                if 0. < acc[i] && acc[i] < 1. {
+                    d_out * weights[i] * 2. * acc[i]
+                }
+                else {
+                    0.
+                }
+            });
+
+            grad[L0B..L1W].iter_mut().zip(&d_acc).for_each(|(g, d)| *g += d);
+            for &feature in &features[..n] {
+                let row = &mut grad[L0W + usize::from(feature) * HIDDEN_SIZE..][..HIDDEN_SIZE];
+                row.iter_mut().zip(&d_acc).for_each(|(g, d)| *g += d);
+            }
+        }
+
+        err * err
+    }
+
+    /// The mean gradient of the squared error over the `batch`, and the mean
+    /// error.
+    pub fn gradient(&self, batch: &[Sample]) -> (Vec<f32>, f32) {
+        let chunk_size = batch.len().div_ceil(rayon::current_num_threads()).max(1);
+        let (mut grad, loss) = batch
+            .par_chunks(chunk_size)
+            .map(|chunk| {
+                let mut grad = vec![0.; N_PARAMS];
+                let loss = chunk.iter().map(|sample| self.backprop(sample, &mut grad)).sum::<f32>();
+                (grad, loss)
+            })
+            .reduce(
+                || (vec![0.; N_PARAMS], 0.),
+                |(mut a, loss_a), (b, loss_b)| {
+                    a.iter_mut().zip(&b).for_each(|(a, b)| *a += b);
+                    (a, loss_a + loss_b)
+                },
+            );
+
+        let n = batch.len() as f32;
+        grad.iter_mut().for_each(|g| *g /= n);
+        (grad, loss / n)
+    }
+}
+
+/// AdamW with the default settings of bullet, which clips the parameters such
+/// that they still fit their quantisation.
+pub struct AdamW {
+    momentum: Vec<f32>,
+    velocity: Vec<f32>,
+}
+
+impl Default for AdamW {
+    fn default() -> Self {
+        Self {
+            momentum: vec![0.; N_PARAMS],
+            velocity: vec![0.; N_PARAMS],
+        }
+    }
+}
+
+impl AdamW {
+    const BETA1: f32 = 0.9;
+    const BETA2: f32 = 0.999;
+    const DECAY: f32 = 0.01;
+    const EPSILON: f32 = 1e-8;
+    const CLIP: f32 = 1.98;
+
+    pub fn step(&mut self, net: &mut Network, grad: &[f32], lr: f32) {
+        net.params
+            .par_iter_mut()
+            .zip(&mut self.momentum)
+            .zip(&mut self.velocity)
+            .zip(grad)
+            .for_each(|(((p, m), v), g)| {
+                *m = Self::BETA1 * *m + (1. - Self::BETA1) * g;
+                *v = Self::BETA2 * *v + (1. - Self::BETA2) * g * g;
+                *p *= 1. - lr * Self::DECAY;
+                *p -= lr * *m / (v.sqrt() + Self::EPSILON);
+                *p = p.clamp(-Self::CLIP, Self::CLIP);
+            });
+    }
+}
@@ -0,0 +1,141 @@
+use engine::core::{
+    color::colors,
+    eval::nnue::{
+        self, Architecture,
+        buckets::InputLayout,
+        format::{self, Compression},
+    },
+    move_iter::sliding_piece::magics,
+    position::Position,
+    search::id::NnueEvaluator,
+    zobrist,
+};
+use rand::{SeedableRng, rngs::SmallRng};
+
+use crate::{
+    data,
+    network::{MAX_FEATURES, N_PARAMS, Network, Sample},
+};
+
+const FENS: [&str; 3] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/pp1n1ppp/2p1pn2/q7/1bPP4/2N1PN2/PP1B1PPP/R2QKB1R b KQkq - 3 9",
+    "8/5pk1/6p1/8/3R4/6P1/5PK1/1r6 w - - 0 40",
+];
+
+fn samples() -> Vec<Sample> {
+    magics::init();
+    zobrist::init();
+
+    FENS.iter()
+        .zip([(40, 1), (-250, -1), (0, 0)])
+        .map(|(fen, (score, result))| data::sample(&Position::from_fen(fen).unwrap(), score, result, 0.75))
+        .collect()
+}
+
+#[test]
+fn targets_blend_result_and_score() {
+    let samples = samples();
+    // a draw with an even score
+    assert_eq!(samples[2].target, 0.5);
+    assert!(samples[0].target > 0.875 && samples[0].target < 1.);
+    assert!(samples[1].target > 0. && samples[1].target < 0.125);
+    assert_eq!(samples[0].n_features, 32);
+}
+
+#[test]
+fn gradient_matches_finite_differences() {
+    let samples = samples();
+    let net = Network::random(&mut SmallRng::seed_from_u64(0));
+    let (grad, _) = net.gradient(&samples);
+
+    let loss = |net: &Network| net.gradient(&samples).1;
+    let eps = 1e-3;
+    // the biggest gradients, which are large enough to compare
+    let mut params: Vec<usize> = (0..N_PARAMS).collect();
+    params.sort_by(|&a, &b| grad[b].abs().total_cmp(&grad[a].abs()));
+    for &idx in params.iter().take(20) {
+        let mut up = net.clone();
+        up.params[idx] += eps;
+        let mut down = net.clone();
+        down.params[idx] -= eps;
+        let numeric = (loss(&up) - loss(&down)) / (2. * eps);
+        assert!(
+            (numeric - grad[idx]).abs() < 1e-2 * grad[idx].abs().max(1e-3),
+            "{idx}: {numeric} vs {}",
+            grad[idx]
+        );
+    }
+}
+
+#[test]
+fn quantised_network_matches_the_engine() {
+    let samples = samples();
+    let net = Network::random(&mut SmallRng::seed_from_u64(1));
+    let engine_net = nnue::load_nnue(&format::encode(
+        Architecture::Shallow,
+        InputLayout::ENGINE,
+        &net.quantised(),
+        Compression::None,
+    ))
+    .unwrap();
+
+    for (fen, sample) in FENS.iter().zip(&samples) {
+        let pos = Position::from_fen(fen).unwrap();
+        let trace = NnueEvaluator::<nnue::LazyAccUpdates>::new(engine_net.clone()).trace(&pos);
+        let raw = if pos.get_turn() == colors::WHITE {
+            trace.raw
+        }
+        else {
+            -trace.raw
+        };
+        let output = net.output(sample);
+        assert!((raw - output).abs() < 0.02, "{fen}: {raw} vs {output}");
+    }
+
+    let loaded = Network::load_bytes(&net.quantised()).unwrap();
+    for sample in &samples {
+        assert!((loaded.output(sample) - net.output(sample)).abs() < 0.02);
+    }
+}
+
+#[test]
+fn bullet_records_match_their_positions() {
+    magics::init();
+    zobrist::init();
+
+    let record = |occ: u64, pcs: &[u8], score: i16, result: u8| {
+        let mut record = [0; data::BULLET_RECORD_SIZE];
+        record[0..8].copy_from_slice(&occ.to_le_bytes());
+        record[8..8 + pcs.len()].copy_from_slice(pcs);
+        record[24..26].copy_from_slice(&score.to_le_bytes());
+        record[26] = result;
+        record
+    };
+    // the start position as `datagen` writes it, won by white
+    let start = record(
+        0xFFFF_0000_0000_FFFF,
+        &[0x13, 0x42, 0x25, 0x31, 0, 0, 0, 0, 0x88, 0x88, 0x88, 0x88, 0x9B, 0xCA, 0xAD, 0xB9],
+        25,
+        2,
+    );
+    // black to move is flipped with its colors swapped, lost by black
+    let black = record(1 << 4 | 1 << 56 | 1 << 60, &[0xB5, 0x0D], -30, 0);
+
+    for (record, fen, labels) in [(start, FENS[0], (25, 1)), (black, "4k3/8/8/8/8/8/8/R3K3 b - - 0 1", (-30, -1))] {
+        let (pos, score, result) = data::bullet_position(&record);
+        assert_eq!((score, result), labels);
+        let read = data::sample(&pos, score, result, 0.75);
+        let expected = data::sample(&Position::from_fen(fen).unwrap(), score, result, 0.75);
+        // the same features, in the order of other squares
+        let sorted = |mut features: [[u16; MAX_FEATURES]; 2]| {
+            features.iter_mut().for_each(|features| features.sort());
+            features
+        };
+        assert_eq!(
+            (sorted(read.features), read.n_features, read.bucket, read.target),
+            (sorted(expected.features), expected.n_features, expected.bucket, expected.target),
+            "{fen}"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use clap::ValueEnum;
+use engine::{
+    core::{
+        color::{colors, perspectives},
+        depth::Depth,
+        eval::{StaticEvaluator, hce::TaperValue},
+        r#move::Move,
+        params::C_IdHceParams,
+        position::{CheckState, EpdLineImport, EpdLineParseError, EpdOp, PieceInfoObserver, Position},
+        search::{data::TTMove, id, quiesce::QSearcher, score::Score, tree::node_types},
+    },
+    uci::tokens::Tokenizer,
+};
+use thiserror::Error;
+
+use crate::tuner;
+
+/// Entries of the transposition table of the q-search that resolves the
+/// positions.
+const RESOLVER_TT_ENTRIES: usize = 1 << 12;
+
+/// The principal variation of the q-search is followed for at most this many
+/// plies.
+const MAX_PV_LENGTH: usize = 32;
+
+#[derive(Debug, Error)]
+pub enum DataError {
+    #[error("Failed to parse the position: {0}")]
+    Position(#[from] EpdLineParseError),
+
+    #[error("Invalid result `{0}`, expected 1-0, 0-1, 1/2-1/2 or a number in [0, 1]")]
+    Result(String),
+
+    #[error("Invalid score `{0}`, expected centipawns")]
+    Score(String),
+
+    #[error("The position has no {0:?} label")]
+    MissingLabel(LabelKind),
+}
+
+/// Which label of a position the evaluation is fitted to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LabelKind {
+    /// The result of the game the position is from.
+    Result,
+    /// The score of a search, e.g. from datagen.
+    Score,
+}
+
+/// The labels of a position, relative to white.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Labels {
+    pub result: Option<f64>,
+    pub score: Option<i32>,
+}
+
+impl Labels {
+    /// The expected result for white. Scores are mapped to results with a
+    /// scaling constant of 1, where 400 cp mean 10:1 odds.
+    pub fn target(&self, kind: LabelKind) -> Result<f64, DataError> {
+        match kind {
+            LabelKind::Result => self.result,
+            LabelKind::Score => self.score.map(|score| tuner::sigmoid(score as f64, 1.)),
+        }
+        .ok_or(DataError::MissingLabel(kind))
+    }
+}
+
+/// Parses a labeled position in one of the formats
+///
+/// - `<fen> | <score> | <result>`, the text format of bullet,
+/// - `<fen> [<result>]`,
+/// - `<epd> c9 "<result>"; ce <score>;`, where the score is relative to the
+///   side to move as usual.
+///
+/// Results are either `1-0`, `0-1` and `1/2-1/2` or the score of white.
+pub fn parse_line(line: &str) -> Result<(Position, Labels), DataError> {
+    if let Some((fen, rest)) = line.split_once('|') {
+        let (score, result) = rest.split_once('|').ok_or(DataError::MissingLabel(LabelKind::Result))?;
+        let labels = Labels {
+            result: Some(parse_result(result)?),
+            score: Some(parse_score(score)?),
+        };
+        return Ok((parse_position(fen)?, labels));
+    }
+
+    if let Some((fen, rest)) = line.split_once('[') {
+        let result = rest.trim_end().strip_suffix(']').ok_or_else(|| DataError::Result(rest.to_string()))?;
+        let labels = Labels {
+            result: Some(parse_result(result)?),
+            score: None,
+        };
+        return Ok((parse_position(fen)?, labels));
+    }
+
+    let (pos, ops): (Position, Vec<EpdOp>) = EpdLineImport(&mut Tokenizer::new(line)).try_into()?;
+    let mut labels = Labels::default();
+    for EpdOp(code, arg) in ops {
+        match code.as_str() {
+            "c9" => labels.result = Some(parse_result(&arg)?),
+            "ce" => {
+                let score = parse_score(&arg)?;
+                labels.score = Some(if pos.get_turn() == colors::WHITE { score } else { -score });
+            }
+            _ => {}
+        }
+    }
+    Ok((pos, labels))
+}
+
+fn parse_position(fen: &str) -> Result<Position, DataError> {
+    let (pos, _): (Position, Vec<EpdOp>) = EpdLineImport(&mut Tokenizer::new(fen.trim())).try_into()?;
+    Ok(pos)
+}
+
+fn parse_result(s: &str) -> Result<f64, DataError> {
+    match s.trim().trim_matches('"') {
+        "1-0" => Ok(1.),
+        "0-1" => Ok(0.),
+        "1/2-1/2" => Ok(0.5),
+        other => other
+            .parse::<f64>()
+            .ok()
+            .filter(|result| (0. ..=1.).contains(result))
+            .ok_or_else(|| DataError::Result(s.to_string())),
+    }
+}
+
+fn parse_score(s: &str) -> Result<i32, DataError> { s.trim().parse().map_err(|_| DataError::Score(s.to_string())) }
+
+/// Plays out the principal variation of a q-search, such that the tuner only
+/// sees quiet positions.
+pub struct Resolver {
+    tt: id::TT,
+    eval: id::HceEvaluator,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self {
+            tt: id::TT::new(RESOLVER_TT_ENTRIES),
+            eval: id::HceEvaluator::default(),
+        }
+    }
+}
+
+impl Resolver {
+    /// Resolves `pos` in place. Returns `false` if the resolved position is
+    /// still in check and thus not quiet.
+    pub fn resolve(&mut self, pos: &mut Position) -> bool {
+        self.tt.clear();
+        self.eval.observe_forward().on_init(pos.piece_info());
+
+        let phase = TaperValue::from_position(pos.piece_info());
+        let mut ss = id::SS::from(vec![id::SearchEntry { phase, ..Default::default() }]);
+        let mut qsearcher = QSearcher::new(pos, &mut self.tt, &mut ss, pos.ply());
+        match pos.get_turn().v() {
+            colors::WHITE_C => {
+                qsearcher.go::<perspectives::White, node_types::Pv>(pos, -Score::INF, Score::INF, C_IdHceParams, &mut self.eval, Depth::new(30));
+            }
+            colors::BLACK_C => {
+                qsearcher.go::<perspectives::Black, node_types::Pv>(pos, -Score::INF, Score::INF, C_IdHceParams, &mut self.eval, Depth::new(30));
+            }
+            _ => unreachable!(),
+        }
+
+        // the best move of every node of the principal variation is in the tt
+        for _ in 0..MAX_PV_LENGTH {
+            let Some(mov) = self.tt.get(pos.get_key()).map(|entry| entry.mov())
+            else {
+                break;
+            };
+            if mov == Move::null() || !pos.is_pseudo_legal(mov) || !pos.is_legal(mov) {
+                break;
+            }
+            pos.make_move(mov, &mut ());
+        }
+
+        pos.get_check_state() == CheckState::None
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use clap::Parser;
+use engine::core::{move_iter::sliding_piece::magics, params::HceParamsGroup, zobrist};
+use rayon::prelude::*;
+
+use crate::{
+    data::{DataError, LabelKind, Resolver},
+    trace::Trace,
+    tuner::Tuner,
+    weights::Weights,
+};
+
+#[cfg(test)] pub mod test;
+
+pub mod data;
+pub mod trace;
+pub mod tuner;
+pub mod weights;
+
+/// Tunes the weights of the hand-crafted evaluation on labeled positions and
+/// prints them as Rust code.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Files with one labeled position per line.
+    #[arg(required = true)]
+    datasets: Vec<PathBuf>,
+
+    /// Which label of the positions to fit.
+    #[arg(short, long, value_enum, default_value_t = LabelKind::Result)]
+    label: LabelKind,
+
+    /// Number of Adam steps over all positions.
+    #[arg(short, long, default_value_t = 2000)]
+    epochs: usize,
+
+    /// Learning rate of Adam, in centipawns.
+    #[arg(long, default_value_t = 1.)]
+    lr: f64,
+
+    /// Epochs between the progress reports and the tuning of the phase
+    /// weights.
+    #[arg(long, default_value_t = 50)]
+    report_interval: usize,
+}
+
+fn main() {
+    magics::init();
+    zobrist::init();
+
+    let args = Args::parse();
+
+    let lines = args
+        .datasets
+        .iter()
+        .flat_map(|path| {
+            let content = fs::read_to_string(path).unwrap_or_else(|err| panic!("Failed to read {}: {err}", path.display()));
+            content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let invalid = AtomicUsize::new(0);
+    let traces = lines
+        .par_iter()
+        .map_init(
+            || (Resolver::default(), weights::default_hce_params()),
+            |(resolver, params), line| match load(line, args.label, resolver, params) {
+                Ok(trace) => trace,
+                Err(err) => {
+                    if invalid.fetch_add(1, Ordering::Relaxed) < 10 {
+                        eprintln!("Skipping `{line}`: {err}");
+                    }
+                    None
+                }
+            },
+        )
+        .flatten()
+        .collect::<Vec<_>>();
+    let invalid = invalid.into_inner();
+    eprintln!(
+        "Loaded {} of {} positions, {invalid} were invalid and {} not quiet.",
+        traces.len(),
+        lines.len(),
+        lines.len() - invalid - traces.len()
+    );
+    assert!(!traces.is_empty(), "No positions to tune on.");
+
+    let mut tuner = Tuner::new(&traces, Weights::default(), args.lr);
+    if args.label == LabelKind::Result {
+        tuner.fit_k();
+    }
+    eprintln!("k = {:.4}, initial loss = {:.6}", tuner.k(), tuner.loss());
+
+    for epoch in 1..=args.epochs {
+        tuner.step();
+        if epoch % args.report_interval == 0 {
+            tuner.tune_phases();
+            eprintln!("epoch {epoch}: loss = {:.6}", tuner.loss());
+        }
+    }
+
+    println!("{}", tuner.weights());
+}
+
+/// Parses, resolves and traces a position. Positions that are in check after
+/// resolving them are skipped.
+fn load(line: &str, label: LabelKind, resolver: &mut Resolver, params: &mut HceParamsGroup) -> Result<Option<Trace>, DataError> {
+    let (mut pos, labels) = data::parse_line(line)?;
+    let target = labels.target(label)?;
+    Ok(resolver.resolve(&mut pos).then(|| Trace::new(&pos, params, target)))
+}
@@ -0,0 +1,137 @@
+use engine::core::{
+    color::{colors, perspectives::White},
+    eval::{
+        hce::{self, TaperValue},
+        pawns::PawnEntry,
+    },
+    move_iter::sliding_piece::magics,
+    params::HceParamsGroup,
+    piece::piece_type,
+    position::Position,
+    zobrist,
+};
+
+use crate::{
+    data::{self, LabelKind, Labels, Resolver},
+    trace::Trace,
+    tuner::Tuner,
+    weights::{self, HCE, Weights},
+};
+
+const FENS: [&str; 4] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 8",
+    "2r3k1/5ppp/p3p3/1p6/3R4/P3P3/1P3PPP/6K1 b - - 0 28",
+    "8/5k2/3p4/1p1P4/1P2K3/8/8/8 w - - 0 50",
+];
+
+fn init() {
+    magics::init();
+    zobrist::init();
+}
+
+/// White's score of `pos` by the engine.
+fn engine_eval(pos: &Position, params: &HceParamsGroup) -> i32 {
+    let pieces = pos.piece_info();
+    let pawns = PawnEntry::new(pieces, pos.get_pawn_key(), params);
+    let phase = TaperValue::from_position(pieces);
+    hce::eval::<White>(pieces, pos.get_turn(), pos.get_ep_target_square(), phase, &pawns, params)
+        .0
+        .v()
+}
+
+#[test]
+fn parse_labels() {
+    init();
+
+    let (_, labels) = data::parse_line("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 | 120 | 1.0").unwrap();
+    assert_eq!(
+        labels,
+        Labels {
+            result: Some(1.),
+            score: Some(120)
+        }
+    );
+
+    let (_, labels) = data::parse_line("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 [0.5]").unwrap();
+    assert_eq!(labels, Labels { result: Some(0.5), score: None });
+
+    // the centipawn evaluation of epds is relative to the side to move
+    let (pos, labels) = data::parse_line(r#"4k3/8/8/8/8/8/4P3/4K3 b - - c9 "1-0"; ce -90;"#).unwrap();
+    assert_eq!(pos.get_turn(), colors::BLACK);
+    assert_eq!(
+        labels,
+        Labels {
+            result: Some(1.),
+            score: Some(90)
+        }
+    );
+
+    assert!(labels.target(LabelKind::Score).unwrap() > 0.5);
+    assert!(data::parse_line("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 [2]").is_err());
+    assert!(
+        data::parse_line("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1")
+            .unwrap()
+            .1
+            .target(LabelKind::Result)
+            .is_err()
+    );
+}
+
+#[test]
+fn resolve_captures() {
+    init();
+
+    // the hanging queen is taken
+    let (mut pos, _) = data::parse_line("4k3/8/8/3q4/8/8/8/3QK3 w - - 0 1 [1.0]").unwrap();
+    assert!(Resolver::default().resolve(&mut pos));
+    assert!(pos.get_bitboard(piece_type::QUEEN, colors::BLACK).is_empty());
+}
+
+#[test]
+fn trace_matches_engine_eval() {
+    init();
+
+    let mut params = weights::default_hce_params();
+    let weights = Weights::default();
+    let no_delta = vec![0.; weights.values.len()];
+    let rook_open_file_mg = weights::hce_names().iter().position(|&name| name == "rook_open_file_mg").unwrap();
+
+    for fen in FENS {
+        let pos = Position::from_fen(fen).unwrap();
+        let trace = Trace::new(&pos, &mut params, 0.5);
+
+        // up to the rounding of the tapering of each term
+        let expected = engine_eval(&pos, &params);
+        assert!((trace.eval(&no_delta, &weights.phases) - expected as f64).abs() <= 4., "{fen}");
+
+        // the terms are linear in their weights
+        let mut delta = no_delta.clone();
+        delta[HCE + rook_open_file_mg] = 10.;
+        weights::add_to_hce_param(&mut params, rook_open_file_mg, 10);
+        let expected = engine_eval(&pos, &params);
+        weights::add_to_hce_param(&mut params, rook_open_file_mg, -10);
+        assert!((trace.eval(&delta, &weights.phases) - expected as f64).abs() <= 4., "{fen}");
+    }
+}
+
+#[test]
+fn tuning_reduces_loss() {
+    init();
+
+    let mut params = weights::default_hce_params();
+    let traces = FENS
+        .iter()
+        .zip([0.5, 1., 0., 0.5])
+        .map(|(fen, target)| Trace::new(&Position::from_fen(fen).unwrap(), &mut params, target))
+        .collect::<Vec<_>>();
+
+    let mut tuner = Tuner::new(&traces, Weights::default(), 1.);
+    tuner.fit_k();
+    let initial = tuner.loss();
+    for _ in 0..50 {
+        tuner.step();
+    }
+    assert!(tuner.loss() < initial);
+    assert_eq!(tuner.weights().values.len(), Weights::default().values.len());
+}
@@ -0,0 +1,112 @@
+use engine::core::{
+    color::{colors, perspectives::White},
+    eval::{
+        hce::{self, TaperValue, piece_phases},
+        pawns::PawnEntry,
+    },
+    params::HceParamsGroup,
+    piece::piece_type,
+    position::Position,
+};
+
+use crate::weights::{self, HCE, MATERIAL, PHASE_PIECES, PSQT_EG, PSQT_MG};
+
+/// The evaluation of a position, as a linear function of the weights.
+#[derive(Clone, Debug)]
+pub struct Trace {
+    /// Number of the [PHASE_PIECES] on the board.
+    pub phase_pieces: [u8; 4],
+    /// Middle and end game evaluation of the engine, relative to white.
+    pub mg: f64,
+    pub eg: f64,
+    /// `(weight, mg, eg)`: how much the middle and end game evaluation change
+    /// per unit of the weight.
+    pub coefs: Vec<(u32, i32, i32)>,
+    /// The expected result for white.
+    pub target: f64,
+}
+
+impl Trace {
+    /// Traces the evaluation of `pos`. The coefficients of the hce params are
+    /// found by changing them one at a time, which is exact for all terms but
+    /// the king attack.
+    pub fn new(pos: &Position, params: &mut HceParamsGroup, target: f64) -> Self {
+        let pieces = pos.piece_info();
+        let mut coefs = vec![];
+
+        for pt in piece_type::PAWN..=piece_type::QUEEN {
+            let diff = pieces.get_bitboard(pt, colors::WHITE).pop_cnt() as i32 - pieces.get_bitboard(pt, colors::BLACK).pop_cnt() as i32;
+            if diff != 0 {
+                coefs.push(((MATERIAL + pt.v() as usize - 1) as u32, diff, diff));
+            }
+        }
+
+        for (color, sign) in [(colors::WHITE, 1), (colors::BLACK, -1)] {
+            for pt in piece_type::PAWN..=piece_type::KING {
+                for sq in pieces.get_bitboard(pt, color) {
+                    let idx = weights::psqt_index(pt, sq, color);
+                    coefs.push(((PSQT_MG + idx) as u32, sign, 0));
+                    coefs.push(((PSQT_EG + idx) as u32, 0, sign));
+                }
+            }
+        }
+
+        let (mg, eg) = eval(pos, params);
+        for i in 0..weights::hce_names().len() {
+            weights::add_to_hce_param(params, i, 1);
+            let (mg_i, eg_i) = eval(pos, params);
+            weights::add_to_hce_param(params, i, -1);
+
+            if (mg_i, eg_i) != (mg, eg) {
+                coefs.push(((HCE + i) as u32, mg_i - mg, eg_i - eg));
+            }
+        }
+
+        Self {
+            phase_pieces: PHASE_PIECES.map(|pt| pieces.get_piece_bb(pt).pop_cnt() as u8),
+            mg: mg as f64,
+            eg: eg as f64,
+            coefs,
+            target,
+        }
+    }
+
+    /// The evaluation with `delta` added to the weights the trace was made
+    /// with.
+    pub fn eval(&self, delta: &[f64], phases: &[i32; 4]) -> f64 {
+        let (mut mg, mut eg) = (self.mg, self.eg);
+        for &(i, coef_mg, coef_eg) in &self.coefs {
+            let d = delta[i as usize];
+            mg += coef_mg as f64 * d;
+            eg += coef_eg as f64 * d;
+        }
+
+        let eg_weight = self.eg_weight(phases);
+        mg * (1. - eg_weight) + eg * eg_weight
+    }
+
+    /// How much the end game evaluation is weighted, like [TaperValue] but for
+    /// any phase weights.
+    pub fn eg_weight(&self, phases: &[i32; 4]) -> f64 {
+        // the phase weights of the starting position
+        let total = 4 * (phases[0] + phases[1] + phases[2]) + 2 * phases[3];
+        if total <= 0 {
+            return 0.;
+        }
+
+        let material: i32 = self.phase_pieces.iter().zip(phases).map(|(&n, &w)| n as i32 * w).sum();
+        (total - material).clamp(0, total) as f64 / total as f64
+    }
+}
+
+/// The middle and end game evaluation of the engine, relative to white.
+fn eval(pos: &Position, params: &HceParamsGroup) -> (i32, i32) {
+    let pieces = pos.piece_info();
+    let pawns = PawnEntry::new(pieces, pos.get_pawn_key(), params);
+    let [mg, eg] = [0, piece_phases::TOTAL_C].map(|phase| {
+        hce::eval::<White>(pieces, pos.get_turn(), pos.get_ep_target_square(), TaperValue::new(phase), &pawns, params)
+            .0
+            .v()
+    });
+    (mg, eg)
+}
@@ -0,0 +1,167 @@
+use std::f64::consts::LN_10;
+
+use rayon::prelude::*;
+
+use crate::{trace::Trace, weights::Weights};
+
+/// The expected result of a position with the evaluation `score`.
+pub fn sigmoid(score: f64, k: f64) -> f64 { 1. / (1. + 10f64.powf(-k * score / 400.)) }
+
+/// Fits the weights to the targets of the traces by minimizing the mean
+/// squared error of the expected results.
+pub struct Tuner<'a> {
+    traces: &'a [Trace],
+    initial: Weights,
+    /// The change of the weights since the traces were made.
+    delta: Vec<f64>,
+    phases: [i32; 4],
+    /// Scaling constant of the [sigmoid].
+    k: f64,
+    adam: Adam,
+}
+
+impl<'a> Tuner<'a> {
+    pub fn new(traces: &'a [Trace], initial: Weights, lr: f64) -> Self {
+        let n = initial.values.len();
+        Self {
+            traces,
+            phases: initial.phases,
+            initial,
+            delta: vec![0.; n],
+            k: 1.,
+            adam: Adam::new(n, lr),
+        }
+    }
+
+    pub fn k(&self) -> f64 { self.k }
+
+    pub fn weights(&self) -> Weights {
+        Weights {
+            values: self.initial.values.iter().zip(&self.delta).map(|(w, d)| w + d).collect(),
+            phases: self.phases,
+        }
+    }
+
+    pub fn loss(&self) -> f64 { self.loss_with(&self.phases, self.k) }
+
+    fn loss_with(&self, phases: &[i32; 4], k: f64) -> f64 {
+        let sum: f64 = self
+            .traces
+            .par_iter()
+            .map(|trace| (trace.target - sigmoid(trace.eval(&self.delta, phases), k)).powi(2))
+            .sum();
+        sum / self.traces.len() as f64
+    }
+
+    /// Finds the scaling constant that fits the current evaluation best, by
+    /// ternary search. Only meaningful if the targets are game results.
+    pub fn fit_k(&mut self) {
+        let (mut lo, mut hi) = (0.01, 10.);
+        while hi - lo > 1e-4 {
+            let m1 = lo + (hi - lo) / 3.;
+            let m2 = hi - (hi - lo) / 3.;
+            if self.loss_with(&self.phases, m1) < self.loss_with(&self.phases, m2) {
+                hi = m2;
+            }
+            else {
+                lo = m1;
+            }
+        }
+        self.k = (lo + hi) / 2.;
+    }
+
+    /// Gradient of the loss by the weights.
+    fn gradient(&self) -> Vec<f64> {
+        let n = self.delta.len();
+        let scale = self.k * LN_10 / 400.;
+        let mut grad = self
+            .traces
+            .par_iter()
+            .fold(
+                || vec![0.; n],
+                |mut grad, trace| {
+                    let eg_weight = trace.eg_weight(&self.phases);
+                    let p = sigmoid(trace.eval(&self.delta, &self.phases), self.k);
+                    let d_eval = 2. * (p - trace.target) * p * (1. - p) * scale;
+                    for &(i, coef_mg, coef_eg) in &trace.coefs {
+                        grad[i as usize] += d_eval * (coef_mg as f64 * (1. - eg_weight) + coef_eg as f64 * eg_weight);
+                    }
+                    grad
+                },
+            )
+            .reduce(
+                || vec![0.; n],
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                    a
+                },
+            );
+
+        let len = self.traces.len() as f64;
+        grad.iter_mut().for_each(|g| *g /= len);
+        grad
+    }
+
+    /// One step of Adam over all traces.
+    pub fn step(&mut self) {
+        let grad = self.gradient();
+        self.adam.step(&mut self.delta, &grad);
+    }
+
+    /// One pass of coordinate descent over the phase weights, which are
+    /// integers and thus not tuned by Adam. Returns whether any changed.
+    pub fn tune_phases(&mut self) -> bool {
+        let mut improved = false;
+        let mut best = self.loss();
+        for i in 0..self.phases.len() {
+            for delta in [1, -1] {
+                let mut phases = self.phases;
+                phases[i] += delta;
+                if phases[i] < 0 {
+                    continue;
+                }
+
+                let loss = self.loss_with(&phases, self.k);
+                if loss < best {
+                    (best, self.phases, improved) = (loss, phases, true);
+                    break;
+                }
+            }
+        }
+        improved
+    }
+}
+
+struct Adam {
+    lr: f64,
+    m: Vec<f64>,
+    v: Vec<f64>,
+    t: i32,
+}
+
+impl Adam {
+    const BETA1: f64 = 0.9;
+    const BETA2: f64 = 0.999;
+    const EPSILON: f64 = 1e-8;
+
+    fn new(n: usize, lr: f64) -> Self {
+        Self {
+            lr,
+            m: vec![0.; n],
+            v: vec![0.; n],
+            t: 0,
+        }
+    }
+
+    fn step(&mut self, weights: &mut [f64], grad: &[f64]) {
+        self.t += 1;
+        let m_correction = 1. - Self::BETA1.powi(self.t);
+        let v_correction = 1. - Self::BETA2.powi(self.t);
+
+        for (((w, g), m), v) in weights.iter_mut().zip(grad).zip(&mut self.m).zip(&mut self.v) {
+            *m = Self::BETA1 * *m + (1. - Self::BETA1) * g;
+            *v = Self::BETA2 * *v + (1. - Self::BETA2) * g * g;
+            *w -= self.lr * (*m / m_correction) / ((*v / v_correction).sqrt() + Self::EPSILON);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+
+use engine::core::{
+    color::{Color, colors},
+    coordinates::{Square, squares},
+    eval::hce::{game_phases, piece_phase, piece_score, psqt_score},
+    params::{C_IdHceParams, HceParamsConfigGroup, HceParamsGroup},
+    piece::{PieceType, piece_type},
+};
+use itertools::Itertools;
+
+/// Number of piece types with a material value, pawn to queen.
+pub const N_MATERIAL: usize = 5;
+/// Number of piece types with a psqt, pawn to king.
+pub const N_PSQT: usize = 6;
+
+/// Offsets of the weight groups in [Weights::values].
+pub const MATERIAL: usize = 0;
+pub const PSQT_MG: usize = MATERIAL + N_MATERIAL;
+pub const PSQT_EG: usize = PSQT_MG + N_PSQT * squares::N_VARIANTS;
+pub const HCE: usize = PSQT_EG + N_PSQT * squares::N_VARIANTS;
+
+/// The piece types that count towards the game phase.
+pub const PHASE_PIECES: [PieceType; 4] = [piece_type::KNIGHT, piece_type::BISHOP, piece_type::ROOK, piece_type::QUEEN];
+
+const PSQT_NAMES: [&str; N_PSQT] = ["PAWN", "KNIGHT", "BISHOP", "ROOK", "QUEEN", "KING"];
+
+/// All weights of the hand-crafted evaluation that are tuned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weights {
+    /// The material values, the middle and end game psqts and the hce params.
+    pub values: Vec<f64>,
+    /// The game phase weights of the [PHASE_PIECES].
+    pub phases: [i32; 4],
+}
+
+impl Default for Weights {
+    /// The weights the engine currently uses.
+    fn default() -> Self {
+        let mut values = Vec::with_capacity(HCE);
+        values.extend((piece_type::PAWN..=piece_type::QUEEN).map(|pt| piece_score(pt).v() as f64));
+        for phase in [game_phases::MG, game_phases::EG] {
+            for pt in piece_type::PAWN..=piece_type::KING {
+                // the tables are indexed from the point of view of black
+                values.extend((squares::A1..=squares::H8).map(|sq| psqt_score(phase, pt, sq, colors::BLACK).v() as f64));
+            }
+        }
+        default_hce_params().visit_fields_mut(|_, field| values.push(*as_i32(field) as f64));
+
+        Self {
+            values,
+            phases: PHASE_PIECES.map(|pt| piece_phase(pt).v()),
+        }
+    }
+}
+
+impl fmt::Display for Weights {
+    /// Prints the weights as Rust code that can be pasted into the engine.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let round = |i: usize| self.values[i].round() as i32;
+
+        let material = (MATERIAL..MATERIAL + N_MATERIAL).map(round).join(", ");
+        writeln!(f, "const PIECE_SCORES: [i32; piece_type::N_VARIANTS] = [0, {material}, 0];")?;
+
+        for (offset, prefix) in [(PSQT_MG, "MG"), (PSQT_EG, "EG")] {
+            for (table, name) in PSQT_NAMES.iter().enumerate() {
+                let start = offset + table * squares::N_VARIANTS;
+                writeln!(f)?;
+                writeln!(f, "#[rustfmt::skip]")?;
+                writeln!(f, "const {prefix}_{name}_TABLE: Psqt = Psqt([")?;
+                for rank in (start..start + squares::N_VARIANTS).chunks(8).into_iter() {
+                    writeln!(f, "  {}", rank.map(|i| format!("{:>5},", round(i))).join(""))?;
+                }
+                writeln!(f, "]);")?;
+            }
+        }
+
+        writeln!(f)?;
+        writeln!(f, "// piece_phases")?;
+        for (pt, phase) in ["KNIGHT", "BISHOP", "ROOK", "QUEEN"].iter().zip(self.phases) {
+            writeln!(f, "{pt} = {phase},")?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "// hce: HceParams")?;
+        for (i, name) in hce_names().into_iter().enumerate() {
+            writeln!(f, "fn {name}(&self) -> i32 {{ {} }}", round(HCE + i))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Index of `pt` on `sq` of `color` in the psqts.
+pub fn psqt_index(pt: PieceType, sq: Square, color: Color) -> usize {
+    let sq = if color == colors::WHITE { sq.flip_v() } else { sq };
+    (pt.v() as usize - 1) * squares::N_VARIANTS + sq.index()
+}
+
+/// The hce params of the hce engine.
+pub fn default_hce_params() -> HceParamsGroup {
+    let mut config = HceParamsConfigGroup::default_builder();
+    config.seed_from(&C_IdHceParams);
+    config.extract_params()
+}
+
+pub fn hce_names() -> Vec<&'static str> {
+    let mut names = vec![];
+    default_hce_params().visit_fields_mut(|name, _| names.push(name));
+    names
+}
+
+/// Adds `delta` to the `idx`th hce param.
+pub fn add_to_hce_param(params: &mut HceParamsGroup, idx: usize, delta: i32) {
+    let mut i = 0;
+    params.visit_fields_mut(|_, field| {
+        if i == idx {
+            *as_i32(field) += delta;
+        }
+        i += 1;
+    });
+}
+
+fn as_i32(field: &mut dyn std::any::Any) -> &mut i32 { field.downcast_mut().expect("All hce params are integers.") }